@@ -0,0 +1,172 @@
+use image::{imageops, GrayImage, Rgba, RgbaImage};
+use rustfft::{num_complex::Complex, FftPlanner};
+
+// Images are downscaled to at most this size for alignment, which is plenty for finding drift
+const ALIGN_SIZE: u32 = 512;
+// Angles the magnitude spectrum is sampled at for finding the rotation, over half a turn as it is symmetric
+const ROTATION_STEPS: usize = 360;
+
+// In-place 2D FFT over a row-major buffer
+fn fft2d(data: &mut [Complex<f32>], width: usize, height: usize, inverse: bool) {
+    let mut planner = FftPlanner::new();
+    let row_fft = if inverse { planner.plan_fft_inverse(width) } else { planner.plan_fft_forward(width) };
+    for row in data.chunks_mut(width) {
+        row_fft.process(row);
+    }
+
+    let column_fft = if inverse { planner.plan_fft_inverse(height) } else { planner.plan_fft_forward(height) };
+    let mut column = vec![Complex::default(); height];
+    for x in 0..width {
+        for y in 0..height {
+            column[y] = data[y * width + x];
+        }
+        column_fft.process(&mut column);
+        for y in 0..height {
+            data[y * width + x] = column[y];
+        }
+    }
+}
+
+// Grayscale copy of the image at the given scale, cropped or padded to width x height and
+// multiplied with a Hann window so the image borders don't dominate the correlation
+fn prepare(image: &RgbaImage, scale: f64, width: u32, height: u32) -> Vec<Complex<f32>> {
+    let scaled_width = ((image.width() as f64 * scale).round() as u32).max(1);
+    let scaled_height = ((image.height() as f64 * scale).round() as u32).max(1);
+    let scaled = imageops::grayscale(&imageops::resize(image, scaled_width, scaled_height, imageops::FilterType::Triangle));
+    let mut canvas = GrayImage::new(width, height);
+    imageops::replace(&mut canvas, &scaled, 0, 0);
+
+    let hann = |i: u32, n: u32| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (n.max(2) - 1) as f32).cos();
+    canvas.enumerate_pixels()
+        .map(|(x, y, pixel)| Complex::new(pixel[0] as f32 * hann(x, width) * hann(y, height), 0.0))
+        .collect()
+}
+
+/// Estimate the translation in pixels that moves `moving` onto `reference`, using phase correlation
+pub fn estimate_translation(reference: &RgbaImage, moving: &RgbaImage) -> (i32, i32) {
+    let scale = (ALIGN_SIZE as f64 / reference.width().max(reference.height()) as f64).min(1.0);
+    let width = ((reference.width() as f64 * scale).round() as u32).max(1);
+    let height = ((reference.height() as f64 * scale).round() as u32).max(1);
+    let (w, h) = (width as usize, height as usize);
+
+    let mut reference_spectrum = prepare(reference, scale, width, height);
+    let mut moving_spectrum = prepare(moving, scale, width, height);
+    fft2d(&mut reference_spectrum, w, h, false);
+    fft2d(&mut moving_spectrum, w, h, false);
+
+    // Normalized cross-power spectrum, its inverse has a peak at the offset between the images
+    let mut correlation: Vec<Complex<f32>> = reference_spectrum.iter()
+        .zip(moving_spectrum.iter())
+        .map(|(a, b)| {
+            let product = a * b.conj();
+            let magnitude = product.norm();
+            if magnitude > f32::EPSILON { product / magnitude } else { Complex::default() }
+        })
+        .collect();
+    fft2d(&mut correlation, w, h, true);
+
+    let peak = correlation.iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.re.total_cmp(&b.re))
+        .map(|(index, _)| index)
+        .unwrap_or(0);
+    // Offsets past the middle wrap around to negative shifts
+    let (mut dx, mut dy) = ((peak % w) as i32, (peak / w) as i32);
+    if dx > width as i32 / 2 {
+        dx -= width as i32;
+    }
+    if dy > height as i32 / 2 {
+        dy -= height as i32;
+    }
+    ((dx as f64 / scale).round() as i32, (dy as f64 / scale).round() as i32)
+}
+
+// Magnitude spectrum of the image with the zero frequency in the middle, on a log scale so the strong low
+// frequencies don't drown out the rest
+fn magnitude_spectrum(image: &RgbaImage, scale: f64, side: u32) -> Vec<f32> {
+    let n = side as usize;
+    let mut spectrum = prepare(image, scale, side, side);
+    fft2d(&mut spectrum, n, n, false);
+    let mut shifted = vec![0f32; n * n];
+    for y in 0..n {
+        for x in 0..n {
+            shifted[((y + n / 2) % n) * n + (x + n / 2) % n] = spectrum[y * n + x].norm().ln_1p();
+        }
+    }
+    shifted
+}
+
+// The spectrum sampled along rays from its middle, a row per angle and a column per radius, so a rotation
+// of the image becomes a shift along the columns
+fn to_polar(spectrum: &[f32], side: usize) -> Vec<Complex<f32>> {
+    let center = side as f32 / 2.0;
+    let radii = side / 2;
+    let mut polar = Vec::with_capacity(ROTATION_STEPS * radii);
+    for step in 0..ROTATION_STEPS {
+        let angle = std::f32::consts::PI * step as f32 / ROTATION_STEPS as f32;
+        let (sin, cos) = angle.sin_cos();
+        for radius in 0..radii {
+            let (x, y) = (center + radius as f32 * cos, center + radius as f32 * sin);
+            polar.push(Complex::new(sample(spectrum, side, x, y), 0.0));
+        }
+    }
+    polar
+}
+
+// Bilinear sample of a square buffer, zero outside of it
+fn sample(data: &[f32], side: usize, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let at = |x: f32, y: f32| if x >= 0.0 && y >= 0.0 && (x as usize) < side && (y as usize) < side { data[y as usize * side + x as usize] } else { 0.0 };
+    let top = at(x0, y0) * (1.0 - fx) + at(x0 + 1.0, y0) * fx;
+    let bottom = at(x0, y0 + 1.0) * (1.0 - fx) + at(x0 + 1.0, y0 + 1.0) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+/// Estimate the rotation in degrees that turns `moving` onto `reference`, clockwise on screen, by phase correlation
+/// of their magnitude spectra in polar coordinates. These don't change when the image is shifted, so the rotation is
+/// found regardless of drift. Only rotations up to a quarter turn either way can be told apart.
+pub fn estimate_rotation(reference: &RgbaImage, moving: &RgbaImage) -> f64 {
+    let scale = (ALIGN_SIZE as f64 / reference.width().max(reference.height()) as f64).min(1.0);
+    let side = ((reference.width().max(reference.height()) as f64 * scale).round() as u32).max(2);
+    let n = side as usize;
+    let radii = n / 2;
+
+    let mut reference_polar = to_polar(&magnitude_spectrum(reference, scale, side), n);
+    let mut moving_polar = to_polar(&magnitude_spectrum(moving, scale, side), n);
+    fft2d(&mut reference_polar, radii, ROTATION_STEPS, false);
+    fft2d(&mut moving_polar, radii, ROTATION_STEPS, false);
+    let mut correlation: Vec<Complex<f32>> = reference_polar.iter()
+        .zip(moving_polar.iter())
+        .map(|(a, b)| {
+            let product = a * b.conj();
+            let magnitude = product.norm();
+            if magnitude > f32::EPSILON { product / magnitude } else { Complex::default() }
+        })
+        .collect();
+    fft2d(&mut correlation, radii, ROTATION_STEPS, true);
+
+    // Only the shift between the angles matters, the radii stay where they are
+    let peak_step = (0..ROTATION_STEPS)
+        .max_by(|&a, &b| correlation[a * radii].re.total_cmp(&correlation[b * radii].re))
+        .unwrap_or(0);
+    let step = if peak_step > ROTATION_STEPS / 2 { peak_step as f64 - ROTATION_STEPS as f64 } else { peak_step as f64 };
+    step * 180.0 / ROTATION_STEPS as f64
+}
+
+/// Turn the image by the angle in degrees, clockwise on screen, around its middle. Parts turned in from
+/// outside of the image are transparent.
+pub fn rotate_by(image: &RgbaImage, degrees: f64) -> RgbaImage {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (center_x, center_y) = (image.width() as f64 / 2.0, image.height() as f64 / 2.0);
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let (u, v) = (x as f64 + 0.5 - center_x, y as f64 + 0.5 - center_y);
+        // Where the pixel came from, turning back by the angle
+        let source_x = center_x + u * cos + v * sin;
+        let source_y = center_y - u * sin + v * cos;
+        if source_x < 0.0 || source_y < 0.0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        image.get_pixel_checked(source_x as u32, source_y as u32).copied().unwrap_or(Rgba([0, 0, 0, 0]))
+    })
+}
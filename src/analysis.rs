@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex},
+    thread,
+};
+
+use fltk::app;
+use image::{imageops, GrayImage};
+
+use crate::similarity;
+
+// Custom FLTK event sent to the main window when the background analysis is done
+pub const ANALYSIS_EVENT: i32 = 42;
+// How often to check whether the background analysis is done, in seconds
+const ANALYSIS_POLL_INTERVAL: f64 = 0.5;
+// Images are analyzed at this size, so the metrics are comparable between different resolutions
+const ANALYSIS_SIZE: u32 = 1024;
+// Images whose sharpness is below this fraction of the folder's median count as blurry
+const BLURRY_FRACTION: f64 = 0.3;
+// Part of the image that may be pure black or white before the exposure counts as a problem
+const SEVERE_CLIPPING: f64 = 0.05;
+// Luminance values at the ends of the histogram that count as clipped
+const SHADOW_CLIP_LEVEL: u8 = 2;
+const HIGHLIGHT_CLIP_LEVEL: u8 = 253;
+
+/// Quality metrics of an image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageStats {
+    // Variance of the Laplacian of the luminance, higher is sharper
+    pub sharpness: f64,
+    // Fractions of the pixels that are crushed to black or blown out to white
+    pub clipped_shadows: f64,
+    pub clipped_highlights: f64,
+    // For finding exact and near-duplicates
+    pub content_hash: u64,
+    pub perceptual_hash: u64,
+}
+
+impl ImageStats {
+    pub fn is_problem_exposure(&self) -> bool {
+        self.clipped_shadows > SEVERE_CLIPPING || self.clipped_highlights > SEVERE_CLIPPING
+    }
+}
+
+/// Ways of browsing that need the analysis results
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnalysisView {
+    SortBySharpness,
+    BlurryOnly,
+    ProblemExposures,
+    Duplicates,
+}
+
+/// Decode the image without FLTK, so this can run outside of the UI thread. RAW and FITS images go through
+/// the viewer's decoders and share their decode cache.
+pub fn decode_image(image_file: &Path) -> Result<image::DynamicImage, String> {
+    crate::decode_to_image(&image_file.to_string_lossy())
+}
+
+fn decode(image_file: &Path) -> Result<GrayImage, String> {
+    let gray = decode_image(image_file)?.to_luma8();
+    let scale = (ANALYSIS_SIZE as f64 / gray.width().max(gray.height()) as f64).min(1.0);
+    Ok(imageops::resize(
+        &gray,
+        ((gray.width() as f64 * scale) as u32).max(1),
+        ((gray.height() as f64 * scale) as u32).max(1),
+        imageops::FilterType::Triangle,
+    ))
+}
+
+fn variance_of_laplacian(gray: &GrayImage) -> f64 {
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+    let mut sum = 0f64;
+    let mut sum_of_squares = 0f64;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let laplacian = gray.get_pixel(x - 1, y)[0] as f64
+                + gray.get_pixel(x + 1, y)[0] as f64
+                + gray.get_pixel(x, y - 1)[0] as f64
+                + gray.get_pixel(x, y + 1)[0] as f64
+                - 4.0 * gray.get_pixel(x, y)[0] as f64;
+            sum += laplacian;
+            sum_of_squares += laplacian * laplacian;
+        }
+    }
+    let count = ((width - 2) * (height - 2)) as f64;
+    let mean = sum / count;
+    sum_of_squares / count - mean * mean
+}
+
+// Fractions of the pixels at the dark and bright end of the luminance histogram
+fn clipping(gray: &GrayImage) -> (f64, f64) {
+    let mut histogram = [0usize; 256];
+    for pixel in gray.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let count = (gray.width() * gray.height()).max(1) as f64;
+    let shadows: usize = histogram[..=SHADOW_CLIP_LEVEL as usize].iter().sum();
+    let highlights: usize = histogram[HIGHLIGHT_CLIP_LEVEL as usize..].iter().sum();
+    (shadows as f64 / count, highlights as f64 / count)
+}
+
+/// Compute the quality metrics of an image
+pub fn analyze(image_file: &Path) -> Result<ImageStats, String> {
+    let gray = decode(image_file)?;
+    let (clipped_shadows, clipped_highlights) = clipping(&gray);
+    let content_hash = similarity::content_hash(image_file).map_err(|err| format!("Failed to read {}: {}", image_file.display(), err))?;
+    Ok(ImageStats {
+        sharpness: variance_of_laplacian(&gray),
+        clipped_shadows,
+        clipped_highlights,
+        content_hash,
+        perceptual_hash: similarity::perceptual_hash_gray(&gray),
+    })
+}
+
+/// Analyzes the images of a directory on a background thread, and notifies the main window when it's done
+pub struct AnalysisWorker {
+    pub directory: PathBuf,
+    results: Arc<Mutex<HashMap<PathBuf, ImageStats>>>,
+    analyzed_count: Arc<AtomicUsize>,
+    total: usize,
+    is_cancelled: Arc<AtomicBool>,
+    poll_timeout: app::TimeoutHandle,
+}
+
+impl AnalysisWorker {
+    pub fn start(directory: &Path, image_files: Vec<PathBuf>) -> AnalysisWorker {
+        let results = Arc::new(Mutex::new(HashMap::new()));
+        let analyzed_count = Arc::new(AtomicUsize::new(0));
+        let is_cancelled = Arc::new(AtomicBool::new(false));
+        let total = image_files.len();
+
+        let thread_results = Arc::clone(&results);
+        let thread_count = Arc::clone(&analyzed_count);
+        let thread_cancelled = Arc::clone(&is_cancelled);
+        thread::spawn(move || {
+            for image_file in image_files {
+                if thread_cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                match analyze(&image_file) {
+                    Ok(stats) => {
+                        log::debug!("Analyzed {}: {:?}", image_file.display(), stats);
+                        if let Ok(mut results) = thread_results.lock() {
+                            results.insert(image_file, stats);
+                        }
+                    },
+                    Err(err) => log::debug!("Skipping analysis of {}: {}", image_file.display(), err),
+                }
+                thread_count.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+        let poll_count = Arc::clone(&analyzed_count);
+        let poll_timeout = app::add_timeout3(ANALYSIS_POLL_INTERVAL, move |handle| {
+            if poll_count.load(Ordering::Relaxed) >= total {
+                let _ = app::handle_main(ANALYSIS_EVENT);
+            } else {
+                app::repeat_timeout3(ANALYSIS_POLL_INTERVAL, handle);
+            }
+        });
+        AnalysisWorker { directory: directory.to_path_buf(), results, analyzed_count, total, is_cancelled, poll_timeout }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.analyzed_count.load(Ordering::Relaxed) >= self.total
+    }
+
+    /// Number of images analyzed so far and the total
+    pub fn progress(&self) -> (usize, usize) {
+        (self.analyzed_count.load(Ordering::Relaxed).min(self.total), self.total)
+    }
+
+    pub fn stats(&self, image_file: &Path) -> Option<ImageStats> {
+        self.results.lock().ok()?.get(image_file).copied()
+    }
+
+    /// Sharpness below which an image is a lot blurrier than the typical image of the directory
+    pub fn blurry_threshold(&self) -> Option<f64> {
+        let results = self.results.lock().ok()?;
+        let mut all_sharpness: Vec<f64> = results.values().map(|stats| stats.sharpness).collect();
+        all_sharpness.sort_by(|a, b| a.total_cmp(b));
+        let median = all_sharpness.get(all_sharpness.len() / 2)?;
+        Some(median * BLURRY_FRACTION)
+    }
+}
+
+impl Drop for AnalysisWorker {
+    fn drop(&mut self) {
+        self.is_cancelled.store(true, Ordering::Relaxed);
+        app::remove_timeout3(self.poll_timeout);
+    }
+}
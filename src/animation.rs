@@ -0,0 +1,243 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, SyncSender, TryRecvError, TrySendError}, Arc},
+    thread,
+    time::Duration,
+};
+
+use fltk::{app, frame::Frame, image::RgbImage, prelude::*};
+use image::{codecs::gif::GifDecoder, AnimationDecoder, RgbaImage};
+
+use crate::{export::TileSpec, rotation};
+
+// Frames converted for display ahead of the one being shown, the others stay as pixels until their turn
+const PREPARED_AHEAD: usize = 2;
+// GIF files this large are decoded while they play instead of all at once
+const STREAMING_MIN_BYTES: u64 = 1024 * 1024;
+// Frames decoded ahead of the one being shown
+const STREAM_AHEAD: usize = 8;
+// How often to check for the next frame when decoding falls behind, in seconds
+const STREAM_RETRY_INTERVAL: f64 = 0.01;
+// Frame delays this short are shown at DEFAULT_FRAME_DELAY, like web browsers do
+const MIN_FRAME_DELAY: f64 = 0.02;
+const DEFAULT_FRAME_DELAY: f64 = 0.1;
+
+thread_local! {
+    // The streamed animation being shown. FLTK timeouts live on the UI thread only.
+    static STREAM: RefCell<Option<StreamedAnimation>> = const { RefCell::new(None) };
+}
+
+/// Cycles through a list of images in the image frame, e.g. sprite sheet frames or images being blink compared
+pub struct FrameAnimation {
+    timeout: app::TimeoutHandle,
+}
+
+// Convert an image to an FLTK image scaled to fit the given size
+fn to_display_image(image: &RgbaImage, width: i32, height: i32) -> Result<RgbImage, String> {
+    let mut display_image = RgbImage::new(image.as_raw(), image.width() as i32, image.height() as i32, fltk::enums::ColorDepth::Rgba8)
+        .map_err(|err| format!("Error creating animation frame: {}", err))?;
+    display_image.scale(width, height, true, true);
+    Ok(display_image)
+}
+
+impl FrameAnimation {
+    /// Show the images one after another, scaled to fit the given size, switching every `interval` seconds
+    pub fn start(images: Vec<RgbaImage>, interval: f64, frame: &Frame, width: i32, height: i32) -> Result<FrameAnimation, String> {
+        if images.is_empty() {
+            return Err("There are no frames to show".to_string());
+        }
+        // Frames are kept no larger than they're shown, and only a few are converted at a time, which
+        // keeps long animations from doubling their memory
+        let pixel_ratio = frame.window().map(|wind| app::screen_scale(wind.screen_num()) as f64).unwrap_or(1.0);
+        let (pixel_width, pixel_height) = ((width as f64 * pixel_ratio) as u32, (height as f64 * pixel_ratio) as u32);
+        let images: Vec<RgbaImage> = images.into_iter().map(|image| shrink_to_fit(image, pixel_width, pixel_height)).collect();
+        let mut prepared = VecDeque::new();
+        prepare_frames(&images, 0, &mut prepared, width, height)?;
+
+        stop_stream();
+        let mut frame = frame.clone();
+        let mut index = 0;
+        let timeout = app::add_timeout3(0.0, move |handle| {
+            if let Err(err) = prepare_frames(&images, index, &mut prepared, width, height) {
+                log::error!("{}", err);
+            }
+            if let Some((_, display_image)) = prepared.front() {
+                frame.set_image(Some(display_image.clone()));
+            }
+            index = (index + 1) % images.len();
+            app::redraw();
+            app::repeat_timeout3(interval, handle);
+        });
+        Ok(FrameAnimation { timeout })
+    }
+
+    /// Cut the sprite sheet into frames and play them at the given frame rate
+    pub fn from_sprite_sheet(sheet: &RgbaImage, spec: TileSpec, fps: f64, frame: &Frame, width: i32, height: i32) -> Result<FrameAnimation, String> {
+        if fps <= 0.0 {
+            return Err(format!("Invalid frame rate: {}", fps));
+        }
+        let sprites: Vec<RgbaImage> = spec.tiles(sheet.width(), sheet.height()).into_iter()
+            .map(|(x, y, tile_width, tile_height)| image::imageops::crop_imm(sheet, x, y, tile_width, tile_height).to_image())
+            .collect();
+        log::debug!("Playing {} sprite frames at {} fps", sprites.len(), fps);
+        FrameAnimation::start(sprites, 1.0 / fps, frame, width, height)
+    }
+}
+
+// Shrink the image to fit into the size, smaller images stay as they are
+fn shrink_to_fit(image: RgbaImage, width: u32, height: u32) -> RgbaImage {
+    if image.width() <= width.max(1) && image.height() <= height.max(1) {
+        return image;
+    }
+    let scale = (width as f64 / image.width() as f64).min(height as f64 / image.height() as f64);
+    image::imageops::thumbnail(&image, ((image.width() as f64 * scale) as u32).max(1), ((image.height() as f64 * scale) as u32).max(1))
+}
+
+// Drop the converted frames before `index` and convert the ones up to PREPARED_AHEAD after it, so
+// the frame at `index` is the first one
+fn prepare_frames(images: &[RgbaImage], index: usize, prepared: &mut VecDeque<(usize, RgbImage)>, width: i32, height: i32) -> Result<(), String> {
+    while prepared.front().is_some_and(|&(prepared_index, _)| prepared_index != index) {
+        prepared.pop_front();
+    }
+    let count = images.len().min(PREPARED_AHEAD + 1);
+    while prepared.len() < count {
+        let next = (index + prepared.len()) % images.len();
+        prepared.push_back((next, to_display_image(&images[next], width, height)?));
+    }
+    Ok(())
+}
+
+impl Drop for FrameAnimation {
+    fn drop(&mut self) {
+        app::remove_timeout3(self.timeout);
+    }
+}
+
+/// Plays a long GIF while it's being decoded on a worker thread, which only keeps a few frames in
+/// memory and starts over from the file at the end
+struct StreamedAnimation {
+    timeout: app::TimeoutHandle,
+    is_stopped: Arc<AtomicBool>,
+}
+
+struct StreamedFrame {
+    pixels: RgbaImage,
+    delay: f64,
+}
+
+impl Drop for StreamedAnimation {
+    fn drop(&mut self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+        app::remove_timeout3(self.timeout);
+    }
+}
+
+/// Whether the file is an animation that is streamed instead of loaded by FLTK
+pub fn is_streamed(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("gif"))
+        && fs::metadata(path).is_ok_and(|metadata| metadata.len() >= STREAMING_MIN_BYTES)
+}
+
+fn open_gif(path: &Path) -> Result<GifDecoder<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+    GifDecoder::new(BufReader::new(file)).map_err(|err| format!("Failed to decode {}: {}", path.display(), err))
+}
+
+/// The first frame of the animation, shown until streaming catches up
+pub fn first_frame(path: &Path) -> Result<RgbaImage, String> {
+    match open_gif(path)?.into_frames().next() {
+        Some(Ok(frame)) => Ok(frame.into_buffer()),
+        Some(Err(err)) => Err(format!("Failed to decode {}: {}", path.display(), err)),
+        None => Err(format!("{} has no frames", path.display())),
+    }
+}
+
+// Hand the frame to the UI thread, waiting while it's STREAM_AHEAD frames ahead. False once the animation is stopped.
+fn send_frame(sender: &SyncSender<StreamedFrame>, mut frame: StreamedFrame, is_stopped: &AtomicBool) -> bool {
+    loop {
+        match sender.try_send(frame) {
+            Ok(()) => return true,
+            Err(TrySendError::Full(unsent)) if !is_stopped.load(Ordering::Relaxed) => {
+                frame = unsent;
+                thread::sleep(Duration::from_secs_f64(STREAM_RETRY_INTERVAL));
+            }
+            Err(_) => return false,
+        }
+    }
+}
+
+// Decode the frames over and over until the animation is stopped
+fn decode_frames(path: PathBuf, turns: u8, sender: SyncSender<StreamedFrame>, is_stopped: Arc<AtomicBool>) {
+    loop {
+        let decoder = match open_gif(&path) {
+            Ok(decoder) => decoder,
+            Err(err) => {
+                log::error!("{}", err);
+                return;
+            }
+        };
+        let mut count = 0;
+        for frame in decoder.into_frames() {
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(err) => {
+                    log::error!("Failed to decode frame {} of {}: {}", count, path.display(), err);
+                    break;
+                }
+            };
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            let delay = numerator as f64 / denominator.max(1) as f64 / 1000.0;
+            let mut pixels = frame.into_buffer();
+            if turns > 0 {
+                pixels = rotation::rotate_image(&pixels, turns);
+            }
+            let delay = if delay < MIN_FRAME_DELAY { DEFAULT_FRAME_DELAY } else { delay };
+            if !send_frame(&sender, StreamedFrame { pixels, delay }, &is_stopped) {
+                return;
+            }
+            count += 1;
+        }
+        // A still image has nothing more to show
+        if count <= 1 {
+            return;
+        }
+    }
+}
+
+/// Play the animation in the image frame, turned clockwise by a number of quarter turns like the
+/// image shown. Each frame is drawn at the size the frame's image has, so zooming keeps working.
+pub fn start_stream(path: &Path, turns: u8, frame: &Frame) {
+    stop_stream();
+    let (sender, receiver) = mpsc::sync_channel(STREAM_AHEAD);
+    let is_stopped = Arc::new(AtomicBool::new(false));
+    let decode_path = path.to_path_buf();
+    let decode_stopped = Arc::clone(&is_stopped);
+    thread::spawn(move || decode_frames(decode_path, turns, sender, decode_stopped));
+
+    let mut frame = frame.clone();
+    let timeout = app::add_timeout3(0.0, move |handle| {
+        match receiver.try_recv() {
+            Ok(streamed) => {
+                let (width, height) = frame.image().map(|image| (image.w(), image.h())).unwrap_or((frame.w(), frame.h()));
+                match to_display_image(&streamed.pixels, width, height) {
+                    Ok(image) => frame.set_image(Some(image)),
+                    Err(err) => log::error!("{}", err),
+                }
+                app::redraw();
+                app::repeat_timeout3(streamed.delay, handle);
+            }
+            Err(TryRecvError::Empty) => app::repeat_timeout3(STREAM_RETRY_INTERVAL, handle),
+            Err(TryRecvError::Disconnected) => {}
+        }
+    });
+    STREAM.with(|stream| *stream.borrow_mut() = Some(StreamedAnimation { timeout, is_stopped }));
+}
+
+/// Stop the streamed animation, done whenever a different image is shown
+pub fn stop_stream() {
+    STREAM.with(|stream| stream.borrow_mut().take());
+}
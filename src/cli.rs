@@ -0,0 +1,137 @@
+use std::{env, path::PathBuf};
+
+use clap::{Parser, Subcommand};
+use fltk::enums::Color;
+
+use crate::{render::RenderOptions, scan::SortMode};
+
+// Arguments that were written with a slash before, e.g. /windowed, which file associations and scripts still use
+const LEGACY_FLAGS: [&str; 5] = ["windowed", "resume", "debug-perf", "register", "unregister"];
+const LEGACY_OPTIONS: [&str; 1] = ["monitor="];
+
+/// A fast image viewer that supports a wide range of image formats
+#[derive(Parser, Debug)]
+#[command(name = "lightningview", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Image files, folders or web addresses to show, several are browsed as one list, and - reads an image from
+    /// stdin. Without any, a file selection dialog is shown
+    pub image_files: Vec<String>,
+
+    /// Open in a window instead of fullscreen
+    #[arg(long, conflicts_with = "fullscreen")]
+    pub windowed: bool,
+
+    /// Open fullscreen, also when resuming a session that was windowed
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Monitor used for fullscreen, starting at 1
+    #[arg(long, value_name = "NUMBER")]
+    pub monitor: Option<i32>,
+
+    /// Start a slideshow, showing each image for the given seconds or the configured interval
+    #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true)]
+    pub slideshow: Option<Option<f64>>,
+
+    /// Browse the images in random order
+    #[arg(long)]
+    pub random: bool,
+
+    /// Include the images in subfolders
+    #[arg(long)]
+    pub recursive: bool,
+
+    /// Order of the images: name, date (oldest first) or size (smallest first)
+    #[arg(long, value_name = "MODE", value_parser = parse_sort_mode)]
+    pub sort: Option<SortMode>,
+
+    /// Background color: black, white, gray or a hex color like #202020
+    #[arg(long = "bg", value_name = "COLOR", value_parser = parse_color)]
+    pub background: Option<Color>,
+
+    /// Continue where the last session left off
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Show loading times and memory use on top of the image
+    #[arg(long)]
+    pub debug_perf: bool,
+
+    /// Print the format, dimensions, bit depth, EXIF summary and FITS headers of the file, and exit
+    #[arg(long, value_name = "FILE")]
+    pub info: Option<String>,
+
+    /// Write a PNG thumbnail of the image IN to OUT, and exit
+    #[arg(long, num_args = 2, value_names = ["IN", "OUT"])]
+    pub thumbnail: Option<Vec<PathBuf>>,
+
+    /// Longest edge of the thumbnail in pixels
+    #[arg(long, value_name = "N", default_value_t = 256, requires = "thumbnail")]
+    pub thumbnail_size: u32,
+
+    /// Convert the image IN to PNG, JPEG, WebP or TIFF by the extension of OUT, and exit
+    #[arg(long, num_args = 2, value_names = ["IN", "OUT"])]
+    pub convert: Option<Vec<PathBuf>>,
+
+    /// Print the supported file extensions grouped by decoder, one decoder per line, and exit
+    #[arg(long)]
+    pub list_formats: bool,
+
+    /// Register as image viewer in Windows
+    #[cfg(target_os = "windows")]
+    #[arg(long)]
+    pub register: bool,
+
+    /// Remove the registration as image viewer
+    #[cfg(target_os = "windows")]
+    #[arg(long)]
+    pub unregister: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Render an image to a file the way the viewer shows it, without opening a window
+    Render(RenderOptions),
+}
+
+fn parse_sort_mode(text: &str) -> Result<SortMode, String> {
+    SortMode::parse(text).ok_or(format!("unknown sort mode \"{}\", use name, date or size", text))
+}
+
+fn parse_color(text: &str) -> Result<Color, String> {
+    match text.to_lowercase().as_str() {
+        "black" => return Ok(Color::Black),
+        "white" => return Ok(Color::White),
+        "gray" | "grey" => return Ok(Color::from_rgb(128, 128, 128)),
+        _ => {}
+    }
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    match u32::from_str_radix(hex, 16) {
+        Ok(rgb) if hex.len() == 6 => Ok(Color::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+        _ => Err(format!("invalid color \"{}\", use black, white, gray or a hex color like #202020", text)),
+    }
+}
+
+// Spell the slash arguments of earlier versions the way clap expects them
+fn modernize(arg: String) -> String {
+    if arg.eq_ignore_ascii_case("/render") || arg == "--render" {
+        return "render".to_string();
+    }
+    let Some(name) = arg.strip_prefix('/') else {
+        return arg;
+    };
+    let lowercase = name.to_lowercase();
+    if LEGACY_FLAGS.contains(&lowercase.as_str()) || LEGACY_OPTIONS.iter().any(|option| lowercase.starts_with(option)) {
+        format!("--{}", lowercase)
+    } else {
+        arg
+    }
+}
+
+/// Parse the command line, exiting with the usage on errors and for --help
+pub fn parse() -> Cli {
+    Cli::parse_from(env::args().map(modernize))
+}
@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use exif::{In, Tag, Value};
+use image::RgbImage;
+
+use crate::mapped;
+
+/// Color model of the pixel data in a file, for the models that need converting before display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorModel {
+    Rgb,
+    Cmyk,
+    // CMYK with the CMY channels stored as YCbCr, common in JPEGs from Adobe applications
+    Ycck,
+    Lab,
+}
+
+// TIFF PhotometricInterpretation values of the CIE L*a*b* variants
+const PHOTOMETRIC_CMYK: u32 = 5;
+const PHOTOMETRIC_CIELAB: u32 = 8;
+const PHOTOMETRIC_ICCLAB: u32 = 9;
+
+/// Find out how the colors of a JPEG file are stored, from its frame header and Adobe marker
+pub fn detect_jpeg(path: &Path) -> ColorModel {
+    // Only the headers at the start of the file are read
+    let data = match mapped::map(path) {
+        Ok(data) => data,
+        Err(_) => return ColorModel::Rgb,
+    };
+    let mut components = 3;
+    let mut adobe_transform = None;
+    let mut position = 2;
+    while position + 4 <= data.len() && data[position] == 0xFF {
+        let marker = data[position + 1];
+        let length = u16::from_be_bytes([data[position + 2], data[position + 3]]) as usize;
+        let segment = match data.get(position + 4..position + 2 + length) {
+            Some(segment) => segment,
+            None => break,
+        };
+        match marker {
+            // Start of scan, all headers have been read
+            0xDA => break,
+            0xEE if segment.starts_with(b"Adobe") && segment.len() > 11 => adobe_transform = Some(segment[11]),
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) && segment.len() > 5 => components = segment[5],
+            _ => {}
+        }
+        position += 2 + length;
+    }
+    match (components, adobe_transform) {
+        (4, Some(2)) => ColorModel::Ycck,
+        (4, _) => ColorModel::Cmyk,
+        _ => ColorModel::Rgb,
+    }
+}
+
+fn tiff_field(exif: &exif::Exif, tag: Tag) -> Option<u32> {
+    exif.get_field(tag, In::PRIMARY).and_then(|field| field.value.get_uint(0))
+}
+
+fn tiff_fields(exif: &exif::Exif, tag: Tag) -> Vec<u32> {
+    match exif.get_field(tag, In::PRIMARY).map(|field| &field.value) {
+        Some(Value::Short(values)) => values.iter().map(|&value| value as u32).collect(),
+        Some(Value::Long(values)) => values.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Find out how the colors of a TIFF file are stored, from its PhotometricInterpretation tag
+pub fn detect_tiff(path: &Path) -> ColorModel {
+    let exif = match mapped::map(path).ok().and_then(|data| mapped::read_tiff_tags(&data).ok()) {
+        Some(exif) => exif,
+        None => return ColorModel::Rgb,
+    };
+    match tiff_field(&exif, Tag::PhotometricInterpretation) {
+        Some(PHOTOMETRIC_CMYK) => ColorModel::Cmyk,
+        Some(PHOTOMETRIC_CIELAB) | Some(PHOTOMETRIC_ICCLAB) => ColorModel::Lab,
+        _ => ColorModel::Rgb,
+    }
+}
+
+// Convert a D50 L*a*b* color, as used by TIFF, to sRGB
+fn lab_to_srgb(l: f32, a: f32, b: f32) -> [u8; 3] {
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+    let inverse = |t: f32| if t > 6.0 / 29.0 { t.powi(3) } else { 3.0 * (6.0f32 / 29.0).powi(2) * (t - 4.0 / 29.0) };
+    let (x, y, z) = (0.96422 * inverse(fx), inverse(fy), 0.82521 * inverse(fz));
+    // XYZ to linear sRGB, with the Bradford adaptation from D50 to D65 folded in
+    let linear = [
+        3.1338561 * x - 1.6168667 * y - 0.4906146 * z,
+        -0.9787684 * x + 1.9161415 * y + 0.0334540 * z,
+        0.0719453 * x - 0.2289914 * y + 1.4052427 * z,
+    ];
+    linear.map(|value| {
+        let value = value.clamp(0.0, 1.0);
+        let encoded = if value <= 0.0031308 { 12.92 * value } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 };
+        (encoded * 255.0).round() as u8
+    })
+}
+
+/// Decode an uncompressed 8 bit L*a*b* TIFF to sRGB. The image crate can't read these, and they
+/// are what some print and scanning workflows produce.
+pub fn decode_lab_tiff(path: &Path) -> Result<RgbImage, String> {
+    let data = mapped::map(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let exif = mapped::read_tiff_tags(&data)?;
+    let width = tiff_field(&exif, Tag::ImageWidth).ok_or("TIFF file has no width".to_string())?;
+    let height = tiff_field(&exif, Tag::ImageLength).ok_or("TIFF file has no height".to_string())?;
+    let samples = tiff_field(&exif, Tag::SamplesPerPixel).unwrap_or(1) as usize;
+    if tiff_field(&exif, Tag::Compression).unwrap_or(1) != 1 {
+        return Err("Only uncompressed L*a*b* TIFF files are supported".to_string());
+    }
+    if samples < 3 || tiff_fields(&exif, Tag::BitsPerSample).iter().any(|&bits| bits != 8) || tiff_field(&exif, Tag::PlanarConfiguration).unwrap_or(1) != 1 {
+        return Err("Only interleaved 8 bit L*a*b* TIFF files are supported".to_string());
+    }
+    // ICCLab stores a* and b* with an offset of 128, CIELab as signed values
+    let is_signed = tiff_field(&exif, Tag::PhotometricInterpretation) == Some(PHOTOMETRIC_CIELAB);
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * samples);
+    for (offset, count) in tiff_fields(&exif, Tag::StripOffsets).iter().zip(tiff_fields(&exif, Tag::StripByteCounts).iter()) {
+        let strip = data.get(*offset as usize..(*offset + *count) as usize).ok_or("TIFF strip is outside of the file".to_string())?;
+        pixels.extend_from_slice(strip);
+    }
+    if pixels.len() < width as usize * height as usize * samples {
+        return Err("TIFF file has less pixel data than its size needs".to_string());
+    }
+
+    let mut image = RgbImage::new(width, height);
+    for (pixel, lab) in image.pixels_mut().zip(pixels.chunks_exact(samples)) {
+        let l = lab[0] as f32 * 100.0 / 255.0;
+        let (a, b) = if is_signed {
+            (lab[1] as i8 as f32, lab[2] as i8 as f32)
+        } else {
+            (lab[1] as f32 - 128.0, lab[2] as f32 - 128.0)
+        };
+        pixel.0 = lab_to_srgb(l, a, b);
+    }
+    Ok(image)
+}
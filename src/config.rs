@@ -0,0 +1,160 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+// Number of entries kept in the recently opened list
+const MAX_RECENT_ENTRIES: usize = 10;
+const RECENT_FILENAME: &str = "recent.txt";
+const SESSION_FILENAME: &str = "session.toml";
+const WINDOW_FILENAME: &str = "window.toml";
+const CONFIG_FILENAME: &str = "config.toml";
+
+/// User settings from config.toml in the config directory
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // External editor program followed by its arguments, the image path is added at the end
+    pub editor: Option<Vec<String>>,
+    // Action name to key list, e.g. next = "Right, Space"
+    pub keys: HashMap<String, String>,
+    // Mouse setting to mode, e.g. wheel = "navigate"
+    pub mouse: HashMap<String, String>,
+    // Number key 1-9 to the folder the current image is moved to, e.g. 1 = "/home/me/keepers"
+    pub move_to: HashMap<String, PathBuf>,
+    // Whether manual rotations are saved in lightningview_rotations.toml next to the images, instead of only for the session
+    pub rotation_sidecars: bool,
+    // Whether the status bar with the file name and loading times is shown from the start
+    pub status_bar: bool,
+    // Whether the toolbar appears when the mouse is at the top of the window
+    pub toolbar: bool,
+    // Seconds each image is shown in the slideshow
+    pub slideshow_interval: Option<f64>,
+    // Filter for showing images larger or smaller than their size: auto, nearest, bilinear or lanczos
+    pub scaling_filter: Option<String>,
+    // Number of images before and after the current one that are decoded ahead of time
+    pub preload: Option<usize>,
+    pub confirm: ConfirmPolicy,
+}
+
+/// Which destructive actions ask before they are done, from the [confirm] section of the config file
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct ConfirmPolicy {
+    pub delete: bool,
+    // Replacing an existing file when copying, moving or saving
+    pub overwrite: bool,
+    // Moving images to the folders of the number keys
+    pub move_to: bool,
+}
+
+impl Default for ConfirmPolicy {
+    fn default() -> Self {
+        ConfirmPolicy { delete: true, overwrite: true, move_to: false }
+    }
+}
+
+/// Load the user settings, falling back to the defaults if there is no config file
+pub fn load_config() -> Result<Config, String> {
+    let path = match config_dir() {
+        Some(dir) => dir.join(CONFIG_FILENAME),
+        None => return Ok(Config::default()),
+    };
+    match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents).map_err(|err| format!("Invalid config file {}: {}", path.display(), err)),
+        Err(_) => Ok(Config::default()),
+    }
+}
+
+/// Viewer state saved on exit, so the last session can be resumed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub file: PathBuf,
+    pub zoom_factor: f64,
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub window_x: i32,
+    pub window_y: i32,
+    pub window_width: i32,
+    pub window_height: i32,
+    pub is_fullscreen: bool,
+    pub is_scaled_to_fit: bool,
+    pub is_randomized: bool,
+}
+
+pub fn load_session() -> Option<Session> {
+    let contents = fs::read_to_string(config_dir()?.join(SESSION_FILENAME)).ok()?;
+    match toml::from_str(&contents) {
+        Ok(session) => Some(session),
+        Err(err) => {
+            log::error!("Failed to read the last session: {}", err);
+            None
+        }
+    }
+}
+
+pub fn save_session(session: &Session) -> Result<(), String> {
+    let dir = config_dir().ok_or("No configuration directory available".to_string())?;
+    let contents = toml::to_string(session).map_err(|err| format!("Failed to serialize session: {}", err))?;
+    fs::create_dir_all(&dir).map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+    fs::write(dir.join(SESSION_FILENAME), contents)
+        .map_err(|err| format!("Failed to save session: {}", err))
+}
+
+/// Position and size of the window when it's not fullscreen, restored on every start
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub is_maximized: bool,
+}
+
+pub fn load_window_geometry() -> Option<WindowGeometry> {
+    let contents = fs::read_to_string(config_dir()?.join(WINDOW_FILENAME)).ok()?;
+    toml::from_str(&contents).ok()
+}
+
+pub fn save_window_geometry(geometry: &WindowGeometry) -> Result<(), String> {
+    let dir = config_dir().ok_or("No configuration directory available".to_string())?;
+    let contents = toml::to_string(geometry).map_err(|err| format!("Failed to serialize window geometry: {}", err))?;
+    fs::create_dir_all(&dir).map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+    fs::write(dir.join(WINDOW_FILENAME), contents)
+        .map_err(|err| format!("Failed to save window geometry: {}", err))
+}
+
+/// Directory where LightningView keeps its settings and state
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("lightningview"))
+}
+
+/// Recently opened files and folders, most recent first
+pub fn load_recent() -> Vec<PathBuf> {
+    config_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(RECENT_FILENAME)).ok())
+        .map(|contents| {
+            contents.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Move the path to the top of the recently opened list and save it
+pub fn add_recent(path: &Path) -> Result<(), String> {
+    let dir = config_dir().ok_or("No configuration directory available".to_string())?;
+    let mut recent = load_recent();
+    recent.retain(|entry| entry != path);
+    recent.insert(0, path.to_path_buf());
+    recent.truncate(MAX_RECENT_ENTRIES);
+
+    let contents: Vec<String> = recent.iter().map(|entry| entry.to_string_lossy().into_owned()).collect();
+    fs::create_dir_all(&dir).map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+    fs::write(dir.join(RECENT_FILENAME), contents.join("\n"))
+        .map_err(|err| format!("Failed to save recent files: {}", err))
+}
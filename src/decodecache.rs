@@ -0,0 +1,122 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{loader::DecodedImage, perf, similarity};
+
+// Start of every cache file, followed by the width and height and the RGB pixels
+const MAGIC: &[u8; 6] = b"LVDC1\n";
+// Oldest entries are removed once the cache grows beyond this
+const MAX_CACHE_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("lightningview").join("decoded"))
+}
+
+// Cache file of the image contents processed a certain way. The version is part of the key, as a
+// newer LightningView may decode the same file differently.
+fn cache_file(image_file: &Path, processing: &str) -> Option<PathBuf> {
+    let content_hash = similarity::content_hash(image_file).ok()?;
+    let mut hasher = DefaultHasher::new();
+    (content_hash, processing, env!("CARGO_PKG_VERSION")).hash(&mut hasher);
+    Some(cache_dir()?.join(format!("{:016x}.rgb", hasher.finish())))
+}
+
+fn read(cache_file: &Path) -> Result<DecodedImage, String> {
+    let mut file = File::open(cache_file).map_err(|err| err.to_string())?;
+    let mut header = [0u8; 14];
+    file.read_exact(&mut header).map_err(|err| err.to_string())?;
+    if &header[..6] != MAGIC {
+        return Err("Not a decode cache file".to_string());
+    }
+    let width = u32::from_le_bytes([header[6], header[7], header[8], header[9]]) as i32;
+    let height = u32::from_le_bytes([header[10], header[11], header[12], header[13]]) as i32;
+    let mut data = Vec::with_capacity(width as usize * height as usize * 3);
+    file.read_to_end(&mut data).map_err(|err| err.to_string())?;
+    if data.len() != width as usize * height as usize * 3 {
+        return Err("Truncated decode cache file".to_string());
+    }
+    // Recently used entries are the last to be removed
+    if let Ok(file) = File::options().append(true).open(cache_file) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+    Ok(DecodedImage { data, width, height })
+}
+
+// Written through a temporary file, so a viewer running at the same time never reads half of it
+fn write(cache_file: &Path, image: &DecodedImage) -> Result<(), String> {
+    let folder = cache_file.parent().ok_or("Invalid cache path".to_string())?;
+    fs::create_dir_all(folder).map_err(|err| format!("Failed to create {}: {}", folder.display(), err))?;
+    let temporary_file = cache_file.with_extension(format!("{}.tmp", std::process::id()));
+    let result = File::create(&temporary_file).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(image.width as u32).to_le_bytes())?;
+        writer.write_all(&(image.height as u32).to_le_bytes())?;
+        writer.write_all(&image.data)?;
+        writer.flush()
+    });
+    if let Err(err) = result.and_then(|_| fs::rename(&temporary_file, cache_file)) {
+        let _ = fs::remove_file(&temporary_file);
+        return Err(format!("Failed to write {}: {}", cache_file.display(), err));
+    }
+    Ok(())
+}
+
+// Remove the least recently used entries until the cache fits its size limit again
+fn trim(folder: &Path) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort();
+    for (_, size, path) in files {
+        if total <= MAX_CACHE_BYTES {
+            break;
+        }
+        if fs::remove_file(&path).is_ok() {
+            log::debug!("Removed {} from the decode cache", path.display());
+            total -= size;
+        }
+    }
+}
+
+/// Decode the image, or take the result of an earlier decode of the same file contents from the
+/// cache on disk. `processing` names how the image is decoded, so different settings get their own entries.
+pub fn get_or_decode(image_file: &str, processing: &str, decode: impl FnOnce(&str) -> Result<DecodedImage, String>) -> Result<DecodedImage, String> {
+    let Some(cache_file) = cache_file(Path::new(image_file), processing) else {
+        return decode(image_file);
+    };
+    match read(&cache_file) {
+        Ok(image) => {
+            log::debug!("Loaded {} from the decode cache", image_file);
+            perf::cache_lookup(true);
+            return Ok(image);
+        }
+        Err(err) if cache_file.exists() => log::debug!("Ignoring decode cache file {}: {}", cache_file.display(), err),
+        Err(_) => {}
+    }
+    perf::cache_lookup(false);
+    let image = decode(image_file)?;
+    match write(&cache_file, &image) {
+        Ok(()) => {
+            if let Some(folder) = cache_file.parent() {
+                trim(folder);
+            }
+        }
+        Err(err) => log::debug!("{}", err),
+    }
+    Ok(image)
+}
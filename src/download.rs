@@ -0,0 +1,115 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc, Mutex},
+    thread,
+};
+
+use fltk::app;
+
+use crate::{progress::ProgressDialog, stdin};
+
+// Temporary folder of the downloads, see stdin::session_dir
+const DOWNLOAD_DIR: &str = "downloads";
+// How often the progress dialog is updated, in seconds
+const PROGRESS_INTERVAL: f64 = 0.05;
+// Downloads are read in pieces of this size, so progress shows and canceling is quick
+const CHUNK_BYTES: usize = 64 * 1024;
+const MEGABYTE: f64 = 1024.0 * 1024.0;
+
+/// Whether the argument is a web address to download instead of a file
+pub fn is_url(text: &str) -> bool {
+    let lowercase = text.to_lowercase();
+    lowercase.starts_with("http://") || lowercase.starts_with("https://")
+}
+
+// Name of the downloaded file without extension, from the last part of the address if it has a usable one
+fn file_stem(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let stem = Path::new(name).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let stem: String = stem.chars().filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.')).collect();
+    if stem.is_empty() { "download".to_string() } else { stem }
+}
+
+fn fetch(url: &str, received: &AtomicU64, total: &AtomicU64, is_canceled: &AtomicBool) -> Result<Vec<u8>, String> {
+    let response = ureq::get(url).call().map_err(|err| format!("Failed to download {}: {}", url, err))?;
+    if let Some(length) = response.header("Content-Length").and_then(|length| length.parse::<u64>().ok()) {
+        total.store(length, Ordering::Relaxed);
+    }
+    let mut reader = response.into_reader();
+    let mut data = Vec::new();
+    let mut chunk = vec![0u8; CHUNK_BYTES];
+    loop {
+        if is_canceled.load(Ordering::Relaxed) {
+            return Err("Download canceled".to_string());
+        }
+        let count = reader.read(&mut chunk).map_err(|err| format!("Failed to download {}: {}", url, err))?;
+        if count == 0 {
+            return Ok(data);
+        }
+        data.extend_from_slice(&chunk[..count]);
+        received.fetch_add(count as u64, Ordering::Relaxed);
+    }
+}
+
+/// Download the image at the address into a temporary file, named after the address with the extension
+/// of the format found in the data. A progress dialog is shown meanwhile, closing it cancels the
+/// download. Remove the files with `remove_files` when done.
+pub fn download(url: &str) -> Result<PathBuf, String> {
+    let received = Arc::new(AtomicU64::new(0));
+    let total = Arc::new(AtomicU64::new(0));
+    let is_canceled = Arc::new(AtomicBool::new(false));
+    let result: Arc<Mutex<Option<Result<Vec<u8>, String>>>> = Arc::new(Mutex::new(None));
+
+    let thread_url = url.to_string();
+    let thread_received = Arc::clone(&received);
+    let thread_total = Arc::clone(&total);
+    let thread_canceled = Arc::clone(&is_canceled);
+    let thread_result = Arc::clone(&result);
+    thread::spawn(move || {
+        let outcome = fetch(&thread_url, &thread_received, &thread_total, &thread_canceled);
+        if let Ok(mut result) = thread_result.lock() {
+            *result = Some(outcome);
+        }
+    });
+
+    let mut progress_dialog = ProgressDialog::show("Downloading", 1.0, &is_canceled);
+    progress_dialog.set_status(url);
+
+    // Nothing else can be shown before the image is there, so wait for it right here
+    let outcome = loop {
+        if let Some(outcome) = result.lock().ok().and_then(|mut result| result.take()) {
+            break outcome;
+        }
+        let (received, total) = (received.load(Ordering::Relaxed), total.load(Ordering::Relaxed));
+        if total > 0 {
+            progress_dialog.set_progress(received as f64 / total as f64, &format!("{:.1} of {:.1} MB", received as f64 / MEGABYTE, total as f64 / MEGABYTE));
+        } else {
+            progress_dialog.set_progress(0.0, &format!("{:.1} MB", received as f64 / MEGABYTE));
+        }
+        let _ = app::wait_for(PROGRESS_INTERVAL);
+    };
+    progress_dialog.hide();
+    let data = outcome?;
+
+    let extension = stdin::detect_extension(&data).ok_or(format!("{} is not an image format LightningView can open", url))?;
+    let directory = stdin::session_dir(DOWNLOAD_DIR);
+    fs::create_dir_all(&directory).map_err(|err| format!("Failed to create {}: {}", directory.display(), err))?;
+    let stem = file_stem(url);
+    let mut path = directory.join(format!("{}.{}", stem, extension));
+    let mut number = 1;
+    while path.exists() {
+        number += 1;
+        path = directory.join(format!("{}-{}.{}", stem, number, extension));
+    }
+    fs::write(&path, &data).map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+    log::debug!("Downloaded {} bytes from {} to {}", data.len(), url, path.display());
+    Ok(path)
+}
+
+/// Remove the files downloaded by this viewer
+pub fn remove_files() {
+    stdin::remove_session_dir(DOWNLOAD_DIR);
+}
@@ -0,0 +1,183 @@
+use std::{fs::File, io::BufReader, path::{Path, PathBuf}};
+
+use fltk::dialog;
+use image::{AnimationDecoder, RgbaImage};
+
+/// How to slice an image into tiles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TileSpec {
+    // Fixed number of columns and rows
+    Grid { columns: u32, rows: u32 },
+    // Fixed tile size in pixels, tiles at the right and bottom edges may be smaller
+    Size { width: u32, height: u32 },
+}
+
+impl TileSpec {
+    /// Parse "4x3" as a grid, and "256px" or "256x128px" as a tile size
+    pub fn parse(text: &str) -> Option<TileSpec> {
+        let text = text.trim().to_lowercase();
+        let (dimensions, is_size) = match text.strip_suffix("px") {
+            Some(dimensions) => (dimensions.trim(), true),
+            None => (text.as_str(), false),
+        };
+        let (first, second) = match dimensions.split_once('x') {
+            Some((first, second)) => (first.trim().parse::<u32>().ok()?, second.trim().parse::<u32>().ok()?),
+            None if is_size => {
+                let size = dimensions.parse::<u32>().ok()?;
+                (size, size)
+            },
+            None => return None,
+        };
+        if first == 0 || second == 0 {
+            return None;
+        }
+        if is_size {
+            Some(TileSpec::Size { width: first, height: second })
+        } else {
+            Some(TileSpec::Grid { columns: first, rows: second })
+        }
+    }
+
+    /// Tile rectangles as (x, y, width, height), row by row
+    pub fn tiles(&self, image_width: u32, image_height: u32) -> Vec<(u32, u32, u32, u32)> {
+        let mut tiles = Vec::new();
+        match *self {
+            TileSpec::Grid { columns, rows } => {
+                // Spread the remainder over the tiles so all of the image is covered
+                for row in 0..rows {
+                    let y0 = row * image_height / rows;
+                    let y1 = (row + 1) * image_height / rows;
+                    for column in 0..columns {
+                        let x0 = column * image_width / columns;
+                        let x1 = (column + 1) * image_width / columns;
+                        if x1 > x0 && y1 > y0 {
+                            tiles.push((x0, y0, x1 - x0, y1 - y0));
+                        }
+                    }
+                }
+            },
+            TileSpec::Size { width, height } => {
+                for y in (0..image_height).step_by(height as usize) {
+                    for x in (0..image_width).step_by(width as usize) {
+                        tiles.push((x, y, width.min(image_width - x), height.min(image_height - y)));
+                    }
+                }
+            },
+        }
+        tiles
+    }
+}
+
+// Ask once whether existing files may be replaced, before the first one is. Returns false if they may not.
+fn confirm_replacing(paths: &[PathBuf], is_confirming_overwrite: bool) -> bool {
+    let existing: Vec<&PathBuf> = paths.iter().filter(|path| path.exists()).collect();
+    let Some(first) = existing.first().filter(|_| is_confirming_overwrite) else {
+        return true;
+    };
+    let message = match existing.len() {
+        1 => format!("{} already exists. Do you want to replace it?", first.display()),
+        count => format!("{} and {} more files already exist. Do you want to replace them?", first.display(), count - 1),
+    };
+    dialog::choice2_default(&message, "Cancel", "Replace", "") == Some(1)
+}
+
+/// Slice the image into tiles and save them as numbered PNG files named after the source image, asking before
+/// existing files are replaced if the confirm policy wants that. Returns the number of tiles written.
+pub fn export_tiles(image: &RgbaImage, spec: TileSpec, output_dir: &Path, base_name: &str, is_confirming_overwrite: bool) -> Result<usize, String> {
+    let tiles = spec.tiles(image.width(), image.height());
+    let digits = tiles.len().to_string().len();
+    let tile_paths: Vec<PathBuf> = (0..tiles.len())
+        .map(|index| output_dir.join(format!("{}_{:0digits$}.png", base_name, index + 1, digits = digits)))
+        .collect();
+    if !confirm_replacing(&tile_paths, is_confirming_overwrite) {
+        log::debug!("Tile export canceled, the files exist already");
+        return Ok(0);
+    }
+    for (&(x, y, width, height), tile_path) in tiles.iter().zip(&tile_paths) {
+        let tile = image::imageops::crop_imm(image, x, y, width, height).to_image();
+        tile.save(tile_path)
+            .map_err(|err| format!("Failed to save {}: {}", tile_path.display(), err))?;
+    }
+    log::debug!("Exported {} tiles to {}", tiles.len(), output_dir.display());
+    Ok(tiles.len())
+}
+
+/// Decode all frames of an animated GIF, PNG or WebP file, fully composited like they are shown
+pub fn animation_frames(image_file: &Path) -> Result<Vec<RgbaImage>, String> {
+    let open = || File::open(image_file).map(BufReader::new).map_err(|err| format!("Failed to open {}: {}", image_file.display(), err));
+    let decode_error = |err: image::ImageError| format!("Failed to decode {}: {}", image_file.display(), err);
+    let extension = image_file.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    let frames = match extension.as_str() {
+        "gif" => image::codecs::gif::GifDecoder::new(open()?).map_err(decode_error)?.into_frames().collect_frames(),
+        "png" | "apng" => image::codecs::png::PngDecoder::new(open()?).map_err(decode_error)?.apng().map_err(decode_error)?.into_frames().collect_frames(),
+        "webp" => image::codecs::webp::WebPDecoder::new(open()?).map_err(decode_error)?.into_frames().collect_frames(),
+        _ => return Err(format!("{} is not an animation", image_file.display())),
+    };
+    Ok(frames.map_err(decode_error)?.into_iter().map(|frame| frame.into_buffer()).collect())
+}
+
+/// Save all frames of an animation as numbered PNG files, asking before existing files are replaced if the
+/// confirm policy wants that. Returns the number of frames written.
+pub fn export_frames(image_file: &Path, output_dir: &Path, base_name: &str, is_confirming_overwrite: bool) -> Result<usize, String> {
+    let frames = animation_frames(image_file)?;
+    let digits = frames.len().to_string().len();
+    let frame_paths: Vec<PathBuf> = (0..frames.len())
+        .map(|index| output_dir.join(format!("{}_frame{:0digits$}.png", base_name, index + 1, digits = digits)))
+        .collect();
+    if !confirm_replacing(&frame_paths, is_confirming_overwrite) {
+        log::debug!("Frame export canceled, the files exist already");
+        return Ok(0);
+    }
+    for (frame, frame_path) in frames.iter().zip(&frame_paths) {
+        frame.save(frame_path)
+            .map_err(|err| format!("Failed to save {}: {}", frame_path.display(), err))?;
+    }
+    log::debug!("Exported {} frames to {}", frames.len(), output_dir.display());
+    Ok(frames.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_grids_and_tile_sizes() {
+        assert_eq!(TileSpec::parse("4x3"), Some(TileSpec::Grid { columns: 4, rows: 3 }));
+        assert_eq!(TileSpec::parse(" 256px "), Some(TileSpec::Size { width: 256, height: 256 }));
+        assert_eq!(TileSpec::parse("256x128PX"), Some(TileSpec::Size { width: 256, height: 128 }));
+        assert_eq!(TileSpec::parse("4"), None);
+        assert_eq!(TileSpec::parse("0x3"), None);
+        assert_eq!(TileSpec::parse("0px"), None);
+        assert_eq!(TileSpec::parse("axb"), None);
+    }
+
+    #[test]
+    fn grid_spreads_the_remainder_over_the_tiles() {
+        let tiles = TileSpec::Grid { columns: 3, rows: 2 }.tiles(10, 5);
+        assert_eq!(tiles, vec![
+            (0, 0, 3, 2), (3, 0, 3, 2), (6, 0, 4, 2),
+            (0, 2, 3, 3), (3, 2, 3, 3), (6, 2, 4, 3),
+        ]);
+    }
+
+    #[test]
+    fn grid_finer_than_the_image_leaves_out_empty_tiles() {
+        let tiles = TileSpec::Grid { columns: 4, rows: 1 }.tiles(2, 1);
+        assert_eq!(tiles, vec![(0, 0, 1, 1), (1, 0, 1, 1)]);
+    }
+
+    #[test]
+    fn fixed_size_tiles_are_smaller_at_the_edges() {
+        let tiles = TileSpec::Size { width: 4, height: 4 }.tiles(10, 6);
+        assert_eq!(tiles, vec![
+            (0, 0, 4, 4), (4, 0, 4, 4), (8, 0, 2, 4),
+            (0, 4, 4, 2), (4, 4, 4, 2), (8, 4, 2, 2),
+        ]);
+    }
+
+    #[test]
+    fn fixed_size_tiles_that_divide_evenly() {
+        let tiles = TileSpec::Size { width: 5, height: 3 }.tiles(10, 3);
+        assert_eq!(tiles, vec![(0, 0, 5, 3), (5, 0, 5, 3)]);
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    cell::RefCell,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+use image::{GrayImage, ImageBuffer, Luma};
+
+pub type Gray16Image = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+// PNG color types without color channels
+const PNG_GRAY: u8 = 0;
+const PNG_GRAY_ALPHA: u8 = 4;
+
+/// Whether the file is a 16 bit grayscale PNG, like heightmaps and scientific data, which FLTK would reduce to 8 bit
+pub fn is_gray16_png(path: &Path) -> bool {
+    // The bit depth and color type follow the signature and the start of the IHDR chunk
+    let mut header = [0u8; 26];
+    let is_read = File::open(path).and_then(|mut file| file.read_exact(&mut header)).is_ok();
+    is_read && header.starts_with(b"\x89PNG") && header[24] == 16 && matches!(header[25], PNG_GRAY | PNG_GRAY_ALPHA)
+}
+
+// A file as it was when it was read, a rewritten file has a different modification time or length
+type FileVersion = (PathBuf, Option<SystemTime>, u64);
+
+thread_local! {
+    // The last image loaded with `load_cached`, kept at full precision so trying another window doesn't decode the file again
+    static CACHED: RefCell<Option<(FileVersion, Rc<Gray16Image>)>> = const { RefCell::new(None) };
+}
+
+fn file_version(path: &Path) -> FileVersion {
+    let metadata = fs::metadata(path).ok();
+    let modified = metadata.as_ref().and_then(|metadata| metadata.modified().ok());
+    (path.to_path_buf(), modified, metadata.map_or(0, |metadata| metadata.len()))
+}
+
+pub fn load(path: &Path) -> Result<Gray16Image, String> {
+    let image = image::open(path).map_err(|err| format!("Decoding \"{}\" failed: {}", path.display(), err))?;
+    Ok(image.into_luma16())
+}
+
+/// Like `load`, but reusing the data of the image loaded last unless the file changed since, for adjusting the
+/// window interactively
+pub fn load_cached(path: &Path) -> Result<Rc<Gray16Image>, String> {
+    let version = file_version(path);
+    if let Some(image) = CACHED.with_borrow(|cached| cached.as_ref().filter(|(cached_version, _)| *cached_version == version).map(|(_, image)| Rc::clone(image))) {
+        return Ok(image);
+    }
+    let image = Rc::new(load(path)?);
+    CACHED.set(Some((version, Rc::clone(&image))));
+    Ok(image)
+}
+
+/// Darkest and brightest value in the image, the window that shows all of the data
+pub fn full_range(image: &Gray16Image) -> (u16, u16) {
+    image.pixels().fold((u16::MAX, u16::MIN), |(low, high), pixel| (low.min(pixel[0]), high.max(pixel[0])))
+}
+
+/// Map the values from low to high to the displayable range, clipping everything outside of it
+pub fn apply_window(image: &Gray16Image, low: u16, high: u16) -> GrayImage {
+    // One entry per possible value, so each pixel is a lookup instead of a division
+    let range = (high.saturating_sub(low) as f32).max(1.0);
+    let levels: Vec<u8> = (0..=u16::MAX)
+        .map(|value| ((value.saturating_sub(low) as f32 / range).min(1.0) * 255.0).round() as u8)
+        .collect();
+    let pixels = image.as_raw().iter().map(|&value| levels[value as usize]).collect();
+    GrayImage::from_raw(image.width(), image.height(), pixels).expect("one level per pixel")
+}
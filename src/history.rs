@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+
+use rand::seq::SliceRandom;
+
+// Number of viewed images remembered
+const MAX_HISTORY: usize = 1000;
+
+/// The images in the order they were actually viewed, to retrace them after random jumps
+#[derive(Default)]
+pub struct History {
+    entries: Vec<PathBuf>,
+    position: usize,
+}
+
+impl History {
+    /// Record the image being shown, unless it's the one the history is already at
+    pub fn visit(&mut self, path: &Path) {
+        if self.entries.get(self.position).is_some_and(|entry| entry == path) {
+            return;
+        }
+        self.entries.truncate(self.position + 1);
+        self.entries.push(path.to_path_buf());
+        if self.entries.len() > MAX_HISTORY {
+            self.entries.remove(0);
+        }
+        self.position = self.entries.len() - 1;
+    }
+
+    /// Step back to the previously viewed image, returning its position in the image order.
+    /// Images that are gone or filtered out are skipped.
+    pub fn back(&mut self, image_files: &[PathBuf], image_order: &[usize]) -> Option<usize> {
+        let start = self.position;
+        while self.position > 0 && !self.entries.is_empty() {
+            self.position -= 1;
+            if let Some(position) = find(&self.entries[self.position], image_files, image_order) {
+                return Some(position);
+            }
+        }
+        self.position = start;
+        None
+    }
+
+    /// Step forward again after going back, returning the image's position in the image order
+    pub fn forward(&mut self, image_files: &[PathBuf], image_order: &[usize]) -> Option<usize> {
+        let start = self.position;
+        while self.position + 1 < self.entries.len() {
+            self.position += 1;
+            if let Some(position) = find(&self.entries[self.position], image_files, image_order) {
+                return Some(position);
+            }
+        }
+        self.position = start;
+        None
+    }
+}
+
+fn find(path: &Path, image_files: &[PathBuf], image_order: &[usize]) -> Option<usize> {
+    image_order.iter().position(|&index| image_files[index] == path)
+}
+
+/// Random order for a slideshow that shows every image once before any of them repeats
+#[derive(Default)]
+pub struct Shuffle {
+    unseen: Vec<PathBuf>,
+}
+
+impl Shuffle {
+    /// Position in the image order of the next random image not seen yet in this round
+    pub fn next(&mut self, image_files: &[PathBuf], image_order: &[usize], current_index: usize) -> Option<usize> {
+        if image_order.len() < 2 {
+            return None;
+        }
+        loop {
+            if self.unseen.is_empty() {
+                // Start the next round, the image being shown comes up again only in the round after
+                self.unseen = image_order.iter()
+                    .filter(|&&index| index != image_order[current_index])
+                    .map(|&index| image_files[index].clone())
+                    .collect();
+                self.unseen.shuffle(&mut rand::thread_rng());
+                log::debug!("New shuffle round of {} images", self.unseen.len());
+            }
+            let path = self.unseen.pop()?;
+            // Images that were deleted or filtered out since the round started are skipped
+            if let Some(position) = find(&path, image_files, image_order) {
+                return Some(position);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> (Vec<PathBuf>, Vec<usize>) {
+        (names.iter().map(PathBuf::from).collect(), (0..names.len()).collect())
+    }
+
+    #[test]
+    fn goes_back_and_forward_through_viewed_images() {
+        let (image_files, image_order) = files(&["a", "b", "c"]);
+        let mut history = History::default();
+        history.visit(Path::new("c"));
+        history.visit(Path::new("a"));
+        history.visit(Path::new("a"));
+        history.visit(Path::new("b"));
+        assert_eq!(history.back(&image_files, &image_order), Some(0));
+        assert_eq!(history.back(&image_files, &image_order), Some(2));
+        assert_eq!(history.back(&image_files, &image_order), None);
+        assert_eq!(history.forward(&image_files, &image_order), Some(0));
+        assert_eq!(history.forward(&image_files, &image_order), Some(1));
+        assert_eq!(history.forward(&image_files, &image_order), None);
+    }
+
+    #[test]
+    fn visiting_after_going_back_drops_the_forward_images() {
+        let (image_files, image_order) = files(&["a", "b", "c"]);
+        let mut history = History::default();
+        history.visit(Path::new("a"));
+        history.visit(Path::new("b"));
+        history.back(&image_files, &image_order);
+        history.visit(Path::new("c"));
+        assert_eq!(history.forward(&image_files, &image_order), None);
+        assert_eq!(history.back(&image_files, &image_order), Some(0));
+    }
+
+    #[test]
+    fn skips_images_that_are_gone() {
+        let (image_files, image_order) = files(&["a", "c"]);
+        let mut history = History::default();
+        history.visit(Path::new("a"));
+        history.visit(Path::new("b"));
+        history.visit(Path::new("c"));
+        assert_eq!(history.back(&image_files, &image_order), Some(0));
+        assert_eq!(history.forward(&image_files, &image_order), Some(1));
+    }
+
+    #[test]
+    fn shuffle_shows_every_other_image_before_repeating() {
+        let (image_files, image_order) = files(&["a", "b", "c", "d"]);
+        let mut shuffle = Shuffle::default();
+        let mut round: Vec<usize> = (0..3).map(|_| shuffle.next(&image_files, &image_order, 0).unwrap()).collect();
+        round.sort();
+        assert_eq!(round, [1, 2, 3]);
+    }
+
+    #[test]
+    fn shuffle_needs_two_images() {
+        let (image_files, image_order) = files(&["a"]);
+        assert_eq!(Shuffle::default().next(&image_files, &image_order, 0), None);
+    }
+}
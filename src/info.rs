@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use exif::{In, Tag};
+use image::{ImageDecoder, ImageReader};
+
+use crate::{measure, metadata, FITS_SUPPORTED_FORMATS, RAW_SUPPORTED_FORMATS};
+
+// EXIF fields summarized for photos, in the order they're printed
+const EXIF_SUMMARY: [Tag; 12] = [
+    Tag::PixelXDimension,
+    Tag::PixelYDimension,
+    Tag::Make,
+    Tag::Model,
+    Tag::LensModel,
+    Tag::DateTimeOriginal,
+    Tag::ExposureTime,
+    Tag::FNumber,
+    Tag::PhotographicSensitivity,
+    Tag::FocalLength,
+    Tag::Orientation,
+    Tag::ColorSpace,
+];
+
+fn describe_fits(path: &Path, lines: &mut Vec<String>) -> Result<(), String> {
+    let headers = measure::fits_headers(path)?;
+    // The image shown is the first one with two dimensions
+    if let Some(image) = headers.iter().find(|header| header.number("NAXIS").unwrap_or(0) >= 2) {
+        lines.push(format!("Dimensions: {}x{}", image.number("NAXIS1").unwrap_or(0), image.number("NAXIS2").unwrap_or(0)));
+        let bits = image.number("BITPIX").unwrap_or(0);
+        lines.push(format!("Bit depth: {}{}", bits.abs(), if bits < 0 { " (floating point)" } else { "" }));
+    }
+    for (index, header) in headers.iter().enumerate() {
+        lines.push(format!("FITS header {}:", index));
+        lines.extend(header.cards.iter().map(|card| format!("  {}", card)));
+    }
+    Ok(())
+}
+
+// Dimensions and bit depth from the header of the formats the image crate reads
+fn describe_image(path: &Path, lines: &mut Vec<String>) -> Result<(), String> {
+    let reader = ImageReader::open(path).and_then(|reader| reader.with_guessed_format()).map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+    let format = reader.format().ok_or("Unknown format".to_string())?;
+    lines.push(format!("Format: {:?}", format));
+    let decoder = reader.into_decoder().map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let (width, height) = decoder.dimensions();
+    let color_type = decoder.color_type();
+    lines.push(format!("Dimensions: {}x{}", width, height));
+    lines.push(format!("Color: {:?}, {} channels", color_type, color_type.channel_count()));
+    lines.push(format!("Bit depth: {} per channel", color_type.bits_per_pixel() / color_type.channel_count() as u16));
+    Ok(())
+}
+
+fn describe_exif(path: &Path, lines: &mut Vec<String>) {
+    let Some(exif) = metadata::read_exif(path) else {
+        return;
+    };
+    let fields: Vec<String> = EXIF_SUMMARY.iter()
+        .filter_map(|&tag| exif.get_field(tag, In::PRIMARY))
+        .map(|field| format!("  {}: {}", field.tag, field.display_value().with_unit(&exif)))
+        .collect();
+    if !fields.is_empty() {
+        lines.push("EXIF:".to_string());
+        lines.extend(fields);
+    }
+}
+
+/// What is known about the image file without decoding it, for --info: its size, format, dimensions
+/// and bit depth, a summary of the EXIF data of photos, and the headers of FITS files
+pub fn describe(path: &Path) -> Result<String, String> {
+    let file_size = path.metadata().map_err(|err| format!("Failed to open {}: {}", path.display(), err))?.len();
+    let mut lines = vec![format!("File: {}", path.display()), format!("Size: {} bytes", file_size)];
+    let lowercase = path.to_string_lossy().to_lowercase();
+    if FITS_SUPPORTED_FORMATS.iter().any(|&format| lowercase.ends_with(format)) {
+        lines.push("Format: FITS".to_string());
+        describe_fits(path, &mut lines)?;
+    } else if let Some(&format) = RAW_SUPPORTED_FORMATS.iter().find(|&&format| lowercase.ends_with(format)) {
+        // RAW files look like TIFF files with only the preview readable, the EXIF data tells about the photo
+        lines.push(format!("Format: RAW ({})", format));
+    } else if let Err(err) = describe_image(path, &mut lines) {
+        lines.push(format!("Error: {}", err));
+    }
+    describe_exif(path, &mut lines);
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+
+/// Find the image a quick jump asks for, as its position in the image order. A number is the
+/// image's position counted from 1, anything else is matched against the file names: names
+/// containing the text come first, then names containing its letters in order, like "img12" for "IMG_0012".
+pub fn find(query: &str, image_files: &[PathBuf], image_order: &[usize]) -> Option<usize> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return None;
+    }
+    if let Ok(number) = query.parse::<usize>() {
+        return (1..=image_order.len()).contains(&number).then(|| number - 1);
+    }
+    image_order.iter()
+        .enumerate()
+        .filter_map(|(position, &index)| {
+            let name = image_files[index].file_name()?.to_string_lossy().to_lowercase();
+            Some((score(&query, &name)?, position))
+        })
+        .min()
+        .map(|(_, position)| position)
+}
+
+// Lower is a better match, None if the name doesn't match at all
+fn score(query: &str, name: &str) -> Option<(usize, usize, usize)> {
+    if let Some(start) = name.find(query) {
+        // Substring matches first, preferring ones at the start and short names
+        return Some((0, start, name.len()));
+    }
+    // Letters in order, preferring matches with fewer letters skipped in between
+    let mut skipped = 0;
+    let mut letters = name.chars();
+    for wanted in query.chars() {
+        loop {
+            let letter = letters.next()?;
+            if letter == wanted {
+                break;
+            }
+            skipped += 1;
+        }
+    }
+    Some((1, skipped, name.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> (Vec<PathBuf>, Vec<usize>) {
+        (names.iter().map(|name| PathBuf::from("/photos").join(name)).collect(), (0..names.len()).collect())
+    }
+
+    #[test]
+    fn numbers_are_positions_counted_from_one() {
+        let (image_files, image_order) = files(&["a.jpg", "b.jpg", "12.jpg"]);
+        assert_eq!(find("2", &image_files, &image_order), Some(1));
+        assert_eq!(find(" 3 ", &image_files, &image_order), Some(2));
+        // A number is a position even if a file is named like it
+        assert_eq!(find("12", &image_files, &image_order), None);
+        assert_eq!(find("0", &image_files, &image_order), None);
+    }
+
+    #[test]
+    fn positions_follow_the_image_order() {
+        let (image_files, _) = files(&["a.jpg", "b.jpg", "c.jpg"]);
+        let image_order = [2, 0, 1];
+        assert_eq!(find("b.jpg", &image_files, &image_order), Some(2));
+        assert_eq!(find("1", &image_files, &image_order), Some(0));
+    }
+
+    #[test]
+    fn substrings_come_before_letters_in_order() {
+        let (image_files, image_order) = files(&["img_0012.jpg", "img12.jpg"]);
+        assert_eq!(find("img12", &image_files, &image_order), Some(1));
+        assert_eq!(find("IMG_0012", &image_files, &image_order), Some(0));
+    }
+
+    #[test]
+    fn substrings_prefer_the_start_and_short_names() {
+        let (image_files, image_order) = files(&["old_sunset.jpg", "sunset_long_name.jpg", "sunset.jpg"]);
+        assert_eq!(find("sunset", &image_files, &image_order), Some(2));
+    }
+
+    #[test]
+    fn letters_in_order_prefer_fewer_skipped() {
+        let (image_files, image_order) = files(&["i_m_g_1_2.png", "img_0012.png"]);
+        assert_eq!(find("img12", &image_files, &image_order), Some(1));
+    }
+
+    #[test]
+    fn no_match_and_empty_queries() {
+        let (image_files, image_order) = files(&["a.jpg"]);
+        assert_eq!(find("zebra", &image_files, &image_order), None);
+        assert_eq!(find("  ", &image_files, &image_order), None);
+    }
+}
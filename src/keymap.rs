@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+
+use fltk::enums::{Key, Shortcut};
+
+/// Everything that can be triggered with the keyboard
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Previous,
+    Next,
+    First,
+    Last,
+    ToggleScaleToFit,
+    Delete,
+    Quit,
+    CopyImage,
+    OpenFolder,
+    ToggleFullscreen,
+    RandomOrder,
+    SortByName,
+    CycleFilter,
+    ToggleSyncView,
+    ToggleMeasure,
+    BlinkCompare,
+    CopyVisibleRegion,
+    CopyPath,
+    RevealInFileManager,
+    EditExternally,
+    CopyTo,
+    MoveTo,
+    CaptureRegion,
+    Rename,
+    NewWindow,
+    ToggleAlwaysOnTop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    ToggleLockView,
+    NudgeLeft,
+    NudgeRight,
+    NudgeUp,
+    NudgeDown,
+    ToggleShuffle,
+    Back,
+    Forward,
+    JumpTo,
+    RandomImage,
+    ToggleStatusBar,
+    ToggleSlideshow,
+    CycleScalingFilter,
+    ToggleSharpening,
+}
+
+impl Action {
+    pub const ALL: [Action; 42] = [
+        Action::Previous, Action::Next, Action::First, Action::Last, Action::ToggleScaleToFit,
+        Action::Delete, Action::Quit, Action::CopyImage, Action::OpenFolder, Action::ToggleFullscreen,
+        Action::RandomOrder, Action::SortByName, Action::CycleFilter, Action::ToggleSyncView,
+        Action::ToggleMeasure, Action::BlinkCompare, Action::CopyVisibleRegion, Action::CopyPath,
+        Action::RevealInFileManager, Action::EditExternally, Action::CopyTo, Action::MoveTo,
+        Action::CaptureRegion, Action::Rename, Action::NewWindow, Action::ToggleAlwaysOnTop,
+        Action::RotateClockwise, Action::RotateCounterClockwise, Action::ToggleLockView, Action::NudgeLeft,
+        Action::NudgeRight, Action::NudgeUp, Action::NudgeDown, Action::ToggleShuffle, Action::Back,
+        Action::Forward, Action::JumpTo, Action::RandomImage, Action::ToggleStatusBar, Action::ToggleSlideshow,
+        Action::CycleScalingFilter, Action::ToggleSharpening,
+    ];
+
+    /// Name used for the action in the [keys] section of the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Previous => "previous",
+            Action::Next => "next",
+            Action::First => "first",
+            Action::Last => "last",
+            Action::ToggleScaleToFit => "scale_to_fit",
+            Action::Delete => "delete",
+            Action::Quit => "quit",
+            Action::CopyImage => "copy_image",
+            Action::OpenFolder => "open_folder",
+            Action::ToggleFullscreen => "fullscreen",
+            Action::RandomOrder => "random_order",
+            Action::SortByName => "sort_by_name",
+            Action::CycleFilter => "cycle_filter",
+            Action::ToggleSyncView => "sync_view",
+            Action::ToggleMeasure => "measure",
+            Action::BlinkCompare => "blink_compare",
+            Action::CopyVisibleRegion => "copy_visible_region",
+            Action::CopyPath => "copy_path",
+            Action::RevealInFileManager => "reveal",
+            Action::EditExternally => "edit",
+            Action::CopyTo => "copy_to",
+            Action::MoveTo => "move_to",
+            Action::CaptureRegion => "capture_region",
+            Action::Rename => "rename",
+            Action::NewWindow => "new_window",
+            Action::ToggleAlwaysOnTop => "always_on_top",
+            Action::RotateClockwise => "rotate_clockwise",
+            Action::RotateCounterClockwise => "rotate_counterclockwise",
+            Action::ToggleLockView => "lock_view",
+            Action::NudgeLeft => "nudge_left",
+            Action::NudgeRight => "nudge_right",
+            Action::NudgeUp => "nudge_up",
+            Action::NudgeDown => "nudge_down",
+            Action::ToggleShuffle => "shuffle",
+            Action::Back => "back",
+            Action::Forward => "forward",
+            Action::JumpTo => "jump_to",
+            Action::RandomImage => "random_image",
+            Action::ToggleStatusBar => "status_bar",
+            Action::ToggleSlideshow => "slideshow",
+            Action::CycleScalingFilter => "scaling_filter",
+            Action::ToggleSharpening => "sharpen",
+        }
+    }
+
+    // Keys used when the config file doesn't bind the action, several keys are separated by commas
+    fn default_keys(&self) -> &'static str {
+        match self {
+            Action::Previous => "Left",
+            Action::Next => "Right",
+            Action::First => "Home",
+            Action::Last => "End",
+            Action::ToggleScaleToFit => "Enter",
+            Action::Delete => "Delete",
+            Action::Quit => "Escape",
+            Action::CopyImage => "Ctrl+C",
+            Action::OpenFolder => "Ctrl+O",
+            Action::ToggleFullscreen => "F",
+            Action::RandomOrder => "R",
+            Action::SortByName => "N",
+            Action::CycleFilter => "T",
+            Action::ToggleSyncView => "S",
+            Action::ToggleMeasure => "M",
+            Action::BlinkCompare => "B",
+            Action::CopyVisibleRegion => "V",
+            Action::CopyPath => "Ctrl+Shift+C",
+            Action::RevealInFileManager => "Ctrl+E",
+            Action::EditExternally => "E",
+            Action::CopyTo => "F5",
+            Action::MoveTo => "F6",
+            Action::CaptureRegion => "Ctrl+Shift+S",
+            Action::Rename => "F2",
+            Action::NewWindow => "Ctrl+N",
+            Action::ToggleAlwaysOnTop => "Ctrl+T",
+            Action::RotateClockwise => "Ctrl+R",
+            Action::RotateCounterClockwise => "Ctrl+L",
+            Action::ToggleLockView => "L",
+            Action::NudgeLeft => "Alt+Left",
+            Action::NudgeRight => "Alt+Right",
+            Action::NudgeUp => "Alt+Up",
+            Action::NudgeDown => "Alt+Down",
+            Action::ToggleShuffle => "Shift+R",
+            Action::Back => "BackSpace",
+            Action::Forward => "Shift+BackSpace",
+            Action::JumpTo => "Ctrl+G",
+            Action::RandomImage => "D",
+            Action::ToggleStatusBar => "I",
+            Action::ToggleSlideshow => "P",
+            Action::CycleScalingFilter => "Q",
+            Action::ToggleSharpening => "U",
+        }
+    }
+}
+
+/// A key together with the modifiers that have to be held down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyCombo {
+    key: Key,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl KeyCombo {
+    /// Parse key descriptions like "Right", "Ctrl+O", "Shift+F5" or "x"
+    pub fn parse(text: &str) -> Option<KeyCombo> {
+        let mut parts: Vec<&str> = text.split('+').map(|part| part.trim()).collect();
+        let key_name = parts.pop()?;
+        let mut combo = KeyCombo { key: parse_key(key_name)?, ctrl: false, alt: false, shift: false };
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => combo.ctrl = true,
+                "alt" => combo.alt = true,
+                "shift" => combo.shift = true,
+                _ => return None,
+            }
+        }
+        Some(combo)
+    }
+}
+
+fn parse_key(name: &str) -> Option<Key> {
+    let key = match name.to_lowercase().as_str() {
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "enter" | "return" => Key::Enter,
+        "delete" | "del" => Key::Delete,
+        "escape" | "esc" => Key::Escape,
+        "backspace" => Key::BackSpace,
+        "tab" => Key::Tab,
+        "insert" => Key::Insert,
+        "space" => Key::from_char(' '),
+        lower => {
+            let mut chars = lower.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => Key::from_char(ch),
+                // Function keys follow each other, starting after FL_F
+                (Some('f'), Some(_)) => {
+                    let number = lower[1..].parse::<i32>().ok().filter(|number| (1..=12).contains(number))?;
+                    Key::from_i32(0xffbd + number)
+                },
+                _ => return None,
+            }
+        }
+    };
+    Some(key)
+}
+
+/// Maps keys to actions, built from the defaults and the [keys] section of the config file
+pub struct Keymap {
+    bindings: Vec<(KeyCombo, Action)>,
+}
+
+impl Keymap {
+    /// Build the keymap, returning warnings about invalid or conflicting bindings alongside it
+    pub fn from_config(keys: &HashMap<String, String>) -> (Keymap, Vec<String>) {
+        let mut warnings = Vec::new();
+        for name in keys.keys() {
+            if !Action::ALL.iter().any(|action| action.name() == name) {
+                warnings.push(format!("Unknown action \"{}\" in key bindings", name));
+            }
+        }
+
+        // The keys set in the config file come first, a default of another action for the same key is dropped
+        let mut bindings: Vec<(KeyCombo, Action)> = Vec::new();
+        for action in Action::ALL {
+            let Some(key_list) = keys.get(action.name()) else {
+                continue;
+            };
+            for key_text in key_list.split(',').map(|key| key.trim()).filter(|key| !key.is_empty()) {
+                let combo = match KeyCombo::parse(key_text) {
+                    Some(combo) => combo,
+                    None => {
+                        warnings.push(format!("Invalid key \"{}\" for action \"{}\"", key_text, action.name()));
+                        continue;
+                    }
+                };
+                // Of two actions the config file binds to a key, the first one wins
+                if let Some((_, bound_action)) = bindings.iter().find(|(bound_combo, _)| *bound_combo == combo) {
+                    warnings.push(format!("Key \"{}\" is bound to both \"{}\" and \"{}\", keeping \"{}\"", key_text, bound_action.name(), action.name(), bound_action.name()));
+                    continue;
+                }
+                bindings.push((combo, action));
+            }
+        }
+        for action in Action::ALL.iter().filter(|action| !keys.contains_key(action.name())) {
+            for key_text in action.default_keys().split(',').map(|key| key.trim()).filter(|key| !key.is_empty()) {
+                if let Some(combo) = KeyCombo::parse(key_text).filter(|combo| !bindings.iter().any(|(bound_combo, _)| bound_combo == combo)) {
+                    bindings.push((combo, *action));
+                }
+            }
+        }
+        (Keymap { bindings }, warnings)
+    }
+
+    /// Find the action for a key press. Shift only has to match if the binding asks for it,
+    /// so "F" also triggers with caps lock or shift held, unless "Shift+F" is bound separately.
+    pub fn action_for(&self, key: Key, state: Shortcut) -> Option<Action> {
+        let pressed = KeyCombo {
+            key,
+            ctrl: state.contains(Shortcut::Ctrl),
+            alt: state.contains(Shortcut::Alt),
+            shift: state.contains(Shortcut::Shift),
+        };
+        let without_shift = KeyCombo { shift: false, ..pressed };
+        self.bindings.iter()
+            .find(|(combo, _)| *combo == pressed)
+            .or_else(|| self.bindings.iter().find(|(combo, _)| *combo == without_shift))
+            .map(|(_, action)| *action)
+    }
+}
+
+/// What turning the mouse wheel does without holding Ctrl, holding Ctrl does the other one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WheelMode {
+    Zoom,
+    Navigate,
+}
+
+/// What dragging with the middle mouse button does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MiddleDragMode {
+    Pan,
+    // Draw a rectangle that is then zoomed into
+    ZoomBox,
+    None,
+}
+
+/// What dragging with the right mouse button does, a right click without dragging always opens the menu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RightDragMode {
+    Menu,
+    Gestures,
+}
+
+/// What clicking near the left or right edge of the window does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeClickMode {
+    None,
+    Navigate,
+}
+
+/// What the back and forward buttons on the side of the mouse do
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SideButtonMode {
+    // Previous and next image in the folder
+    Navigate,
+    // Back and forward through the viewed images
+    History,
+}
+
+// Minimum horizontal distance in pixels for a right-drag to count as a gesture
+const GESTURE_DISTANCE: i32 = 50;
+// Part of the window width at each side where clicks navigate
+const EDGE_ZONE_FRACTION: f64 = 0.15;
+
+/// Mouse behavior from the [mouse] section of the config file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MouseBindings {
+    pub wheel: WheelMode,
+    pub middle_drag: MiddleDragMode,
+    pub right_drag: RightDragMode,
+    pub edge_click: EdgeClickMode,
+    pub side_buttons: SideButtonMode,
+}
+
+impl Default for MouseBindings {
+    fn default() -> Self {
+        MouseBindings { wheel: WheelMode::Zoom, middle_drag: MiddleDragMode::Pan, right_drag: RightDragMode::Menu, edge_click: EdgeClickMode::None, side_buttons: SideButtonMode::Navigate }
+    }
+}
+
+impl MouseBindings {
+    /// Build the mouse bindings, returning warnings about invalid settings alongside them
+    pub fn from_config(mouse: &HashMap<String, String>) -> (MouseBindings, Vec<String>) {
+        let mut bindings = MouseBindings::default();
+        let mut warnings = Vec::new();
+        for (name, value) in mouse {
+            let is_valid = match (name.as_str(), value.trim().to_lowercase().as_str()) {
+                ("wheel", "zoom") => { bindings.wheel = WheelMode::Zoom; true },
+                ("wheel", "navigate") => { bindings.wheel = WheelMode::Navigate; true },
+                ("middle_drag", "pan") => { bindings.middle_drag = MiddleDragMode::Pan; true },
+                ("middle_drag", "zoom_box") => { bindings.middle_drag = MiddleDragMode::ZoomBox; true },
+                ("middle_drag", "none") => { bindings.middle_drag = MiddleDragMode::None; true },
+                ("right_drag", "menu") => { bindings.right_drag = RightDragMode::Menu; true },
+                ("right_drag", "gestures") => { bindings.right_drag = RightDragMode::Gestures; true },
+                ("edge_click", "none") => { bindings.edge_click = EdgeClickMode::None; true },
+                ("edge_click", "navigate") => { bindings.edge_click = EdgeClickMode::Navigate; true },
+                ("side_buttons", "navigate") => { bindings.side_buttons = SideButtonMode::Navigate; true },
+                ("side_buttons", "history") => { bindings.side_buttons = SideButtonMode::History; true },
+                ("wheel", _) | ("middle_drag", _) | ("right_drag", _) | ("edge_click", _) | ("side_buttons", _) => false,
+                _ => {
+                    warnings.push(format!("Unknown mouse setting \"{}\"", name));
+                    continue;
+                }
+            };
+            if !is_valid {
+                warnings.push(format!("Invalid value \"{}\" for mouse setting \"{}\"", value, name));
+            }
+        }
+        (bindings, warnings)
+    }
+
+    /// Navigation action for a wheel turn, or None if the wheel should zoom
+    pub fn wheel_action(&self, scrolled_down: bool, state: Shortcut) -> Option<Action> {
+        let navigates = (self.wheel == WheelMode::Navigate) != state.contains(Shortcut::Ctrl);
+        match (navigates, scrolled_down) {
+            (false, _) => None,
+            (true, true) => Some(Action::Next),
+            (true, false) => Some(Action::Previous),
+        }
+    }
+
+    /// Navigation action for a click at a horizontal position, or None if it's not on an edge or edge clicks are off
+    pub fn edge_action(&self, x: i32, window_width: i32) -> Option<Action> {
+        let zone = (window_width as f64 * EDGE_ZONE_FRACTION) as i32;
+        match self.edge_click {
+            EdgeClickMode::None => None,
+            EdgeClickMode::Navigate if x < zone => Some(Action::Previous),
+            EdgeClickMode::Navigate if x >= window_width - zone => Some(Action::Next),
+            EdgeClickMode::Navigate => None,
+        }
+    }
+
+    /// Action for the forward or back button on the side of the mouse
+    pub fn side_button_action(&self, is_forward: bool) -> Action {
+        match (self.side_buttons, is_forward) {
+            (SideButtonMode::Navigate, true) => Action::Next,
+            (SideButtonMode::Navigate, false) => Action::Previous,
+            (SideButtonMode::History, true) => Action::Forward,
+            (SideButtonMode::History, false) => Action::Back,
+        }
+    }
+
+    /// Action for a finished right-drag, or None if the mouse didn't move far enough sideways
+    pub fn gesture_action(&self, dx: i32, dy: i32) -> Option<Action> {
+        if dx.abs() < GESTURE_DISTANCE || dx.abs() < dy.abs() {
+            None
+        } else if dx > 0 {
+            Some(Action::Next)
+        } else {
+            Some(Action::Previous)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combo(key: Key, ctrl: bool, alt: bool, shift: bool) -> KeyCombo {
+        KeyCombo { key, ctrl, alt, shift }
+    }
+
+    #[test]
+    fn parses_keys_with_modifiers() {
+        assert_eq!(KeyCombo::parse("x"), Some(combo(Key::from_char('x'), false, false, false)));
+        assert_eq!(KeyCombo::parse("Ctrl+O"), Some(combo(Key::from_char('o'), true, false, false)));
+        assert_eq!(KeyCombo::parse("control + alt + Right"), Some(combo(Key::Right, true, true, false)));
+        assert_eq!(KeyCombo::parse("Shift+BackSpace"), Some(combo(Key::BackSpace, false, false, true)));
+        assert_eq!(KeyCombo::parse("Space"), Some(combo(Key::from_char(' '), false, false, false)));
+    }
+
+    #[test]
+    fn parses_function_keys() {
+        assert_eq!(KeyCombo::parse("Shift+F5"), Some(combo(Key::from_i32(0xffc2), false, false, true)));
+        assert_eq!(KeyCombo::parse("F12"), Some(combo(Key::from_i32(0xffc9), false, false, false)));
+        assert_eq!(KeyCombo::parse("F13"), None);
+        assert_eq!(KeyCombo::parse("F0"), None);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_modifiers() {
+        assert_eq!(KeyCombo::parse("Hyper+X"), None);
+        assert_eq!(KeyCombo::parse("Ctrl+"), None);
+        assert_eq!(KeyCombo::parse("Foo"), None);
+    }
+
+    #[test]
+    fn configured_keys_replace_defaults_of_other_actions() {
+        let keys = HashMap::from([("random_image".to_string(), "R".to_string())]);
+        let (keymap, warnings) = Keymap::from_config(&keys);
+        assert!(warnings.is_empty());
+        assert_eq!(keymap.action_for(Key::from_char('r'), Shortcut::None), Some(Action::RandomImage));
+        // The action keeps its other default keys
+        assert_eq!(keymap.action_for(Key::from_char('d'), Shortcut::None), None);
+    }
+
+    #[test]
+    fn warns_about_keys_configured_twice() {
+        let keys = HashMap::from([("next".to_string(), "N".to_string()), ("previous".to_string(), "N".to_string())]);
+        let (keymap, warnings) = Keymap::from_config(&keys);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(keymap.action_for(Key::from_char('n'), Shortcut::None), Some(Action::Previous));
+    }
+}
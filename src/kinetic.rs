@@ -0,0 +1,92 @@
+use std::time::Instant;
+
+use fltk::app;
+
+// Custom FLTK event sent to the main window for every step of a glide
+pub const GLIDE_EVENT: i32 = 46;
+// Seconds between the steps of a glide, about the refresh rate of a screen
+const GLIDE_INTERVAL: f64 = 1.0 / 60.0;
+// How quickly a glide slows down, the speed drops to 1/e after 1/DECELERATION seconds
+const DECELERATION: f64 = 4.0;
+// Speeds in window pixels per second that start a glide when the button is released, and end it
+const MIN_START_SPEED: f64 = 300.0;
+const MIN_SPEED: f64 = 20.0;
+// A release later than this after the last movement means the mouse was held still, so there's no glide
+const MAX_RELEASE_DELAY: f64 = 0.05;
+
+/// Lets a panned image keep moving after the mouse button is released, slowing down with friction
+#[derive(Default)]
+pub struct Glide {
+    // Window pixels per second
+    velocity: (f64, f64),
+    last_move: Option<Instant>,
+    last_step: Option<Instant>,
+    // Fractions of pixels not moved yet, so slow glides don't stop early
+    remainder: (f64, f64),
+    timeout: Option<app::TimeoutHandle>,
+}
+
+impl Glide {
+    /// Follow the speed of the pan while the mouse is dragged
+    pub fn track(&mut self, dx: i32, dy: i32) {
+        let now = Instant::now();
+        let elapsed = self.last_move.map(|last_move| now.duration_since(last_move).as_secs_f64()).unwrap_or(0.0);
+        if elapsed > 0.0 {
+            let speed = (dx as f64 / elapsed, dy as f64 / elapsed);
+            // Smoothed, as single mouse moves are jittery
+            self.velocity = if elapsed > MAX_RELEASE_DELAY {
+                speed
+            } else {
+                ((self.velocity.0 + speed.0) / 2.0, (self.velocity.1 + speed.1) / 2.0)
+            };
+        }
+        self.last_move = Some(now);
+    }
+
+    /// Start gliding when the button is released during a fast pan
+    pub fn start(&mut self) {
+        let is_moving = self.last_move.take().is_some_and(|last_move| last_move.elapsed().as_secs_f64() < MAX_RELEASE_DELAY);
+        if !is_moving || self.velocity.0.hypot(self.velocity.1) < MIN_START_SPEED {
+            self.velocity = (0.0, 0.0);
+            return;
+        }
+        self.stop_timer();
+        self.remainder = (0.0, 0.0);
+        self.last_step = Some(Instant::now());
+        self.timeout = Some(app::add_timeout3(GLIDE_INTERVAL, |handle| {
+            let _ = app::handle_main(GLIDE_EVENT);
+            app::repeat_timeout3(GLIDE_INTERVAL, handle);
+        }));
+    }
+
+    /// Distance to move the image by for the next step, or None when the glide is over
+    pub fn step(&mut self) -> Option<(i32, i32)> {
+        let last_step = self.last_step?;
+        let now = Instant::now();
+        let elapsed = now.duration_since(last_step).as_secs_f64();
+        self.last_step = Some(now);
+        let friction = (-DECELERATION * elapsed).exp();
+        self.velocity = (self.velocity.0 * friction, self.velocity.1 * friction);
+        if self.velocity.0.hypot(self.velocity.1) < MIN_SPEED {
+            self.stop();
+            return None;
+        }
+        let distance = (self.velocity.0 * elapsed + self.remainder.0, self.velocity.1 * elapsed + self.remainder.1);
+        let pixels = (distance.0.trunc(), distance.1.trunc());
+        self.remainder = (distance.0 - pixels.0, distance.1 - pixels.1);
+        Some((pixels.0 as i32, pixels.1 as i32))
+    }
+
+    /// End the glide, e.g. when the image is grabbed again
+    pub fn stop(&mut self) {
+        self.stop_timer();
+        self.velocity = (0.0, 0.0);
+        self.last_step = None;
+    }
+
+    fn stop_timer(&mut self) {
+        if let Some(handle) = self.timeout.take() {
+            app::remove_timeout3(handle);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use fltk::{app, frame::Frame, image::JpegImage, prelude::*};
+
+// How often the newest live view frame is shown, in seconds
+const LIVE_VIEW_INTERVAL: f64 = 1.0 / 25.0;
+
+/// Shows the live view of a tethered camera in the image frame. The frames come from gphoto2,
+/// which streams them as concatenated JPEG images.
+pub struct LiveView {
+    camera: Child,
+    // The newest frame with its number, counting from 1
+    latest_frame: Arc<Mutex<Option<(u64, Vec<u8>)>>>,
+    timeout: app::TimeoutHandle,
+}
+
+// Position of the first JPEG end marker after `start`
+fn find_marker(data: &[u8], marker: [u8; 2], start: usize) -> Option<usize> {
+    data.get(start..)?.windows(2).position(|window| window == marker).map(|position| position + start)
+}
+
+impl LiveView {
+    pub fn start(frame: &Frame, width: i32, height: i32) -> Result<LiveView, String> {
+        if cfg!(target_os = "windows") {
+            return Err("Camera live view needs gphoto2, which is not available on Windows".to_string());
+        }
+        let mut camera = Command::new("gphoto2")
+            .args(["--capture-movie", "--stdout"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("Failed to start gphoto2: {}", err))?;
+        let mut stdout = camera.stdout.take().ok_or("Failed to read from gphoto2".to_string())?;
+
+        // Split the stream into JPEG images and keep only the newest one, older frames are of no use
+        let latest_frame = Arc::new(Mutex::new(None));
+        let reader_frame = Arc::clone(&latest_frame);
+        thread::spawn(move || {
+            let mut sequence = 0;
+            let mut buffer = Vec::new();
+            let mut chunk = [0u8; 65536];
+            while let Ok(size) = stdout.read(&mut chunk) {
+                if size == 0 {
+                    break;
+                }
+                buffer.extend_from_slice(&chunk[..size]);
+                while let Some(start) = find_marker(&buffer, [0xFF, 0xD8], 0) {
+                    let end = match find_marker(&buffer, [0xFF, 0xD9], start + 2) {
+                        Some(end) => end + 2,
+                        None => break,
+                    };
+                    sequence += 1;
+                    if let Ok(mut latest) = reader_frame.lock() {
+                        *latest = Some((sequence, buffer[start..end].to_vec()));
+                    }
+                    buffer.drain(..end);
+                }
+            }
+            log::debug!("Camera live view stream ended");
+        });
+
+        let mut frame = frame.clone();
+        let display_frame = Arc::clone(&latest_frame);
+        let mut shown_sequence = 0;
+        let timeout = app::add_timeout3(LIVE_VIEW_INTERVAL, move |handle| {
+            // The camera may send fewer frames than are shown, a frame already shown isn't decoded again
+            let data = display_frame.lock().ok()
+                .and_then(|latest| latest.as_ref().filter(|(sequence, _)| *sequence != shown_sequence).cloned());
+            if let Some((sequence, data)) = data {
+                shown_sequence = sequence;
+                if let Ok(mut image) = JpegImage::from_data(&data) {
+                    image.scale(width, height, true, true);
+                    frame.set_image(Some(image));
+                    app::redraw();
+                }
+            }
+            app::repeat_timeout3(LIVE_VIEW_INTERVAL, handle);
+        });
+        Ok(LiveView { camera, latest_frame, timeout })
+    }
+
+    /// Save the frame currently shown as a JPEG file in the directory, returning its path
+    pub fn save_frame(&self, directory: &Path) -> Result<PathBuf, String> {
+        let data = self.latest_frame.lock().ok()
+            .and_then(|latest| latest.as_ref().map(|(_, data)| data.clone()))
+            .ok_or("No frame received from the camera yet".to_string())?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_millis()).unwrap_or_default();
+        let path = directory.join(format!("liveview-{}.jpg", timestamp));
+        std::fs::write(&path, data).map_err(|err| format!("Failed to save {}: {}", path.display(), err))?;
+        log::debug!("Saved live view frame to {}", path.display());
+        Ok(path)
+    }
+}
+
+impl Drop for LiveView {
+    fn drop(&mut self) {
+        app::remove_timeout3(self.timeout);
+        let _ = self.camera.kill();
+        let _ = self.camera.wait();
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, AtomicU32, Ordering}, Arc, Condvar, Mutex, Once},
+    thread,
+};
+
+use fltk::app;
+
+use crate::perf;
+
+// Custom FLTK event sent to the main window when an image decoded in the background is ready
+pub const LOADED_EVENT: i32 = 47;
+// Custom FLTK event for preloading the neighbors of the image that was just shown
+pub const PRELOAD_EVENT: i32 = 48;
+// How often to check whether the background decode is done, in seconds
+const LOADED_POLL_INTERVAL: f64 = 0.02;
+// Progress is kept in thousandths
+const PROGRESS_STEPS: f64 = 1000.0;
+// Decodes running at the same time. RAW and FITS decodes use several cores and lots of memory each,
+// so navigating quickly queues the images instead of decoding all of them at once.
+const DECODE_WORKERS: usize = 2;
+
+// Decodes waiting for a worker, the image being waited for first
+static QUEUE: Mutex<VecDeque<Job>> = Mutex::new(VecDeque::new());
+static JOB_QUEUED: Condvar = Condvar::new();
+static START_WORKERS: Once = Once::new();
+
+/// RGB pixels of an image decoded on a worker thread. FLTK images can only be made on the UI
+/// thread, so decoders that run in the background return these instead.
+pub struct DecodedImage {
+    pub data: Vec<u8>,
+    pub width: i32,
+    pub height: i32,
+}
+
+pub type Decoder = fn(&str) -> Result<DecodedImage, String>;
+
+type LoadResult = Arc<Mutex<Option<Result<DecodedImage, String>>>>;
+
+/// How far a decode has come and whether it's still wanted, shared by the worker thread and the loader
+#[derive(Default)]
+struct Task {
+    progress: AtomicU32,
+    is_canceled: AtomicBool,
+}
+
+thread_local! {
+    // The task of the decode running on a worker thread
+    static WORKER_TASK: RefCell<Option<Arc<Task>>> = const { RefCell::new(None) };
+    // On the UI thread, the task of the image being waited for
+    static WAITING_TASK: RefCell<Option<Arc<Task>>> = const { RefCell::new(None) };
+}
+
+// A decode running on a worker thread
+struct Decoding {
+    result: LoadResult,
+    task: Arc<Task>,
+}
+
+// A decode waiting in the queue
+struct Job {
+    image_file: String,
+    decode: Decoder,
+    result: LoadResult,
+    task: Arc<Task>,
+}
+
+struct PendingLoad {
+    path: PathBuf,
+    decoding: Decoding,
+    poll_timeout: app::TimeoutHandle,
+}
+
+/// Decodes slow formats like RAW and FITS on a worker thread, so the window stays responsive and
+/// keeps showing the previous image until the new one is ready. Also decodes the neighbors of the
+/// image being shown ahead of time, so going to them is instant.
+#[derive(Default)]
+pub struct Loader {
+    pending: Option<PendingLoad>,
+    preloaded: HashMap<PathBuf, Decoding>,
+}
+
+// Take decodes from the queue one after another
+fn run_worker() {
+    loop {
+        let job = {
+            let Ok(mut queue) = QUEUE.lock() else {
+                return;
+            };
+            loop {
+                match queue.pop_front() {
+                    Some(job) => break job,
+                    None => match JOB_QUEUED.wait(queue) {
+                        Ok(waited) => queue = waited,
+                        Err(_) => return,
+                    },
+                }
+            }
+        };
+        // Navigating quickly makes images obsolete while they wait, those are skipped
+        if job.task.is_canceled.load(Ordering::Relaxed) {
+            continue;
+        }
+        WORKER_TASK.with(|task| *task.borrow_mut() = Some(Arc::clone(&job.task)));
+        // A decoder that panics still leaves a result, or the loader would wait for it forever
+        let decoded = panic::catch_unwind(AssertUnwindSafe(|| perf::time("Decode", || (job.decode)(&job.image_file))))
+            .unwrap_or_else(|_| Err(format!("Decoding \"{}\" failed unexpectedly", job.image_file)));
+        WORKER_TASK.with(|task| task.borrow_mut().take());
+        if let Ok(mut result) = job.result.lock() {
+            *result = Some(decoded);
+        }
+    }
+}
+
+// Queue the image for decoding on a worker thread, first if it's waited for, the result is filled in when it's done
+fn spawn_decode(path: &Path, decode: Decoder, is_waited_for: bool) -> Decoding {
+    START_WORKERS.call_once(|| {
+        for _ in 0..DECODE_WORKERS {
+            thread::spawn(run_worker);
+        }
+    });
+    let result: LoadResult = Arc::new(Mutex::new(None));
+    let task = Arc::new(Task::default());
+    let job = Job { image_file: path.to_string_lossy().to_string(), decode, result: Arc::clone(&result), task: Arc::clone(&task) };
+    if let Ok(mut queue) = QUEUE.lock() {
+        if is_waited_for {
+            queue.push_front(job);
+        } else {
+            queue.push_back(job);
+        }
+        JOB_QUEUED.notify_one();
+    }
+    Decoding { result, task }
+}
+
+// Move a preload that is still waiting to the front of the queue, the image is wanted now
+fn move_to_front(task: &Arc<Task>) {
+    if let Ok(mut queue) = QUEUE.lock() {
+        if let Some(position) = queue.iter().position(|job| Arc::ptr_eq(&job.task, task)) {
+            if let Some(job) = queue.remove(position) {
+                queue.push_front(job);
+            }
+        }
+    }
+}
+
+/// Report how far the decode running on this worker thread has come, from 0 to 1
+pub fn set_progress(fraction: f64) {
+    WORKER_TASK.with(|task| {
+        if let Some(task) = task.borrow().as_ref() {
+            task.progress.store((fraction.clamp(0.0, 1.0) * PROGRESS_STEPS) as u32, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Whether the image decoded on this worker thread isn't wanted anymore, long decodes stop early then
+pub fn is_canceled() -> bool {
+    WORKER_TASK.with(|task| task.borrow().as_ref().is_some_and(|task| task.is_canceled.load(Ordering::Relaxed)))
+}
+
+/// For decoders to return early between their expensive steps once the image isn't wanted anymore
+pub fn check_canceled() -> Result<(), String> {
+    if is_canceled() {
+        Err("Canceled".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// How far the decode of the image being waited for has come, for decodes that report their progress
+pub fn progress() -> Option<f64> {
+    WAITING_TASK.with(|task| {
+        let progress = task.borrow().as_ref()?.progress.load(Ordering::Relaxed);
+        (progress > 0).then(|| progress as f64 / PROGRESS_STEPS)
+    })
+}
+
+fn is_finished(result: &LoadResult) -> bool {
+    result.lock().is_ok_and(|result| result.is_some())
+}
+
+impl Loader {
+    /// Start decoding the image, LOADED_EVENT is sent when it's done. An image that is still
+    /// being decoded is dropped, only the newest one is shown.
+    pub fn start(&mut self, path: &Path, decode: Decoder) {
+        self.cancel();
+        // A preload that is still running is waited for instead of decoding the image twice
+        let decoding = match self.preloaded.remove(path) {
+            Some(decoding) => {
+                move_to_front(&decoding.task);
+                decoding
+            }
+            None => spawn_decode(path, decode, true),
+        };
+
+        let poll_result = Arc::clone(&decoding.result);
+        let poll_task = Arc::clone(&decoding.task);
+        let mut shown_progress = 0;
+        let poll_timeout = app::add_timeout3(LOADED_POLL_INTERVAL, move |handle| {
+            if is_finished(&poll_result) {
+                let _ = app::handle_main(LOADED_EVENT);
+            } else {
+                // Redraw the progress bar when the decode reports progress
+                let progress = poll_task.progress.load(Ordering::Relaxed);
+                if progress != shown_progress {
+                    shown_progress = progress;
+                    app::redraw();
+                }
+                app::repeat_timeout3(LOADED_POLL_INTERVAL, handle);
+            }
+        });
+        WAITING_TASK.with(|task| *task.borrow_mut() = Some(Arc::clone(&decoding.task)));
+        self.pending = Some(PendingLoad { path: path.to_path_buf(), decoding, poll_timeout });
+        self.update_queue_depth();
+    }
+
+    /// Forget the image being decoded, e.g. because a different one was shown in the meantime.
+    /// Decoders that check for it stop early.
+    pub fn cancel(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            app::remove_timeout3(pending.poll_timeout);
+            pending.decoding.task.is_canceled.store(true, Ordering::Relaxed);
+            WAITING_TASK.with(|task| task.borrow_mut().take());
+            self.update_queue_depth();
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// The path and decoding result of the image once it's done
+    pub fn take_finished(&mut self) -> Option<(PathBuf, Result<DecodedImage, String>)> {
+        let decoded = self.pending.as_ref()?.decoding.result.lock().ok()?.take()?;
+        let pending = self.pending.take()?;
+        WAITING_TASK.with(|task| task.borrow_mut().take());
+        self.update_queue_depth();
+        Some((pending.path, decoded))
+    }
+
+    /// Decode the images in the background, canceling earlier preloads that aren't among them
+    pub fn preload(&mut self, images: Vec<(PathBuf, Decoder)>) {
+        self.preloaded.retain(|path, decoding| {
+            let is_wanted = images.iter().any(|(image, _)| image == path);
+            if !is_wanted {
+                decoding.task.is_canceled.store(true, Ordering::Relaxed);
+            }
+            is_wanted
+        });
+        for (path, decode) in images {
+            let is_loading = self.pending.as_ref().is_some_and(|pending| pending.path == path);
+            if !is_loading && !self.preloaded.contains_key(&path) {
+                log::debug!("Preloading {}", path.display());
+                let decoding = spawn_decode(&path, decode, false);
+                self.preloaded.insert(path, decoding);
+            }
+        }
+        self.update_queue_depth();
+    }
+
+    /// The decoded image if preloading it is done
+    pub fn take_preloaded(&mut self, path: &Path) -> Option<Result<DecodedImage, String>> {
+        let decoded = self.preloaded.get(path)?.result.lock().ok()?.take()?;
+        self.preloaded.remove(path);
+        self.update_queue_depth();
+        Some(decoded)
+    }
+
+    /// Preload the neighbors of the image that is shown once the current event is handled
+    pub fn schedule_preload() {
+        app::add_timeout3(0.0, |_| {
+            let _ = app::handle_main(PRELOAD_EVENT);
+        });
+    }
+
+    fn update_queue_depth(&self) {
+        let preloading = self.preloaded.values().filter(|decoding| !is_finished(&decoding.result)).count();
+        perf::set_queue_depth(preloading + usize::from(self.pending.is_some()));
+    }
+}
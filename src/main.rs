@@ -4,15 +4,89 @@
     ),
     windows_subsystem = "windows"
   )]
-use fltk::{app::{self, MouseWheel}, dialog, enums::{Color, Event}, frame::Frame, image::{AnimGifImage, AnimGifImageFlags, SharedImage}, prelude::*, window::Window};
+use fltk::{app::{self, MouseWheel}, dialog, enums::{Color, Cursor, Event, Key}, frame::Frame, image::{AnimGifImage, AnimGifImageFlags, SharedImage}, prelude::*, window::Window};
 use arboard::{Clipboard, ImageData};
-use rand::seq::SliceRandom;
-use std::{env, error::Error, fs, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+use rand::{seq::SliceRandom, Rng};
+use std::{cell::RefCell, collections::{HashMap, HashSet}, env, error::Error, fs, path::{Path, PathBuf}, rc::Rc, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}};
 use image::{ImageReader, Rgb};
 use image::GenericImageView;
 use rustronomy_fits as rsf;
 use log;
 
+mod align;
+mod analysis;
+mod animation;
+mod cli;
+mod colormodel;
+mod config;
+mod decodecache;
+mod download;
+mod export;
+mod gray16;
+mod history;
+mod info;
+mod jump;
+mod keymap;
+mod loader;
+mod kinetic;
+mod liveview;
+mod mapped;
+mod measure;
+mod merge;
+mod metadata;
+mod mipmap;
+mod normalmap;
+mod overlay;
+mod palette;
+mod panorama;
+mod perf;
+mod pixelart;
+mod preview;
+mod progress;
+mod relief;
+mod removed;
+mod render;
+mod rotation;
+mod scaling;
+mod scan;
+mod scanner;
+mod scrub;
+mod share;
+mod similarity;
+mod statistics;
+mod stdin;
+mod sync;
+mod target;
+#[cfg(feature = "tagging")]
+mod tagging;
+mod testpattern;
+mod thumbnails;
+mod tiling;
+mod toolbar;
+mod viewlock;
+#[cfg(feature = "webcam")]
+mod webcam;
+use crate::analysis::{AnalysisView, AnalysisWorker, ANALYSIS_EVENT};
+use crate::animation::FrameAnimation;
+use crate::colormodel::ColorModel;
+use crate::export::TileSpec;
+use crate::history::{History, Shuffle};
+use crate::keymap::{Action, Keymap, MiddleDragMode, MouseBindings, RightDragMode};
+use crate::kinetic::{Glide, GLIDE_EVENT};
+use crate::liveview::LiveView;
+use crate::loader::{DecodedImage, Decoder, Loader, LOADED_EVENT, PRELOAD_EVENT};
+use crate::measure::{Measurement, PixelScale};
+use crate::overlay::{EdgeArrow, MeasureLine, Overlay, TargetFrame};
+use crate::removed::RemovedFile;
+use crate::rotation::Rotations;
+use crate::scaling::ScalingFilter;
+use crate::scan::{DirectoryScan, SCAN_EVENT};
+use crate::scrub::{Scrub, SCRUB_EVENT};
+use crate::sync::{ViewState, ViewSync, SYNC_EVENT, SYNC_POLL_INTERVAL};
+use crate::target::Target;
+use crate::toolbar::{Toolbar, TOOLBAR_EVENT};
+use crate::viewlock::ViewLock;
+
 #[cfg(target_os = "windows")]
 mod windows;
 #[cfg(target_os = "windows")]
@@ -24,7 +98,90 @@ pub const FLTK_SUPPORTED_FORMATS: [&str; 9] = ["jpg", "jpeg", "png", "bmp", "svg
 pub const RAW_SUPPORTED_FORMATS: [&str; 23] = ["mrw", "arw", "srf", "sr2", "nef", "mef", "orf", "srw", "erf", "kdc", "dcs", "rw2", "raf", "dcr", "dng", "pef", "crw", "iiq", "3fr", "nrw", "mos", "cr2", "ari"];
 pub const FITS_SUPPORTED_FORMATS: [&str; 2] = ["fits", "fit"];
 
-const KEY_C : fltk::enums::Key = fltk::enums::Key::from_char('c');
+// Size of the cached thumbnails the similarity search compares
+const SIMILARITY_THUMBNAIL_SIZE: u32 = 128;
+// Maximum size of the longer edge for quick previews computed from several images
+const PREVIEW_SIZE: u32 = 1600;
+
+// Time for the window to disappear before the screen is captured, in milliseconds
+const CAPTURE_DELAY: u64 = 300;
+
+// Size of the color swatches in the palette window
+const SWATCH_WIDTH: i32 = 240;
+const SWATCH_HEIGHT: i32 = 40;
+const MAX_PALETTE_COLORS: usize = 16;
+
+// Number of shots followed at most when looking for a panorama
+const MAX_PANORAMA_IMAGES: usize = 12;
+// Largest size of the onion skin window, and the height of its opacity slider
+const ONION_SKIN_WIDTH: u32 = 1024;
+const ONION_SKIN_HEIGHT: u32 = 768;
+const SLIDER_HEIGHT: i32 = 30;
+
+// Mouse movement in pixels up to which pressing and releasing a button counts as a click
+const CLICK_DISTANCE: i32 = 5;
+// FLTK's FL_ZOOM_GESTURE, sent for pinching on a macOS trackpad with the magnification in thousandths as dy
+#[cfg(target_os = "macos")]
+const ZOOM_GESTURE_EVENT: i32 = 26;
+// Custom FLTK event sent to the main window for pinch gestures on a touch screen
+#[cfg(target_os = "windows")]
+const PINCH_EVENT: i32 = 45;
+
+// Seconds each image is shown when blink comparing
+const BLINK_INTERVAL: f64 = 0.5;
+
+// Custom FLTK event sent to the main window when the slideshow shows the next image
+const SLIDESHOW_EVENT: i32 = 44;
+// Seconds each image is shown in the slideshow, if the config file doesn't set it
+const SLIDESHOW_INTERVAL: f64 = 5.0;
+
+// Custom FLTK event sent to the main window when files in the current directory changed
+const DIRECTORY_CHANGED_EVENT: i32 = 41;
+// How RAW and FITS images are processed for display, the decode cache keeps the results of each apart
+const RAW_PROCESSING: &str = "raw-8bit";
+const FITS_PROCESSING: &str = "fits-log";
+// Share of a FITS decode done once the file is read and once the range of values is known, the rest is the stretch
+const FITS_READ_PROGRESS: f64 = 0.1;
+const FITS_RANGE_PROGRESS: f64 = 0.2;
+// Largest image width or height that is drawn, beyond this FLTK's drawing fails or gets clipped on
+// some systems, e.g. X11 coordinates are 16 bit
+const MAX_IMAGE_SIZE: i32 = 16384;
+// Size quick previews are decoded at for a placeholder while scrubbing through images with a key held down
+const SCRUB_PREVIEW_SIZE: i32 = 256;
+// JPEG and PNG files this large are decoded in the background, JPEG images showing a quick low resolution pass first
+const PROGRESSIVE_MIN_BYTES: u64 = 4 * 1024 * 1024;
+// Number of images preloaded on each side of the current one, if the config file doesn't set it
+const DEFAULT_PRELOAD: usize = 1;
+// How often to check for directory changes, in seconds
+const DIRECTORY_POLL_INTERVAL: f64 = 0.5;
+
+// Restricts directory navigation to a subset of the supported formats
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum FileTypeFilter {
+    All,
+    RawOnly,
+    JpegOnly,
+}
+
+impl FileTypeFilter {
+    fn matches(&self, path: &Path) -> bool {
+        let filename = path.to_string_lossy().to_lowercase();
+        match self {
+            FileTypeFilter::All => true,
+            FileTypeFilter::RawOnly => RAW_SUPPORTED_FORMATS.iter().any(|&format| filename.ends_with(format)),
+            FileTypeFilter::JpegOnly => ["jpg", "jpeg"].iter().any(|&format| filename.ends_with(format)),
+        }
+    }
+
+    // Cycle through the filters with the keyboard
+    fn next(&self) -> FileTypeFilter {
+        match self {
+            FileTypeFilter::All => FileTypeFilter::RawOnly,
+            FileTypeFilter::RawOnly => FileTypeFilter::JpegOnly,
+            FileTypeFilter::JpegOnly => FileTypeFilter::All,
+        }
+    }
+}
 
 // Enum to hold the image type, either a shared image or an animated gif
 #[derive(Clone)]
@@ -33,35 +190,338 @@ enum ImageType {
     AnimatedGif(AnimGifImage),
 }
 
-fn load_and_display_image(original_image: &mut ImageType, frame: &mut Frame, wind: &mut Window, path: &PathBuf, zoom_factor: &mut f64, is_fullscreen: bool, is_scaled_to_fit: bool) {
-    if let Ok(image) = load_image(&path.to_string_lossy(), wind) {
+fn all_supported_formats() -> Vec<&'static str> {
+    formats_by_decoder().iter().flat_map(|(_, formats)| formats.iter().copied()).collect()
+}
+
+// The file extensions each decoder opens, for --list-formats
+fn formats_by_decoder() -> [(&'static str, &'static [&'static str]); 5] {
+    [
+        ("fltk", &FLTK_SUPPORTED_FORMATS),
+        ("image", &IMAGEREADER_SUPPORTED_FORMATS),
+        ("animation", &ANIM_SUPPORTED_FORMATS),
+        ("raw", &RAW_SUPPORTED_FORMATS),
+        ("fits", &FITS_SUPPORTED_FORMATS),
+    ]
+}
+
+fn is_supported_image(path: &Path) -> bool {
+    let lowercase = path.to_string_lossy().to_lowercase();
+    all_supported_formats().iter().any(|&format| lowercase.ends_with(format))
+}
+
+// Get a list of all supported image files in the directory, sorted by name
+fn gather_images_from_directory(dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut image_files: Vec<PathBuf> = Vec::new();
+    scan::walk(dir, is_supported_image, &AtomicBool::new(false), |path| image_files.push(path))?;
+
+    //Sort files by name case insensitive, or as chosen on the command line
+    scan::sort(&mut image_files);
+    Ok(image_files)
+}
+
+// Show the image file, decoding slow formats in the background while the previous image stays on screen.
+// Once done, LOADED_EVENT shows the image with show_loaded_image.
+fn load_and_display_image(original_image: &mut ImageType, frame: &mut Frame, wind: &mut Window, path: &PathBuf, zoom_factor: &mut f64, is_fullscreen: bool, is_scaled_to_fit: bool, rotations: &Rotations, view_lock: &mut ViewLock, loader: &mut Loader) {
+    if let Some(decoded) = loader.take_preloaded(path) {
+        if loader.is_loading() {
+            loader.cancel();
+            wind.set_cursor(Cursor::Default);
+        }
+        match decoded.and_then(decoded_to_shared_image) {
+            Ok(image) => show_loaded_image(ImageType::Shared(image), original_image, frame, wind, path, zoom_factor, is_fullscreen, is_scaled_to_fit, rotations, view_lock),
+            Err(err) => log::error!("Error loading {}: {}", path.display(), err),
+        }
+        Loader::schedule_preload();
+        return;
+    }
+    let slow_decoder = match background_decoder(&path.to_string_lossy()) {
+        Some((decode, _)) => Some(decode),
+        None if is_large_plain_image(&path.to_string_lossy()) => Some(decode_imagereader as Decoder),
+        None => None,
+    };
+    if let Some(decode) = slow_decoder {
+        // The fastest preview there is shows right away, and is swapped for the full image once it's decoded
+        if is_scaled_to_fit {
+            let pixel_ratio = pixel_ratio(wind.screen_num());
+            let preview = perf::time("Preview", || quick_preview(&path.to_string_lossy(), to_pixels(wind.w(), pixel_ratio), to_pixels(wind.h(), pixel_ratio)));
+            match preview.map(|preview| preview.and_then(decoded_to_shared_image)) {
+                Some(Ok(image)) => show_loaded_image(ImageType::Shared(image), original_image, frame, wind, path, zoom_factor, is_fullscreen, is_scaled_to_fit, rotations, view_lock),
+                Some(Err(err)) => log::debug!("No quick preview of {}: {}", path.display(), err),
+                None => {}
+            }
+        }
+        loader.start(path, decode);
+        wind.set_cursor(Cursor::Wait);
+        return;
+    }
+    // A quickly loaded image replaces one still being decoded
+    if loader.is_loading() {
+        loader.cancel();
+        wind.set_cursor(Cursor::Default);
+    }
+    display_image_file(original_image, frame, wind, path, zoom_factor, is_fullscreen, is_scaled_to_fit, rotations, view_lock);
+    Loader::schedule_preload();
+}
+
+// Show a cheap stand-in for an image passed while scrubbing through the folder with a key held down: its cached
+// thumbnail, or its quick preview. Once scrubbing stops the full image is loaded.
+fn show_scrub_placeholder(original_image: &mut ImageType, frame: &mut Frame, wind: &mut Window, path: &PathBuf, zoom_factor: &mut f64, is_fullscreen: bool, rotations: &Rotations, loader: &mut Loader) {
+    if loader.is_loading() {
+        loader.cancel();
+        wind.set_cursor(Cursor::Default);
+    }
+    let placeholder = thumbnails::cached(path).or_else(|| {
+        let preview = quick_preview(&path.to_string_lossy(), SCRUB_PREVIEW_SIZE, SCRUB_PREVIEW_SIZE)?.ok()?;
+        image::RgbImage::from_raw(preview.width as u32, preview.height as u32, preview.data).map(|rgb| image::DynamicImage::ImageRgb8(rgb).into_rgba8())
+    });
+    let Some(mut placeholder) = placeholder else {
+        log::debug!("No placeholder for {}", path.display());
+        return;
+    };
+    let turns = rotations.get(path);
+    if turns > 0 {
+        placeholder = rotation::rotate_image(&placeholder, turns);
+    }
+    match rgba_to_shared_image(&placeholder) {
+        Ok(image) => display_image(ImageType::Shared(image), original_image, frame, wind, zoom_factor, is_fullscreen, true),
+        Err(err) => log::debug!("{}", err),
+    }
+}
+
+// Load and show the image file right away, for the first image before the window opens
+fn display_image_file(original_image: &mut ImageType, frame: &mut Frame, wind: &mut Window, path: &PathBuf, zoom_factor: &mut f64, is_fullscreen: bool, is_scaled_to_fit: bool, rotations: &Rotations, view_lock: &mut ViewLock) {
+    match perf::time("Decode", || load_image(&path.to_string_lossy(), wind)) {
+        Ok(image) => show_loaded_image(image, original_image, frame, wind, path, zoom_factor, is_fullscreen, is_scaled_to_fit, rotations, view_lock),
+        Err(err) => log::error!("{}", err),
+    }
+}
+
+// Show an image that was loaded from the file, with its rotation and the locked view
+fn show_loaded_image(mut image: ImageType, original_image: &mut ImageType, frame: &mut Frame, wind: &mut Window, path: &PathBuf, zoom_factor: &mut f64, is_fullscreen: bool, is_scaled_to_fit: bool, rotations: &Rotations, view_lock: &mut ViewLock) {
+    // With the view locked, the next image is shown with the zoom and pan of the one being left
+    let locked_view = if view_lock.is_locked {
+        let (nudge_x, nudge_y) = nudge_offset(view_lock.current_nudge(), original_image, frame);
+        Some(ViewState { zoom_factor: *zoom_factor, x: frame.x() - nudge_x, y: frame.y() - nudge_y })
+    } else {
+        None
+    };
+    let turns = rotations.get(path);
+    if turns > 0 {
+        match perf::time("Rotate", || image_to_rgba(&image).and_then(|pixels| rgba_to_shared_image(&rotation::rotate_image(&pixels, turns)))) {
+            Ok(rotated) => image = ImageType::Shared(rotated),
+            Err(err) => log::error!("Failed to rotate {}: {}", path.display(), err),
+        }
+    }
+    perf::time("Display", || display_image(image, original_image, frame, wind, zoom_factor, is_fullscreen, is_scaled_to_fit));
+    if animation::is_streamed(path) {
+        animation::start_stream(path, turns, frame);
+    }
+    let nudge = view_lock.show(path);
+    if let Some(view) = locked_view {
+        if view.zoom_factor != 1.0 {
+            *zoom_factor = view.zoom_factor;
+            set_frame_zoom(original_image, frame, *zoom_factor);
+        }
+        let (nudge_x, nudge_y) = nudge_offset(nudge, original_image, frame);
+        frame.set_pos(view.x + nudge_x, view.y + nudge_y);
+    }
+    record_image_memory(original_image, frame);
+}
+
+// Show or hide the status bar, returning whether it's shown. The text is filled in before the next event.
+fn toggle_status_bar(is_status_bar_shown: bool, overlay: &Rc<RefCell<Overlay>>, wind: &mut Window) -> bool {
+    if is_status_bar_shown {
+        overlay.borrow_mut().status_bar = None;
+        wind.redraw();
+    } else {
+        // The loading times are only measured while someone looks at them
+        perf::enable();
+    }
+    !is_status_bar_shown
+}
+
+// Switch sharpening of shrunk images on or off, showing the image again at the same zoom
+fn toggle_sharpening(original_image: &ImageType, frame: &mut Frame, zoom_factor: f64) {
+    scaling::set_sharpening(!scaling::is_sharpening());
+    log::debug!("Sharpening shrunk images: {}", scaling::is_sharpening());
+    set_frame_zoom(original_image, frame, zoom_factor);
+}
+
+// File name, position and size of the image with its loading statistics, for the status bar
+fn status_text(path: &Path, current_index: usize, image_count: usize, original_image: &ImageType) -> String {
+    let (data_w, data_h) = image_data_size(original_image);
+    format!("{}   {}/{}   {}x{}   {} filter   {}", path.file_name().unwrap_or_default().to_string_lossy(), current_index + 1, image_count, data_w, data_h, scaling::filter().name(), perf::summary())
+}
+
+// Zoom by a ratio, keeping the point of the image at the window position in place, for pinching.
+// Like with the mouse wheel, the image isn't zoomed out beyond its fitted size.
+fn zoom_around(original_image: &ImageType, frame: &mut Frame, zoom_factor: &mut f64, ratio: f64, x: i32, y: i32) {
+    let new_zoom_factor = (*zoom_factor * ratio).max(1.0);
+    let ratio = new_zoom_factor / *zoom_factor;
+    *zoom_factor = new_zoom_factor;
+    set_frame_zoom(original_image, frame, new_zoom_factor);
+    if new_zoom_factor > 1.0 {
+        // The image is drawn centered in the frame, so it grows around the frame's center
+        let dx = (x - frame.x() - frame.w() / 2) as f64;
+        let dy = (y - frame.y() - frame.h() / 2) as f64;
+        frame.set_pos(frame.x() + (dx * (1.0 - ratio)).round() as i32, frame.y() + (dy * (1.0 - ratio)).round() as i32);
+    } else {
         frame.set_pos(0, 0);
-        let cloned_image = image.clone();
-        match cloned_image {
-            ImageType::Shared(img) => {
-                let mut new_image = img.clone();
-                if is_scaled_to_fit {
-                    new_image.scale(wind.width(), wind.height(), true, true);
-                } else {
-                    new_image.scale(new_image.data_w(), new_image.data_h(), true, true);
-                }
+    }
+}
+
+// Whether the mouse event being handled comes from a touch screen, which is only known on Windows
+fn is_touch() -> bool {
+    #[cfg(target_os = "windows")]
+    return is_touch_event();
+    #[cfg(not(target_os = "windows"))]
+    return false;
+}
+
+// Pixel memory of the image for the performance overlay, the displayed copy is scaled to the zoom
+fn record_image_memory(original_image: &ImageType, frame: &Frame) {
+    if perf::is_enabled() {
+        let (data_w, data_h) = image_data_size(original_image);
+        let (display_w, display_h) = frame.image().map(|image| (image.w(), image.h())).unwrap_or_default();
+        perf::set_image_memory(data_w.max(0) as usize * data_h.max(0) as usize * 4, display_w.max(0) as usize * display_h.max(0) as usize * 4);
+    }
+}
+
+// Window pixels an image is shifted by for its nudge in image pixels
+fn nudge_offset(nudge: (i32, i32), original_image: &ImageType, frame: &Frame) -> (i32, i32) {
+    match image_screen_geometry(original_image, frame) {
+        Some((_, _, scale_x, scale_y)) => ((nudge.0 as f64 / scale_x).round() as i32, (nudge.1 as f64 / scale_y).round() as i32),
+        None => (0, 0),
+    }
+}
+
+// Show an image that was loaded or generated, resetting the zoom
+fn display_image(image: ImageType, original_image: &mut ImageType, frame: &mut Frame, wind: &mut Window, zoom_factor: &mut f64, is_fullscreen: bool, is_scaled_to_fit: bool) {
+    frame.set_pos(0, 0);
+    mipmap::clear();
+    animation::stop_stream();
+    let cloned_image = image.clone();
+    match cloned_image {
+        ImageType::Shared(img) => {
+            let mut new_image = img.clone();
+            let pixel_ratio = pixel_ratio(wind.screen_num());
+            if is_scaled_to_fit {
+                new_image.scale(wind.width(), wind.height(), true, true);
+            } else {
+                // One image pixel per screen pixel, which is less than one window unit on HiDPI screens
+                new_image.scale(to_units(new_image.data_w(), pixel_ratio), to_units(new_image.data_h(), pixel_ratio), true, true);
+            }
+            // FLTK scales bilinearly while drawing, other filters and sharpening need a resized copy, and so do
+            // images too large to be drawn, like very long panoramas
+            let is_too_large = new_image.data_w() > MAX_IMAGE_SIZE || new_image.data_h() > MAX_IMAGE_SIZE;
+            if is_too_large || scaling::needs_resample(new_image.w() as f64 * pixel_ratio / new_image.data_w().max(1) as f64) {
+                frame.set_image(Some(resample(&new_image, new_image.w(), new_image.h(), pixel_ratio)));
+            } else if let Some(mut level) = mipmap::level_for(&new_image, to_pixels(new_image.w(), pixel_ratio), to_pixels(new_image.h(), pixel_ratio)) {
+                // Drawn from a halved copy, which doesn't shimmer and is quicker to scale on every redraw
+                level.scale(new_image.w(), new_image.h(), false, true);
+                frame.set_image(Some(level));
+            } else {
                 frame.set_image(Some(new_image));
-            },
-            ImageType::AnimatedGif(mut anim_img) => {
-                if is_scaled_to_fit {
-                    anim_img.scale(wind.width(), wind.height(), true, true);
-                } else {
-                    anim_img.scale(anim_img.data_w(), anim_img.data_h(), true, true);
-                }
-                frame.set_image(Some(anim_img.clone()));
             }
+        },
+        ImageType::AnimatedGif(mut anim_img) => {
+            if is_scaled_to_fit {
+                anim_img.scale(wind.width(), wind.height(), true, true);
+            } else {
+                let pixel_ratio = pixel_ratio(wind.screen_num());
+                anim_img.scale(to_units(anim_img.data_w(), pixel_ratio), to_units(anim_img.data_h(), pixel_ratio), true, true);
+            }
+            frame.set_image(Some(anim_img.clone()));
         }
-        wind.redraw();
-        wind.fullscreen(is_fullscreen);
+    }
+    wind.redraw();
+    wind.fullscreen(is_fullscreen);
+
+    *zoom_factor = 1.0;
+    *original_image = image;
+}
+
+// Display the original image scaled by the zoom factor
+fn set_frame_zoom(original_image: &ImageType, frame: &mut Frame, zoom_factor: f64) {
+    let pixel_ratio = pixel_ratio(frame.window().map(|wind| wind.screen_num()).unwrap_or(0));
+    match original_image {
+        ImageType::Shared(img) => {
+            let new_image = img.clone();
+            let new_width = (new_image.width() as f64 * zoom_factor) as i32;
+            let new_height = (new_image.height() as f64 * zoom_factor) as i32;
+            log::debug!("New width/height: {}, {}", new_width, new_height);
+            frame.set_image(Some(perf::time("Zoom", || resample(&new_image, new_width, new_height, pixel_ratio))));
+        },
+        ImageType::AnimatedGif(anim_img) => {
+            let new_image = anim_img.clone();
+            let new_width = (new_image.width() as f64 * zoom_factor) as i32;
+            let new_height = (new_image.height() as f64 * zoom_factor) as i32;
+            log::debug!("New width/height: {}, {}", new_width, new_height);
+            let filter = scaling::filter().resolve(new_width as f64 * pixel_ratio / new_image.data_w().max(1) as f64);
+            fltk::image::RgbImage::set_scaling_algorithm(filter.rgb_scaling());
+            let mut zoomed = perf::time("Zoom", || new_image.copy_sized(to_pixels(new_width, pixel_ratio), to_pixels(new_height, pixel_ratio)));
+            fltk::image::RgbImage::set_scaling_algorithm(fltk::image::RgbScaling::Bilinear);
+            zoomed.scale(new_width, new_height, false, true);
+            frame.set_image(Some(zoomed));
+        }
+    }
+    record_image_memory(original_image, frame);
+}
+
+// Resize the image for display with the scaling filter for how much it's scaled. The width and height
+// are in window units, the copy has the screen pixels they cover so HiDPI screens show all of them.
+fn resample(img: &SharedImage, width: i32, height: i32, pixel_ratio: f64) -> SharedImage {
+    let (pixel_width, pixel_height) = drawable_size(to_pixels(width, pixel_ratio), to_pixels(height, pixel_ratio));
+    if pixel_width == img.data_w() && pixel_height == img.data_h() && width == img.w() && height == img.h() {
+        return img.clone();
+    }
+    let scale = pixel_width as f64 / img.data_w().max(1) as f64;
+    let filter = scaling::filter().resolve(scale);
+    // Shrinking starts from the closest halved copy, except for nearest neighbor which keeps the original pixels
+    let source = match filter {
+        ScalingFilter::Nearest => img.clone(),
+        _ => mipmap::level_for(img, pixel_width, pixel_height).unwrap_or_else(|| img.clone()),
+    };
+    let mut resized = None;
+    if filter == ScalingFilter::Lanczos || (scaling::is_sharpening() && scale < 1.0) {
+        match image_to_rgba(&ImageType::Shared(source.clone())).and_then(|pixels| rgba_to_shared_image(&scaling::resize(&pixels, pixel_width as u32, pixel_height as u32, filter))) {
+            Ok(image) => resized = Some(image),
+            Err(err) => log::error!("Failed to resize the image, using FLTK's scaling: {}", err),
+        }
+    }
+    let mut resized = resized.unwrap_or_else(|| {
+        // Other resizing in the viewer, like for previews, stays bilinear
+        fltk::image::RgbImage::set_scaling_algorithm(filter.rgb_scaling());
+        let image = source.copy_sized(pixel_width, pixel_height);
+        fltk::image::RgbImage::set_scaling_algorithm(fltk::image::RgbScaling::Bilinear);
+        image
+    });
+    resized.scale(width, height, false, true);
+    resized
+}
 
-        *zoom_factor = 1.0;
-        *original_image = image;
+// Shrink the size of a drawn copy to what can be drawn, FLTK scales it up to the requested size while drawing.
+// Only the copy is shrunk, the image keeps all its pixels for measuring, copying and exporting.
+fn drawable_size(pixel_width: i32, pixel_height: i32) -> (i32, i32) {
+    let scale = (MAX_IMAGE_SIZE as f64 / pixel_width.max(pixel_height).max(1) as f64).min(1.0);
+    if scale < 1.0 {
+        log::debug!("Shrinking the drawn copy of {}x{} pixels by {:.3}", pixel_width, pixel_height, scale);
     }
+    (((pixel_width as f64 * scale) as i32).max(1), ((pixel_height as f64 * scale) as i32).max(1))
+}
+
+// Screen pixels per window unit, 2.0 on a screen scaled to 200%
+fn pixel_ratio(screen: i32) -> f64 {
+    app::screen_scale(screen) as f64
+}
+
+fn to_pixels(units: i32, pixel_ratio: f64) -> i32 {
+    ((units as f64 * pixel_ratio).round() as i32).max(1)
+}
+
+fn to_units(pixels: i32, pixel_ratio: f64) -> i32 {
+    ((pixels as f64 / pixel_ratio).round() as i32).max(1)
 }
 
 fn get_absolute_path(filename: &str) -> PathBuf {
@@ -76,11 +536,28 @@ fn get_absolute_path(filename: &str) -> PathBuf {
     }
 }
 
-fn load_imagereader(image_file: &str) -> Result<SharedImage, String> {
+fn is_cmyk_jpeg(image_file: &str) -> bool {
+    let lowercase = image_file.to_lowercase();
+    (lowercase.ends_with("jpg") || lowercase.ends_with("jpeg"))
+        && matches!(colormodel::detect_jpeg(Path::new(image_file)), ColorModel::Cmyk | ColorModel::Ycck)
+}
+
+fn decode_imagereader(image_file: &str) -> Result<DecodedImage, String> {
     log::debug!("Processing with Imagereader: {}", image_file);
 
-    let reader = ImageReader::open(image_file)
+    let lowercase = image_file.to_lowercase();
+    if (lowercase.ends_with("tif") || lowercase.ends_with("tiff")) && colormodel::detect_tiff(Path::new(image_file)) == ColorModel::Lab {
+        log::debug!("Converting L*a*b* TIFF to sRGB: {}", image_file);
+        let lab_image = colormodel::decode_lab_tiff(Path::new(image_file))?;
+        let (width, height) = (lab_image.width() as i32, lab_image.height() as i32);
+        return Ok(DecodedImage { data: lab_image.into_raw(), width, height });
+    }
+
+    let format = image::ImageFormat::from_path(image_file)
         .map_err(|err| format!("Don't know how to load \"{}\": {}", image_file, err))?;
+    let mapped_file = mapped::map(Path::new(image_file))
+        .map_err(|err| format!("Failed to open \"{}\": {}", image_file, err))?;
+    let reader = ImageReader::with_format(std::io::Cursor::new(&mapped_file[..]), format);
 
     let decoded_image = reader
         .decode()
@@ -90,42 +567,28 @@ fn load_imagereader(image_file: &str) -> Result<SharedImage, String> {
     log::debug!("Image dimensions: {}x{}", width, height);
     log::debug!("Image color type: {:?}", decoded_image.color());
 
-    let data = decoded_image.into_rgb8().to_vec();
-    let img = fltk::image::RgbImage::new(
-        &data,
-        width as i32,
-        height as i32,
-        fltk::enums::ColorDepth::Rgb8,
-    )
-    .map_err(|err| format!("Processing \"{}\" failed: {}", image_file, err))?;
-
-    SharedImage::from_image(img).map_err(|err| format!("Error creating image: {}", err))
+    let data = decoded_image.into_rgb8().into_raw();
+    Ok(DecodedImage { data, width: width as i32, height: height as i32 })
 }
 
-fn load_raw(image_file: &str) -> Result<SharedImage, String> {
+fn decode_raw(image_file: &str) -> Result<DecodedImage, String> {
     log::debug!("Processing as RAW: {}", image_file);
 
     let mut pipeline = imagepipe::Pipeline::new_from_file(image_file)
         .map_err(|err| format!("Don't know how to load \"{}\": {}", image_file, err))?;
+    // Reading the sensor data and processing it are the slow steps, the second is skipped once the image isn't wanted anymore
+    loader::check_canceled()?;
 
     let decoded = pipeline
         .output_8bit(Some(&imagepipe::Pipeline::new_cache(100_000_000)))
         .map_err(|err| format!("Processing for \"{}\" failed: {}", image_file, err))?;
 
-    let img = fltk::image::RgbImage::new(
-        &decoded.data,
-        decoded.width as i32,
-        decoded.height as i32,
-        fltk::enums::ColorDepth::Rgb8,
-    )
-    .map_err(|err| format!("Processing for \"{}\" failed: {}", image_file, err))?;
-
-    SharedImage::from_image(img).map_err(|err| format!("Error creating image: {}", err))
+    Ok(DecodedImage { data: decoded.data, width: decoded.width as i32, height: decoded.height as i32 })
 }
 
 fn load_animated_image(image_file: &str, widget: &mut Window) -> Result<AnimGifImage, String> {
     log::debug!("Processing as animated image: {}", image_file);
-    let anim_image = AnimGifImage::load(image_file, widget, AnimGifImageFlags::DONT_RESIZE_CANVAS)
+    let anim_image = AnimGifImage::load(image_file, widget, AnimGifImageFlags::DONT_RESIZE_CANVAS | AnimGifImageFlags::OPTIMIZE_MEMORY)
         .map_err(|err| format!("Error loading animated image: {}", err))?;
 
     Ok(anim_image)
@@ -142,58 +605,267 @@ fn grey_scale(count: f32, min: f32, log_max: f32)
     Ok(Rgb([col, col, col]))
 }
 
-fn load_fits(image_file: &str) -> Result<SharedImage, String> {
+fn decode_fits(image_file: &str) -> Result<DecodedImage, String> {
     log::debug!("Processing as FITS: {}", image_file);
     let mut fits = rsf::Fits::open(Path::new(image_file)).map_err(|err| format!("Error creating image: {}", err))?;
-    let (header, data) = fits.remove_hdu(1).unwrap().to_parts();
-    let array = match data.unwrap() {
+    let (header, data) = fits.remove_hdu(1).ok_or("No image data found".to_string())?.to_parts();
+    let array = match data.ok_or("No image data found".to_string())? {
         rsf::Extension::Image(img) => img.as_owned_f32_array(),
         _ => return Err("No image data found".to_string())
     };
-    
+    // Big mosaics take a while to stretch, which is stopped once the image isn't wanted anymore
+    loader::check_canceled()?;
+    loader::set_progress(FITS_READ_PROGRESS);
+
     match array {
         Ok(a) => {
             // Normalize the data to fit in the 0-255 range for RGB
             let min = a.fold(f32::INFINITY, |a, &b| a.min(b));
             let max = a.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+            loader::check_canceled()?;
+            loader::set_progress(FITS_RANGE_PROGRESS);
 
-            let normalized_data = a.mapv(|x| {
-                let scaled = (x - min) / (max - min) * 255.0;
-                scaled.round() as u8
-            });            
+            // The normalized values are whole numbers from 0 to 255, so the logarithmic curve is worked out
+            // once for each of them instead of for every pixel
+            let curve = (0..=255u8)
+                .map(|count| grey_scale(count as f32, min, max.log10()))
+                .collect::<Result<Vec<Rgb<u8>>, Box<dyn Error>>>()
+                .map_err(|err| format!("Error creating image: {}", err))?;
 
             // Create an RGB image of the same size as the FITS image
-            let dim = normalized_data.dim();
+            let dim = a.dim();
             // get width and height out of dim
             let width = dim[1];
             let height = dim[0];
             let mut rgb_image = image::RgbImage::new(width as u32, height as u32);
 
-            // Iterate over the ndarray and convert to RGB
-            for (pos, count) in normalized_data.indexed_iter() {
-                let pixel = grey_scale(*count as f32, min, max.log10()).map_err(|err| format!("Error creating image: {}", err))?;
-                rgb_image.put_pixel(pos[0] as u32, pos[1] as u32, pixel);
-            }
-            let fltk_img = fltk::image::RgbImage::new(
-                &rgb_image.into_vec(),
-                width as i32,
-                height as i32,
-                fltk::enums::ColorDepth::Rgb8,
-            )
-            .map_err(|err| format!("Processing for \"{}\" failed: {}", image_file, err))?;
-        
-            return SharedImage::from_image(fltk_img).map_err(|err| format!("Error creating image: {}", err));
+            // Iterate over the ndarray and convert to RGB, a row at a time to report progress
+            let rows = a.outer_iter().len().max(1);
+            for (row, values) in a.outer_iter().enumerate() {
+                loader::check_canceled()?;
+                for (column, &value) in values.iter().enumerate() {
+                    let count = ((value - min) / (max - min) * 255.0).round() as u8;
+                    rgb_image.put_pixel(column as u32, row as u32, curve[count as usize]);
+                }
+                loader::set_progress(FITS_RANGE_PROGRESS + (1.0 - FITS_RANGE_PROGRESS) * (row + 1) as f64 / rows as f64);
+            }
+            return Ok(DecodedImage { data: rgb_image.into_vec(), width: width as i32, height: height as i32 });
         },
         Err(err) => return Err(format!("Error reading array: {}", err))
     }
 }
 
+// Load a 16 bit grayscale PNG showing the values in the window from low to high, or all of them
+fn decode_gray16(image_file: &str) -> Result<DecodedImage, String> {
+    log::debug!("Processing as 16 bit grayscale: {}", image_file);
+    let data = gray16::load(Path::new(image_file))?;
+    let (low, high) = gray16::full_range(&data);
+    Ok(windowed_gray16(&data, low, high))
+}
+
+// Show the window of a 16 bit grayscale image as RGB like all other images, so copying and exporting work the same
+fn windowed_gray16(data: &gray16::Gray16Image, low: u16, high: u16) -> DecodedImage {
+    let rgb_image = image::DynamicImage::ImageLuma8(gray16::apply_window(data, low, high)).into_rgb8();
+    let (width, height) = (rgb_image.width() as i32, rgb_image.height() as i32);
+    DecodedImage { data: rgb_image.into_raw(), width, height }
+}
+
+// Turn pixels decoded on a worker thread into an image FLTK can show, on the UI thread
+fn decoded_to_shared_image(decoded: DecodedImage) -> Result<SharedImage, String> {
+    let img = fltk::image::RgbImage::new(&decoded.data, decoded.width, decoded.height, fltk::enums::ColorDepth::Rgb8)
+        .map_err(|err| format!("Processing image failed: {}", err))?;
+    SharedImage::from_image(img).map_err(|err| format!("Error creating image: {}", err))
+}
+
+// Render the current image as a shaded relief, reading 16 bit heightmaps from the file for their full precision
+fn hillshade_interactive(original_image: &ImageType, image_file: &Path) -> Option<ImageType> {
+    let exaggeration = dialog::input_default("Height exaggeration:", "1")?.trim().parse::<f32>().unwrap_or(1.0);
+    let heights = if gray16::is_gray16_png(image_file) {
+        gray16::load(image_file).map(|data| relief::HeightMap::from_gray16(&data))
+    } else {
+        image_to_rgba(original_image).map(|image| relief::HeightMap::from_rgba(&image))
+    };
+    let result = heights
+        .map(|heights| image::DynamicImage::ImageLuma8(heights.hillshade(exaggeration)).into_rgba8())
+        .and_then(|shaded| rgba_to_shared_image(&shaded));
+    match result {
+        Ok(image) => Some(ImageType::Shared(image)),
+        Err(err) => {
+            dialog::alert_default(&format!("Relief rendering failed: {}", err));
+            None
+        }
+    }
+}
+
+// Ask for the range of values to show of a 16 bit grayscale image, and load it with that window
+fn gray16_window_interactive(image_file: &Path) -> Option<ImageType> {
+    if !gray16::is_gray16_png(image_file) {
+        dialog::alert_default("Level windowing is only available for 16 bit grayscale PNG images");
+        return None;
+    }
+    let data = match gray16::load_cached(image_file) {
+        Ok(data) => data,
+        Err(err) => {
+            dialog::alert_default(&err);
+            return None;
+        }
+    };
+    let (low, high) = gray16::full_range(&data);
+    let text = dialog::input_default(&format!("Values to show from darkest to brightest (the image has {} to {}):", low, high), &format!("{}-{}", low, high))?;
+    let window = text.split_once('-').and_then(|(low, high)| Some((low.trim().parse::<u16>().ok()?, high.trim().parse::<u16>().ok()?)));
+    match window {
+        Some((low, high)) if low < high => match decoded_to_shared_image(windowed_gray16(&data, low, high)) {
+            Ok(image) => Some(ImageType::Shared(image)),
+            Err(err) => {
+                dialog::alert_default(&err);
+                None
+            }
+        },
+        _ => {
+            dialog::alert_default(&format!("Invalid window: {}", text));
+            None
+        }
+    }
+}
+
+fn load_test_pattern(name: &str, width: i32, height: i32) -> Result<SharedImage, String> {
+    log::debug!("Generating test pattern: {}", name);
+    let pattern = testpattern::generate(name, width.max(1) as u32, height.max(1) as u32)
+        .ok_or(format!("Unknown test pattern: {}", name))?;
+    let img = fltk::image::RgbImage::new(
+        &pattern.into_raw(),
+        width.max(1),
+        height.max(1),
+        fltk::enums::ColorDepth::Rgb8,
+    )
+    .map_err(|err| format!("Processing test pattern \"{}\" failed: {}", name, err))?;
+
+    SharedImage::from_image(img).map_err(|err| format!("Error creating image: {}", err))
+}
+
+// Decoder of the formats that are slow enough to be decoded on a worker thread, with the kind of image for errors
+fn background_decoder(image_file: &str) -> Option<(Decoder, &'static str)> {
+    let lowercase = image_file.to_lowercase();
+    if testpattern::test_pattern_name(Path::new(image_file)).is_some() {
+        None
+    } else if gray16::is_gray16_png(Path::new(image_file)) {
+        Some((decode_gray16 as Decoder, "16 bit grayscale image"))
+    } else if is_cmyk_jpeg(image_file) {
+        // FLTK can't convert CMYK, the image crate decoder can
+        Some((decode_imagereader, "CMYK JPEG image"))
+    } else if RAW_SUPPORTED_FORMATS.iter().any(|&format| lowercase.ends_with(format)) {
+        // Demosaicing and stretching take seconds, so their results are kept on disk
+        Some(((|image_file: &str| decodecache::get_or_decode(image_file, RAW_PROCESSING, decode_raw)) as Decoder, "RAW image"))
+    } else if FITS_SUPPORTED_FORMATS.iter().any(|&format| lowercase.ends_with(format)) {
+        Some(((|image_file: &str| decodecache::get_or_decode(image_file, FITS_PROCESSING, decode_fits)) as Decoder, "FITS image"))
+    } else if IMAGEREADER_SUPPORTED_FORMATS.iter().any(|&format| lowercase.ends_with(format)) {
+        Some((decode_imagereader, "Imagereader image"))
+    } else {
+        None
+    }
+}
+
+// Decode the image without FLTK, for --convert and the analyses: RAW, FITS and L*a*b* TIFF images the way
+// the viewer shows them, through the decode cache, everything else with the image crate, which keeps the
+// bit depth and transparency
+fn decode_to_image(image_file: &str) -> Result<image::DynamicImage, String> {
+    let lowercase = image_file.to_lowercase();
+    let is_tiff = lowercase.ends_with("tif") || lowercase.ends_with("tiff");
+    let is_viewer_decoded = RAW_SUPPORTED_FORMATS.iter().any(|&format| lowercase.ends_with(format))
+        || FITS_SUPPORTED_FORMATS.iter().any(|&format| lowercase.ends_with(format))
+        || (is_tiff && colormodel::detect_tiff(Path::new(image_file)) == ColorModel::Lab);
+    let decoder = background_decoder(image_file).filter(|_| is_viewer_decoded);
+    let Some((decode, _)) = decoder else {
+        return image::open(image_file).map_err(|err| format!("Decoding \"{}\" failed: {}", image_file, err));
+    };
+    let decoded = decode(image_file)?;
+    image::RgbImage::from_raw(decoded.width as u32, decoded.height as u32, decoded.data)
+        .map(image::DynamicImage::ImageRgb8)
+        .ok_or(format!("Decoding \"{}\" failed: unexpected image size", image_file))
+}
+
+// Re-encode the image in the format of the output file's extension: PNG, JPEG, WebP or TIFF
+fn convert_image(input: &str, output: &Path) -> Result<(), String> {
+    let format = image::ImageFormat::from_path(output).ok()
+        .filter(|format| matches!(format, image::ImageFormat::Png | image::ImageFormat::Jpeg | image::ImageFormat::WebP | image::ImageFormat::Tiff))
+        .ok_or(format!("Unsupported output format for {}, use png, jpg, webp or tif", output.display()))?;
+    let image = decode_to_image(input)?;
+    // JPEG has neither transparency nor more than 8 bits, and the WebP encoder only takes 8 bits
+    let image = match format {
+        image::ImageFormat::Jpeg => image::DynamicImage::ImageRgb8(image.into_rgb8()),
+        image::ImageFormat::WebP if image.color().has_alpha() => image::DynamicImage::ImageRgba8(image.into_rgba8()),
+        image::ImageFormat::WebP => image::DynamicImage::ImageRgb8(image.into_rgb8()),
+        _ => image,
+    };
+    image.save_with_format(output, format).map_err(|err| format!("Failed to save {}: {}", output.display(), err))?;
+    log::debug!("Converted {} to {}", input, output.display());
+    Ok(())
+}
+
+// Whether the file is a JPEG or PNG image large enough to be decoded in the background. PNG images
+// with transparency are left to FLTK, which blends them with the window background.
+fn is_large_plain_image(image_file: &str) -> bool {
+    let lowercase = image_file.to_lowercase();
+    let is_jpeg = lowercase.ends_with("jpg") || lowercase.ends_with("jpeg");
+    if !is_jpeg && !lowercase.ends_with("png") {
+        return false;
+    }
+    if fs::metadata(image_file).map(|metadata| metadata.len()).unwrap_or(0) < PROGRESSIVE_MIN_BYTES {
+        return false;
+    }
+    is_jpeg || ImageReader::open(image_file)
+        .ok()
+        .and_then(|reader| reader.with_guessed_format().ok()?.into_decoder().ok())
+        .is_some_and(|decoder| !image::ImageDecoder::color_type(&decoder).has_alpha())
+}
+
+// The fastest stand-in for an image that takes a while to decode, covering about width x height pixels: the
+// preview embedded in RAW files, or a JPEG image decoded at a fraction of its size. None for other formats.
+fn quick_preview(image_file: &str, width: i32, height: i32) -> Option<Result<DecodedImage, String>> {
+    let lowercase = image_file.to_lowercase();
+    if lowercase.ends_with("jpg") || lowercase.ends_with("jpeg") {
+        Some(preview::jpeg_file(Path::new(image_file), width, height))
+    } else if RAW_SUPPORTED_FORMATS.iter().any(|&format| lowercase.ends_with(format)) {
+        Some(preview::raw_preview(Path::new(image_file), width, height))
+    } else {
+        None
+    }
+}
+
+// Decoder for preloading the image in the background. JPEG images are shown with FLTK's decoder,
+// but as the most common format they're worth preloading with the image crate's.
+fn preload_decoder(image_file: &str) -> Option<Decoder> {
+    let lowercase = image_file.to_lowercase();
+    match background_decoder(image_file) {
+        Some((decode, _)) => Some(decode),
+        None if lowercase.ends_with("jpg") || lowercase.ends_with("jpeg") => Some(decode_imagereader),
+        None => None,
+    }
+}
+
 fn load_image(image_file: &str, widget: &mut Window) -> Result<ImageType, String> {
-    if FLTK_SUPPORTED_FORMATS.iter().any(|&format| image_file.to_lowercase().ends_with(format)) {
+    if let Some(name) = testpattern::test_pattern_name(Path::new(image_file)) {
+        // Generated at the window size, so patterns are shown 1:1
+        match load_test_pattern(&name, widget.width(), widget.height()) {
+            Ok(image) => Ok(ImageType::Shared(image)),
+            Err(err) => Err(format!("Error generating test pattern: {}", err)),
+        }
+    } else if let Some((decode, kind)) = background_decoder(image_file) {
+        match decode(image_file).and_then(decoded_to_shared_image) {
+            Ok(image) => Ok(ImageType::Shared(image)),
+            Err(err) => Err(format!("Error loading {}: {}", kind, err)),
+        }
+    } else if FLTK_SUPPORTED_FORMATS.iter().any(|&format| image_file.to_lowercase().ends_with(format)) {
         match SharedImage::load(image_file) {
             Ok(image) => Ok(ImageType::Shared(image)),
             Err(err) => Err(format!("Error loading image: {}", err)),
         }
+    } else if animation::is_streamed(Path::new(image_file)) {
+        // The rest of the frames are decoded while it plays
+        match animation::first_frame(Path::new(image_file)).and_then(|first_frame| rgba_to_shared_image(&first_frame)) {
+            Ok(image) => Ok(ImageType::Shared(image)),
+            Err(err) => Err(format!("Error loading animated GIF image: {}", err)),
+        }
     } else if ANIM_SUPPORTED_FORMATS.iter().any(|&format| image_file.to_lowercase().ends_with(format)) {
         match load_animated_image(image_file, widget) {
             Ok(image) => {
@@ -201,147 +873,1402 @@ fn load_image(image_file: &str, widget: &mut Window) -> Result<ImageType, String
             },
             Err(err) => Err(format!("Error loading animated GIF image: {}", err)),
         }
-    } else if RAW_SUPPORTED_FORMATS.iter().any(|&format| image_file.to_lowercase().ends_with(format)) {
-        match load_raw(image_file) {
-            Ok(image) => Ok(ImageType::Shared(image)),
-            Err(err) => Err(format!("Error loading RAW image: {}", err)),
-        }
-    } else if FITS_SUPPORTED_FORMATS.iter().any(|&format| image_file.to_lowercase().ends_with(format)) {
-        match load_fits(image_file) {
-            Ok(image) => Ok(ImageType::Shared(image)),
-            Err(err) => Err(format!("Error loading FITS image: {}", err)),
-        }
-    } else if IMAGEREADER_SUPPORTED_FORMATS.iter().any(|&format| image_file.to_lowercase().ends_with(format)) {
-        match load_imagereader(image_file) {
-            Ok(image) => Ok(ImageType::Shared(image)),
-            Err(err) => Err(format!("Error loading Imagereader image: {}", err)),
-        }
     } else {
         Err("Unsupported file format.".to_string())
     }
 }
 
 fn copy_to_clipboard(original_image: &mut ImageType, clipboard: &mut Clipboard) -> Result<(), String> {
-    match &original_image {
+    if let ImageType::AnimatedGif(_anim_img) = &original_image {
+        return Err(format!("Copying animated images to clipboard is not supported"));
+    }
+    // The pixels are only taken out of the image for the clipboard, and handed over without another copy
+    let pixels = image_to_rgba(original_image)?;
+    let img_data: ImageData = ImageData {
+        width: pixels.width() as usize,
+        height: pixels.height() as usize,
+        bytes: pixels.into_raw().into(),
+    };
+    let _ = clipboard.set_image(img_data);
+    log::debug!("Image copied to clipboard");
+    Ok(())
+}
+
+// Get the pixels of the image at its original resolution
+fn image_to_rgba(original_image: &ImageType) -> Result<image::RgbaImage, String> {
+    match original_image {
         ImageType::Shared(img) => {
-            match img.depth() {
-                fltk::enums::ColorDepth::Rgba8 => {
-                    let rgba_image = img.to_rgb()
-                        .map_err(|err| format!("Error converting SharedImage to RGB: {}", err))?;
-                    let rgb_data = rgba_image.to_rgb_data();
-                    let img_data: ImageData = ImageData {
-                        bytes: rgb_data.into(),
-                        width: img.data_w() as usize,
-                        height: img.data_h() as usize,
-                    };
-                    let _ = clipboard.set_image(img_data);
-                    log::debug!("Image copied to clipboard");
-                    Ok(())
-                },
+            let rgb_image = img.to_rgb()
+                .map_err(|err| format!("Error converting SharedImage to RGB: {}", err))?;
+            let (width, height) = (rgb_image.data_w() as u32, rgb_image.data_h() as u32);
+            // RGBA images are taken as they are and RGB images are expanded, without an extra converted FLTK copy
+            let pixels = match rgb_image.depth() {
+                fltk::enums::ColorDepth::Rgba8 => image::RgbaImage::from_raw(width, height, rgb_image.to_rgb_data()),
                 fltk::enums::ColorDepth::Rgb8 => {
-                    let rgb_image = img.to_rgb()
-                        .map_err(|err| format!("Error converting SharedImage to RGB: {}", err))?;
+                    let data = rgb_image.to_rgb_data();
+                    drop(rgb_image);
+                    image::RgbImage::from_raw(width, height, data).map(|rgb| image::DynamicImage::ImageRgb8(rgb).into_rgba8())
+                },
+                _ => {
                     let rgba_image = rgb_image.convert(fltk::enums::ColorDepth::Rgba8)
                         .map_err(|err| format!("Error converting RGB to RGBA: {}", err))?;
-                    let rgba_data = rgba_image.to_rgb_data();
-                    log::debug!("rgba image size: {}", rgba_data.len());
-                    let img_data: ImageData = ImageData {
-                        bytes: rgba_data.into(),
-                        width: img.data_w() as usize,
-                                height: img.data_h() as usize,
-                    };
-                    let _ = clipboard.set_image(img_data);
-                    log::debug!("Image copied to clipboard");
-                    Ok(())
+                    image::RgbaImage::from_raw(width, height, rgba_image.to_rgb_data())
                 },
-                _ => {
-                    Err(format!("Unsupported color depth: {:?}", img.depth()))
-                }
-            }
+            };
+            pixels.ok_or("Image data does not match the image size".to_string())
         },
         ImageType::AnimatedGif(_anim_img) => {
-            Err(format!("Copying animated images to clipboard is not supported"))
+            Err(format!("Exporting animated images is not supported"))
         }
     }
 }
 
-fn order_by_name(image_order: &mut Vec<usize>, current_index: &mut usize, is_randomized: &mut bool) {
-    let original_index = image_order[*current_index];
-    // Remember the index of the image we're currently viewing
-    image_order.sort();
-    // Sort the image_order list to the original sequence
-    log::debug!("Image ordering sorted by name");
-    *is_randomized = false;
-    *current_index = image_order.iter().position(|&index| index == original_index).unwrap();
-    //Find the new index of the image we were viewing
+// Load an image file as pixels at its original resolution
+fn load_rgba(image_file: &Path, wind: &mut Window) -> Result<image::RgbaImage, String> {
+    let image = load_image(&image_file.to_string_lossy(), wind)?;
+    image_to_rgba(&image)
 }
 
-fn order_random(image_order: &mut Vec<usize>, current_index: &mut usize, is_randomized: &mut bool) {
-    let original_index = image_order[*current_index];
-    //Remember the index of the image we're currently viewing
-    let mut rng = rand::thread_rng();
-    image_order.shuffle(&mut rng);
-    log::debug!("Image ordering randomized");
-    *is_randomized = true;
-    *current_index = image_order.iter().position(|&index| index == original_index).unwrap();
-    //Find the new index of the image we were viewing
+fn rgba_to_shared_image(image: &image::RgbaImage) -> Result<SharedImage, String> {
+    let img = fltk::image::RgbImage::new(
+        image.as_raw(),
+        image.width() as i32,
+        image.height() as i32,
+        fltk::enums::ColorDepth::Rgba8,
+    )
+    .map_err(|err| format!("Processing image failed: {}", err))?;
+
+    SharedImage::from_image(img).map_err(|err| format!("Error creating image: {}", err))
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-//    std::env::set_var("RUST_LOG", "debug");
-    env_logger::init();
+// Load the current and following images, downscaled for a quick preview
+fn load_burst(image_files: &Vec<PathBuf>, image_order: &Vec<usize>, current_index: usize, count: usize, wind: &mut Window) -> Result<Vec<image::RgbaImage>, String> {
+    let mut images = Vec::new();
+    for offset in 0..count.min(image_order.len()) {
+        let path = &image_files[image_order[(current_index + offset) % image_order.len()]];
+        images.push(load_preview(path, wind)?);
+    }
+    Ok(images)
+}
 
-    let args: Vec<String> = env::args().collect();
-    let mut is_fullscreen = true;
-    let mut is_randomized = false; // Whether to start with the images in random order
-    let mut is_scaled_to_fit = true; // Whether to start with the image zoomed in to fit the screen
-    let mut image_order:Vec<usize> = Vec::new();
+// Load an image downscaled to at most PREVIEW_SIZE
+fn load_preview(path: &Path, wind: &mut Window) -> Result<image::RgbaImage, String> {
+    Ok(downscale_to_preview(&load_rgba(path, wind)?))
+}
+
+fn downscale_to_preview(image: &image::RgbaImage) -> image::RgbaImage {
+    let scale = (PREVIEW_SIZE as f64 / image.width().max(image.height()) as f64).min(1.0);
+    image::imageops::resize(
+        image,
+        ((image.width() as f64 * scale) as u32).max(1),
+        ((image.height() as f64 * scale) as u32).max(1),
+        image::imageops::FilterType::Triangle,
+    )
+}
 
-    if args.len() < 2 {
-        println!("Usage: {} [/windowed] <imagefile>", args[0]);
-        println!("The optional /windowed argument will open the image in a windowed mode instead of fullscreen.");
-        #[cfg(target_os = "windows")]
-        {
-            println!("To register as image viewer in Windows, run: {} /register", args[0]);
-            println!("To unregister, run: {} /unregister", args[0]);
+// Follow the shots after the current image as long as each one overlaps the previous one,
+// and show a rough stitch of them so the sequence can be picked for proper stitching later
+fn panorama_preview_interactive(image_files: &Vec<PathBuf>, image_order: &Vec<usize>, current_index: usize, wind: &mut Window) -> Option<ImageType> {
+    let mut images: Vec<image::RgbaImage> = Vec::new();
+    let mut positions = vec![(0, 0)];
+    for offset in 0..MAX_PANORAMA_IMAGES.min(image_order.len()) {
+        let path = &image_files[image_order[(current_index + offset) % image_order.len()]];
+        let image = match load_preview(path, wind) {
+            Ok(image) => image,
+            Err(err) => {
+                log::error!("Failed to load {} for the panorama preview: {}", path.display(), err);
+                break;
+            }
+        };
+        if let Some(previous) = images.last() {
+            match panorama::find_overlap(previous, &image) {
+                Some((dx, dy)) => {
+                    let (previous_x, previous_y) = positions[positions.len() - 1];
+                    positions.push((previous_x + dx, previous_y + dy));
+                }
+                None => break,
+            }
         }
-        std::process::exit(1);
+        images.push(image);
     }
+    if images.len() < 2 {
+        dialog::alert_default("No panorama found, the next image doesn't overlap the current one");
+        return None;
+    }
+    log::debug!("Panorama of {} images starting at {}, positions {:?}", images.len(), image_files[image_order[current_index]].display(), positions);
 
-    let mut image_file = &args[1];
-    if args.len() > 2 {
-        if args[1].eq_ignore_ascii_case("/windowed") {
-            is_fullscreen = false;
-            image_file = &args[2];
+    match panorama::stitch(&images, &positions).and_then(|stitched| rgba_to_shared_image(&stitched)) {
+        Ok(image) => Some(ImageType::Shared(image)),
+        Err(err) => {
+            dialog::alert_default(&format!("Panorama preview failed: {}", err));
+            None
         }
     }
+}
 
-    #[cfg(target_os = "windows")]
-    {
-        if image_file.eq_ignore_ascii_case("/register") {
-            match register_urlhandler() {
-                Ok(_) => println!("Success! LightningView egistered as image viewer."),
-                Err(err) => println!("Failed to register as image viewer: {}", err),
-            }
-            std::process::exit(0);
-        } else if image_file.eq_ignore_ascii_case("/unregister") {
-            unregister_urlhandler();
-            println!("LightningView unregistered as image viewer.");
-            std::process::exit(0);
+// Focus stack the current image and the following shots of a burst, and show the result
+fn focus_stack_interactive(image_files: &Vec<PathBuf>, image_order: &Vec<usize>, current_index: usize, wind: &mut Window) -> Option<ImageType> {
+    let count = dialog::input_default("Number of images to stack, starting with the current one:", "5")?
+        .trim()
+        .parse::<usize>()
+        .unwrap_or(0);
+    if count < 2 {
+        dialog::alert_default("At least two images are needed for focus stacking");
+        return None;
+    }
+    let result = load_burst(image_files, image_order, current_index, count, wind)
+        .and_then(|images| merge::focus_stack(&images))
+        .and_then(|stacked| rgba_to_shared_image(&stacked));
+    match result {
+        Ok(image) => Some(ImageType::Shared(image)),
+        Err(err) => {
+            dialog::alert_default(&format!("Focus stacking failed: {}", err));
+            None
+        }
+    }
+}
+
+// Find the exposure bracket starting at the current image, merge it and tone map the result
+fn hdr_merge_interactive(image_files: &Vec<PathBuf>, image_order: &Vec<usize>, current_index: usize, wind: &mut Window) -> Option<ImageType> {
+    let candidates: Vec<&Path> = (0..image_order.len())
+        .map(|offset| image_files[image_order[(current_index + offset) % image_order.len()]].as_path())
+        .collect();
+    let bracket = metadata::find_exposure_bracket(&candidates);
+    if bracket.is_empty() {
+        dialog::alert_default("No exposure bracket found, the following images need different exposure settings in their EXIF data");
+        return None;
+    }
+    log::debug!("Merging exposure bracket: {:?}", bracket);
+
+    let result = load_burst(image_files, image_order, current_index, bracket.len(), wind)
+        .map(|images| images.into_iter().zip(bracket.iter().map(|(_, exposure)| *exposure)).collect::<Vec<_>>())
+        .and_then(|exposures| merge::hdr_merge(&exposures))
+        .and_then(|merged| rgba_to_shared_image(&merged));
+    match result {
+        Ok(image) => Some(ImageType::Shared(image)),
+        Err(err) => {
+            dialog::alert_default(&format!("HDR merge failed: {}", err));
+            None
+        }
+    }
+}
+
+// Ask for the size and the zip file, and export the images being browsed for sharing
+fn prepare_for_sharing_interactive(image_files: &Vec<PathBuf>, image_order: &Vec<usize>, current_index: usize, is_confirming_overwrite: bool) {
+    let question = format!("Prepare the {} images being browsed for sharing.\nLongest edge in pixels:", image_order.len());
+    let max_edge = match dialog::input_default(&question, "2048") {
+        Some(text) => text.trim().parse::<u32>().unwrap_or(0),
+        None => return,
+    };
+    if max_edge == 0 {
+        dialog::alert_default("The longest edge has to be a number of pixels");
+        return;
+    }
+    let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseSaveFile);
+    chooser.set_title("Save zip file");
+    chooser.set_filter("Zip files\t*.zip");
+    chooser.set_preset_file("shared.zip");
+    if let Some(directory) = image_files[image_order[current_index]].parent() {
+        let _ = chooser.set_directory(&directory);
+    }
+    if is_confirming_overwrite {
+        chooser.set_option(dialog::NativeFileChooserOptions::SaveAsConfirm);
+    }
+    chooser.show();
+    let mut zip_path = chooser.filename();
+    if zip_path.as_os_str().is_empty() {
+        return;
+    }
+    if zip_path.extension().is_none() {
+        zip_path.set_extension("zip");
+    }
+    let files = image_order.iter().map(|&index| image_files[index].clone()).collect();
+    share::start(files, max_edge, zip_path);
+}
+
+// Ask for a directory with the native file chooser, returns None if the dialog was cancelled
+fn choose_directory(title: &str) -> Option<PathBuf> {
+    let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseDir);
+    chooser.set_title(title);
+    chooser.show();
+    let directory = chooser.filename();
+    if directory.as_os_str().is_empty() {
+        None
+    } else {
+        Some(directory)
+    }
+}
+
+// Ask for an image file with the native file chooser, returns None if the dialog was cancelled
+fn choose_image_file() -> Option<PathBuf> {
+    let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseFile);
+    chooser.set_title("Open image");
+    chooser.set_filter(&format!("Images\t*.{{{}}}", all_supported_formats().join(",")));
+    chooser.show();
+    let image_file = chooser.filename();
+    if image_file.as_os_str().is_empty() {
+        None
+    } else {
+        Some(image_file)
+    }
+}
+
+// Ask for the frame layout and speed of a sprite sheet, and start playing it
+fn start_sprite_animation_interactive(original_image: &ImageType, frame: &Frame, wind: &Window) -> Option<FrameAnimation> {
+    let spec_text = dialog::input_default("Frame size (e.g. 32x32px) or frame grid (e.g. 8x1):", "32x32px")?;
+    let spec = match TileSpec::parse(&spec_text) {
+        Some(spec) => spec,
+        None => {
+            dialog::alert_default(&format!("Invalid frame specification: {}", spec_text));
+            return None;
+        }
+    };
+    let fps = dialog::input_default("Frames per second:", "12")?.trim().parse::<f64>().unwrap_or(12.0);
+    let result = image_to_rgba(original_image)
+        .and_then(|sheet| FrameAnimation::from_sprite_sheet(&sheet, spec, fps, frame, wind.width(), wind.height()));
+    match result {
+        Ok(animation) => Some(animation),
+        Err(err) => {
+            dialog::alert_default(&format!("Failed to play sprite sheet: {}", err));
+            None
+        }
+    }
+}
+
+// Alternate between two images to spot differences, optionally shifting the other image to compensate for drift
+fn start_blink_comparison(original_image: &ImageType, other_image_file: &Path, is_aligning: bool, frame: &Frame, wind: &mut Window) -> Result<FrameAnimation, String> {
+    let reference = image_to_rgba(original_image)?;
+    let other_image = load_image(&other_image_file.to_string_lossy(), wind)?;
+    let mut other = image_to_rgba(&other_image)?;
+    if is_aligning {
+        other = align_to(&reference, &other, other_image_file);
+    }
+    FrameAnimation::start(vec![reference, other], BLINK_INTERVAL, frame, wind.width(), wind.height())
+}
+
+// Turn and shift the other image to compensate for drift and field rotation against the reference
+fn align_to(reference: &image::RgbaImage, other: &image::RgbaImage, other_image_file: &Path) -> image::RgbaImage {
+    let angle = align::estimate_rotation(reference, other);
+    let turned;
+    let other = if angle.abs() > f64::EPSILON {
+        turned = align::rotate_by(other, angle);
+        &turned
+    } else {
+        other
+    };
+    let (dx, dy) = align::estimate_translation(reference, other);
+    log::debug!("Aligning {} by {:.1} degrees and {}, {}", other_image_file.display(), angle, dx, dy);
+    let mut aligned = image::RgbaImage::new(reference.width(), reference.height());
+    image::imageops::overlay(&mut aligned, other, dx as i64, dy as i64);
+    aligned
+}
+
+// Show the next image as an onion skin over the current one, with a slider for its opacity
+fn show_onion_skin_interactive(image_files: &Vec<PathBuf>, image_order: &Vec<usize>, current_index: usize, is_aligning: bool, wind: &mut Window) {
+    let mut images = match load_burst(image_files, image_order, current_index, 2, wind) {
+        Ok(images) if images.len() == 2 => images,
+        Ok(_) => {
+            dialog::alert_default("Onion skin needs a second image in the directory");
+            return;
+        }
+        Err(err) => {
+            dialog::alert_default(&format!("Failed to load the images for the onion skin: {}", err));
+            return;
+        }
+    };
+    let mut overlay = images.pop().unwrap_or_default();
+    let base = images.pop().unwrap_or_default();
+    if is_aligning {
+        overlay = align_to(&base, &overlay, &image_files[image_order[(current_index + 1) % image_order.len()]]);
+    }
+
+    let scale = (ONION_SKIN_WIDTH as f64 / base.width() as f64).min(ONION_SKIN_HEIGHT as f64 / base.height() as f64).min(1.0);
+    let width = ((base.width() as f64 * scale) as i32).max(1);
+    let height = ((base.height() as f64 * scale) as i32).max(1);
+    let mut onion_window = Window::default().with_size(width, height + SLIDER_HEIGHT).with_label("Onion skin");
+    let mut preview = Frame::new(0, 0, width, height, None);
+    let mut slider = fltk::valuator::HorValueSlider::new(0, height, width, SLIDER_HEIGHT, None);
+    slider.set_range(0.0, 100.0);
+    slider.set_step(1.0, 1);
+    slider.set_value(50.0);
+    slider.set_tooltip("Opacity of the next image in percent");
+    let mut show_blend = move |opacity: f64| {
+        match rgba_to_shared_image(&merge::onion_skin(&base, &overlay, opacity as f32 / 100.0)) {
+            Ok(mut image) => {
+                image.scale(width, height, true, true);
+                preview.set_image(Some(image));
+                preview.redraw();
+            }
+            Err(err) => log::error!("Failed to show the onion skin: {}", err),
+        }
+    };
+    show_blend(slider.value());
+    slider.set_callback(move |slider| show_blend(slider.value()));
+    onion_window.end();
+    onion_window.show();
+}
+
+// Ask how to slice the current image and where to put the tiles, then export them
+fn export_tiles_interactive(original_image: &ImageType, image_file: &Path, is_confirming_overwrite: bool) {
+    let spec_text = match dialog::input_default("Tiles as grid (e.g. 4x3) or tile size (e.g. 256px or 256x128px):", "4x4") {
+        Some(text) => text,
+        None => return,
+    };
+    let spec = match TileSpec::parse(&spec_text) {
+        Some(spec) => spec,
+        None => {
+            dialog::alert_default(&format!("Invalid tile specification: {}", spec_text));
+            return;
+        }
+    };
+    let output_dir = match choose_directory("Export tiles to") {
+        Some(directory) => directory,
+        None => return,
+    };
+    let base_name = image_file.file_stem().unwrap_or_default().to_string_lossy();
+    let result = image_to_rgba(original_image)
+        .and_then(|image| export::export_tiles(&image, spec, &output_dir, &base_name, is_confirming_overwrite));
+    match result {
+        Ok(count) => log::debug!("{} tiles written to {}", count, output_dir.display()),
+        Err(err) => dialog::alert_default(&format!("Failed to export tiles: {}", err)),
+    }
+}
+
+// Ask where to put the frames of the current animation, then save them as numbered PNG files
+fn export_frames_interactive(image_file: &Path, is_confirming_overwrite: bool) {
+    let output_dir = match choose_directory("Export frames to") {
+        Some(directory) => directory,
+        None => return,
+    };
+    let base_name = image_file.file_stem().unwrap_or_default().to_string_lossy();
+    match export::export_frames(image_file, &output_dir, &base_name, is_confirming_overwrite) {
+        Ok(count) => log::debug!("{} frames written to {}", count, output_dir.display()),
+        Err(err) => dialog::alert_default(&format!("Failed to export frames: {}", err)),
+    }
+}
+
+fn copy_text_to_clipboard(text: &str) -> Result<(), String> {
+    let mut clipboard = Clipboard::new().map_err(|err| format!("Failed to initialize clipboard: {}", err))?;
+    clipboard.set_text(text).map_err(|err| format!("Failed to copy text to clipboard: {}", err))?;
+    log::debug!("Copied to clipboard: {}", text);
+    Ok(())
+}
+
+// Keep the window above other windows. There is no FLTK function for this, so it's done by the platform:
+// directly on Windows, with wmctrl on X11.
+#[cfg(target_os = "windows")]
+fn set_always_on_top(wind: &Window, is_on_top: bool) -> Result<(), String> {
+    set_topmost(wind.raw_handle(), is_on_top)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_always_on_top(wind: &Window, is_on_top: bool) -> Result<(), String> {
+    let status = std::process::Command::new("wmctrl")
+        .args(["-i", "-r", &format!("0x{:x}", wind.raw_handle() as u64), "-b", if is_on_top { "add,above" } else { "remove,above" }])
+        .status()
+        .map_err(|err| format!("Failed to run wmctrl, which is needed to keep the window on top: {}", err))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err("wmctrl failed to change the window state".to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn set_always_on_top(_wind: &Window, _is_on_top: bool) -> Result<(), String> {
+    Err("Keeping the window on top is not supported on macOS".to_string())
+}
+
+// Make fullscreen mode use the monitor, counted from 0, and move the window there
+fn set_fullscreen_monitor(wind: &mut Window, monitor: i32) -> Result<(), String> {
+    if monitor < 0 || monitor >= app::screen_count() {
+        return Err(format!("There is no monitor {}, {} found", monitor + 1, app::screen_count()));
+    }
+    let is_fullscreen = wind.fullscreen_active();
+    if is_fullscreen {
+        wind.fullscreen(false);
+    }
+    // Keep the windowed size, centered on the monitor
+    let (x, y, width, height) = app::screen_xywh(monitor);
+    let window_width = wind.w().min(width);
+    let window_height = wind.h().min(height);
+    wind.resize(x + (width - window_width) / 2, y + (height - window_height) / 2, window_width, window_height);
+    wind.fullscreen_screens(monitor, monitor, monitor, monitor);
+    if is_fullscreen {
+        wind.fullscreen(true);
+    }
+    log::debug!("Fullscreen on monitor {}", monitor + 1);
+    Ok(())
+}
+
+// Advance to the next image every interval seconds, until the returned timeout is removed
+fn start_slideshow(interval: f64) -> app::TimeoutHandle {
+    app::add_timeout3(interval, move |handle| {
+        let _ = app::handle_main(SLIDESHOW_EVENT);
+        app::repeat_timeout3(interval, handle);
+    })
+}
+
+// Start another viewer with the image, windowed so the two can be placed side by side.
+// Separate processes keep the windows independent, and they can still follow each other with sync view.
+fn open_new_window(path: &Path) -> Result<(), String> {
+    let executable = env::current_exe().map_err(|err| format!("Failed to find the viewer executable: {}", err))?;
+    std::process::Command::new(executable)
+        .arg("--windowed")
+        .arg(path)
+        .spawn()
+        .map_err(|err| format!("Failed to open a new window: {}", err))?;
+    Ok(())
+}
+
+// Open the file manager at the containing folder with the file selected
+fn reveal_in_file_manager(path: &Path) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    let result = {
+        use std::os::windows::process::CommandExt;
+        // Explorer does its own parsing of the command line, so the quotes have to be exactly like this
+        std::process::Command::new("explorer").raw_arg(format!("/select,\"{}\"", path.display())).spawn().map(|_| ())
+    };
+    #[cfg(target_os = "macos")]
+    let result = std::process::Command::new("open").arg("-R").arg(path).spawn().map(|_| ());
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let result = {
+        // Most file managers implement the freedesktop interface for selecting files, the
+        // others at least get to open the folder
+        let uri: String = path.to_string_lossy().bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+                _ => format!("%{:02X}", byte),
+            })
+            .collect();
+        let shown = std::process::Command::new("dbus-send")
+            .args(["--session", "--print-reply", "--dest=org.freedesktop.FileManager1", "--type=method_call",
+                "/org/freedesktop/FileManager1", "org.freedesktop.FileManager1.ShowItems"])
+            .arg(format!("array:string:file://{}", uri))
+            .arg("string:")
+            .status()
+            .is_ok_and(|status| status.success());
+        if shown {
+            Ok(())
+        } else {
+            let folder = path.parent().unwrap_or(Path::new("."));
+            std::process::Command::new("xdg-open").arg(folder).spawn().map(|_| ())
+        }
+    };
+    result.map_err(|err| format!("Failed to open the file manager for {}: {}", path.display(), err))
+}
+
+// What to do when a file of the same name already exists in the target folder
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum CollisionPolicy {
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+// First free "name (n).ext" next to the given path
+fn unique_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+    (2..)
+        .map(|number| path.with_file_name(format!("{} ({}){}", stem, number, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(path.to_path_buf())
+}
+
+// Copy or move a file into a directory, moves to a different drive are done by copying.
+// Returns the new path, or None if the file was skipped because the target exists.
+fn transfer_file(path: &Path, directory: &Path, is_move: bool, collision: CollisionPolicy) -> Result<Option<PathBuf>, String> {
+    if path.parent() == Some(directory) {
+        return Err(format!("{} is already in {}", path.display(), directory.display()));
+    }
+    let mut target = directory.join(path.file_name().ok_or(format!("{} is not a file", path.display()))?);
+    if target.exists() {
+        match collision {
+            CollisionPolicy::Skip => return Ok(None),
+            CollisionPolicy::Overwrite => {},
+            CollisionPolicy::Rename => target = unique_path(&target),
+        }
+    }
+    if !is_move || fs::rename(path, &target).is_err() {
+        fs::copy(path, &target).map_err(|err| format!("Failed to copy {} to {}: {}", path.display(), directory.display(), err))?;
+        if is_move {
+            fs::remove_file(path).map_err(|err| format!("Failed to remove {} after copying it: {}", path.display(), err))?;
+        }
+    }
+    log::debug!("{} {} to {}", if is_move { "Moved" } else { "Copied" }, path.display(), target.display());
+    Ok(Some(target))
+}
+
+// Ask for the target folder, and how to handle an existing file there, then copy or move the image.
+// Returns where the image was moved to, if it was moved away.
+fn transfer_file_interactive(path: &Path, is_move: bool, is_confirming_overwrite: bool) -> Option<PathBuf> {
+    let directory = choose_directory(if is_move { "Move to" } else { "Copy to" })?;
+    let target = directory.join(path.file_name().unwrap_or_default());
+    let collision = if target.exists() && !is_confirming_overwrite {
+        CollisionPolicy::Overwrite
+    } else if target.exists() {
+        match dialog::choice2_default(&format!("{} already exists.", target.display()), "Skip", "Overwrite", "Keep both") {
+            Some(1) => CollisionPolicy::Overwrite,
+            Some(2) => CollisionPolicy::Rename,
+            _ => CollisionPolicy::Skip,
+        }
+    } else {
+        CollisionPolicy::Skip
+    };
+    match transfer_file(path, &directory, is_move, collision) {
+        Ok(Some(target)) if is_move => Some(target),
+        Ok(_) => None,
+        Err(err) => {
+            dialog::alert_default(&err);
+            None
+        }
+    }
+}
+
+// Ask for an image number or part of its name, returning the position of the image in the image order
+fn jump_to_interactive(image_files: &[PathBuf], image_order: &[usize], initial_text: &str) -> Option<usize> {
+    let query = dialog::input_default(&format!("Go to image number (1-{}) or name:", image_order.len()), initial_text)?;
+    let position = jump::find(&query, image_files, image_order);
+    if position.is_none() {
+        dialog::alert_default(&format!("No image matches \"{}\"", query.trim()));
+    }
+    position
+}
+
+// Ask for a new file name and rename the image in place, keeping its position in the list
+fn rename_interactive(image_files: &mut Vec<PathBuf>, image_index: usize) {
+    let path = image_files[image_index].clone();
+    let old_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let new_name = match dialog::input_default("Rename to:", &old_name) {
+        Some(name) => name.trim().to_string(),
+        None => return,
+    };
+    if new_name.is_empty() || new_name == old_name {
+        return;
+    }
+    if new_name.contains(['/', '\\']) {
+        dialog::alert_default("The new name can't contain a path separator");
+        return;
+    }
+    let new_path = path.with_file_name(&new_name);
+    if new_path.exists() {
+        dialog::alert_default(&format!("{} already exists", new_path.display()));
+        return;
+    }
+    match fs::rename(&path, &new_path) {
+        Ok(()) => {
+            log::debug!("Renamed {} to {}", path.display(), new_path.display());
+            image_files[image_index] = new_path;
+        },
+        Err(err) => dialog::alert_default(&format!("Failed to rename {}: {}", path.display(), err)),
+    }
+}
+
+// Show the dominant colors of the image as swatches in their own window, clicking one copies its hex code
+fn show_palette_interactive(original_image: &ImageType) {
+    let count = match dialog::input_default("Number of colors:", "6") {
+        Some(text) => text.trim().parse::<usize>().unwrap_or(0),
+        None => return,
+    };
+    if !(1..=MAX_PALETTE_COLORS).contains(&count) {
+        dialog::alert_default(&format!("The number of colors has to be between 1 and {}", MAX_PALETTE_COLORS));
+        return;
+    }
+    let colors = match image_to_rgba(original_image) {
+        Ok(image) => palette::dominant_colors(&image, count),
+        Err(err) => {
+            dialog::alert_default(&err);
+            return;
+        }
+    };
+    log::debug!("Palette: {:?}", colors);
+
+    let mut palette_window = Window::default().with_size(SWATCH_WIDTH, SWATCH_HEIGHT * colors.len() as i32).with_label("Palette");
+    for (index, (color, share)) in colors.iter().enumerate() {
+        let hex = palette::hex_code(*color);
+        let mut swatch = fltk::button::Button::new(0, index as i32 * SWATCH_HEIGHT, SWATCH_WIDTH, SWATCH_HEIGHT, None);
+        swatch.set_label(&format!("{} ({:.0}%)", hex, share * 100.0));
+        swatch.set_frame(fltk::enums::FrameType::FlatBox);
+        swatch.set_color(Color::from_rgb(color[0], color[1], color[2]));
+        // Dark text on light colors and light text on dark ones
+        let luminance = 0.2126 * color[0] as f64 + 0.7152 * color[1] as f64 + 0.0722 * color[2] as f64;
+        swatch.set_label_color(if luminance > 128.0 { Color::Black } else { Color::White });
+        swatch.set_tooltip("Click to copy the hex code");
+        swatch.set_callback(move |_| {
+            if let Err(err) = copy_text_to_clipboard(&hex) {
+                log::error!("{}", err);
+            }
+        });
+    }
+    palette_window.end();
+    palette_window.show();
+}
+
+// Hide the window and take a screenshot of the monitor it is on
+fn capture_screen(wind: &mut Window) -> Result<image::RgbaImage, String> {
+    let monitor = xcap::Monitor::from_point(wind.x() + wind.width() / 2, wind.y() + wind.height() / 2)
+        .map_err(|err| format!("Failed to find the monitor: {}", err))?;
+    wind.hide();
+    app::flush();
+    std::thread::sleep(std::time::Duration::from_millis(CAPTURE_DELAY));
+    let result = monitor.capture_image().map_err(|err| format!("Failed to capture the screen: {}", err));
+    wind.show();
+    result
+}
+
+// List the open application windows in a popup menu and capture the chosen one
+fn capture_window_interactive(x: i32, y: i32) -> Option<image::RgbaImage> {
+    let windows: Vec<xcap::Window> = match xcap::Window::all() {
+        Ok(windows) => windows.into_iter().filter(|window| !window.is_minimized() && !window.title().is_empty()).collect(),
+        Err(err) => {
+            dialog::alert_default(&format!("Failed to list the windows: {}", err));
+            return None;
+        }
+    };
+    if windows.is_empty() {
+        dialog::alert_default("No windows to capture");
+        return None;
+    }
+    // '&' marks a keyboard shortcut in menu labels
+    let labels: Vec<String> = windows.iter()
+        .map(|window| format!("{} - {}", window.app_name(), window.title()).replace('&', "&&"))
+        .collect();
+    let label_refs: Vec<&str> = labels.iter().map(|label| label.as_str()).collect();
+    let popup_menu = fltk::menu::MenuItem::new(&label_refs);
+    let selected = popup_menu.popup(x, y)?.label().unwrap_or_default();
+    let window = &windows[labels.iter().position(|label| *label == selected)?];
+    log::debug!("Capturing window {}", window.title());
+    match window.capture_image() {
+        Ok(capture) => Some(capture),
+        Err(err) => {
+            dialog::alert_default(&format!("Failed to capture {}: {}", window.title(), err));
+            None
+        }
+    }
+}
+
+// Launch the external editor from the config file with the image
+fn open_in_editor(editor: &Option<Vec<String>>, path: &Path) -> Result<(), String> {
+    let (program, args) = editor.as_ref()
+        .and_then(|command| command.split_first())
+        .ok_or("No external editor configured, add e.g. editor = [\"gimp\"] to config.toml".to_string())?;
+    log::debug!("Opening {} with {}", path.display(), program);
+    std::process::Command::new(program)
+        .args(args)
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+        .map_err(|err| format!("Failed to start {}: {}", program, err))
+}
+
+// Show the tags the classification model suggests for the current image
+#[cfg(feature = "tagging")]
+fn suggest_tags_interactive(tagger: &mut Option<tagging::Tagger>, original_image: &ImageType) {
+    if tagger.is_none() {
+        match tagging::Tagger::load() {
+            Ok(loaded) => *tagger = Some(loaded),
+            Err(err) => {
+                dialog::alert_default(&err);
+                return;
+            }
+        }
+    }
+    let tagger = match tagger {
+        Some(tagger) => tagger,
+        None => return,
+    };
+    match image_to_rgba(original_image).and_then(|image| tagger.suggest(&image)) {
+        Ok(tags) if tags.is_empty() => dialog::message_default("No tags suggested for this image"),
+        Ok(tags) => {
+            let text: Vec<String> = tags.iter().map(|(tag, probability)| format!("{} ({:.0}%)", tag, probability * 100.0)).collect();
+            dialog::message_default(&format!("Suggested tags: {}", text.join(", ")));
+        },
+        Err(err) => dialog::alert_default(&err),
+    }
+}
+
+// Size of the image in pixels
+fn image_data_size(original_image: &ImageType) -> (i32, i32) {
+    match original_image {
+        ImageType::Shared(img) => (img.data_w(), img.data_h()),
+        ImageType::AnimatedGif(anim_img) => (anim_img.data_w(), anim_img.data_h()),
+    }
+}
+
+// Find where the image is drawn in the window, as (x, y, scale_x, scale_y) with the scale in image pixels per window pixel
+fn image_screen_geometry(original_image: &ImageType, frame: &Frame) -> Option<(i32, i32, f64, f64)> {
+    let (data_w, data_h) = image_data_size(original_image);
+    let displayed_image = frame.image()?;
+    let (display_w, display_h) = (displayed_image.w(), displayed_image.h());
+    if display_w <= 0 || display_h <= 0 {
+        return None;
+    }
+
+    // The image is drawn centered within the frame
+    let image_x = frame.x() + (frame.w() - display_w) / 2;
+    let image_y = frame.y() + (frame.h() - display_h) / 2;
+    Some((image_x, image_y, data_w as f64 / display_w as f64, data_h as f64 / display_h as f64))
+}
+
+// Where the image would be cropped for the output target, in window coordinates
+fn target_frame(target: Target, original_image: &ImageType, frame: &Frame) -> Option<TargetFrame> {
+    let (image_x, image_y, scale_x, scale_y) = image_screen_geometry(original_image, frame)?;
+    let (data_w, data_h) = image_data_size(original_image);
+    let to_window = |(x, y, width, height): (f64, f64, f64, f64)| (
+        image_x + (x / scale_x).round() as i32,
+        image_y + (y / scale_y).round() as i32,
+        (width / scale_x).round() as i32,
+        (height / scale_y).round() as i32,
+    );
+    let crop = target.crop(data_w as f64, data_h as f64);
+    Some(TargetFrame {
+        crop: to_window(crop),
+        safe_area: to_window(Target::safe_area(crop)),
+        label: target.describe(data_w.max(1) as u32, data_h.max(1) as u32),
+    })
+}
+
+// Convert window coordinates to image pixel coordinates
+fn window_to_image_coords(original_image: &ImageType, frame: &Frame, x: i32, y: i32) -> Option<(f64, f64)> {
+    let (image_x, image_y, scale_x, scale_y) = image_screen_geometry(original_image, frame)?;
+    Some(((x - image_x) as f64 * scale_x, (y - image_y) as f64 * scale_y))
+}
+
+// Calculate which part of the image is visible in the window, as (x, y, width, height) in image pixels
+fn visible_region(original_image: &ImageType, frame: &Frame, wind: &Window) -> Option<(i32, i32, i32, i32)> {
+    let (image_x, image_y, scale_x, scale_y) = image_screen_geometry(original_image, frame)?;
+    let displayed_image = frame.image()?;
+    let (display_w, display_h) = (displayed_image.w(), displayed_image.h());
+    let left = image_x.max(0);
+    let top = image_y.max(0);
+    let right = (image_x + display_w).min(wind.w());
+    let bottom = (image_y + display_h).min(wind.h());
+    if right <= left || bottom <= top {
+        return None;
+    }
+
+    Some((
+        ((left - image_x) as f64 * scale_x).round() as i32,
+        ((top - image_y) as f64 * scale_y).round() as i32,
+        ((right - left) as f64 * scale_x).round() as i32,
+        ((bottom - top) as f64 * scale_y).round() as i32,
+    ))
+}
+
+// Append the visible region of an image to a CSV file in the image's directory
+fn append_region_to_csv(image_file: &Path, region: (i32, i32, i32, i32)) -> Result<PathBuf, String> {
+    use std::io::Write;
+
+    let csv_path = image_file.with_file_name("lightningview_regions.csv");
+    let is_new_file = !csv_path.exists();
+    let mut csv_file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&csv_path)
+        .map_err(|err| format!("Failed to open {}: {}", csv_path.display(), err))?;
+    if is_new_file {
+        writeln!(csv_file, "file,x,y,width,height").map_err(|err| format!("Failed to write {}: {}", csv_path.display(), err))?;
+    }
+    let (x, y, width, height) = region;
+    let filename = image_file.file_name().unwrap_or_default().to_string_lossy().replace('"', "\"\"");
+    writeln!(csv_file, "\"{}\",{},{},{},{}", filename, x, y, width, height)
+        .map_err(|err| format!("Failed to write {}: {}", csv_path.display(), err))?;
+    Ok(csv_path)
+}
+
+fn order_by_name(image_order: &mut Vec<usize>, current_index: &mut usize, is_randomized: &mut bool) {
+    let original_index = image_order[*current_index];
+    // Remember the index of the image we're currently viewing
+    image_order.sort();
+    // Sort the image_order list to the original sequence
+    log::debug!("Image ordering sorted by name");
+    *is_randomized = false;
+    *current_index = image_order.iter().position(|&index| index == original_index).unwrap();
+    //Find the new index of the image we were viewing
+}
+
+// Position of the image after the current one, a random one not seen yet when shuffling
+fn next_index(current_index: usize, image_files: &Vec<PathBuf>, image_order: &Vec<usize>, shuffle: &mut Option<Shuffle>) -> usize {
+    shuffle.as_mut()
+        .and_then(|shuffle| shuffle.next(image_files, image_order, current_index))
+        .unwrap_or((current_index + 1) % image_order.len())
+}
+
+fn order_random(image_order: &mut Vec<usize>, current_index: &mut usize, is_randomized: &mut bool) {
+    let original_index = image_order[*current_index];
+    //Remember the index of the image we're currently viewing
+    let mut rng = rand::thread_rng();
+    image_order.shuffle(&mut rng);
+    log::debug!("Image ordering randomized");
+    *is_randomized = true;
+    *current_index = image_order.iter().position(|&index| index == original_index).unwrap();
+    //Find the new index of the image we were viewing
+}
+
+// Sort the image_order list by how similar the images look to the current one, which then comes first.
+// Images that fail to load go to the end.
+fn order_by_similarity(image_files: &Vec<PathBuf>, image_order: &mut Vec<usize>, current_index: &mut usize, is_randomized: &mut bool, wind: &mut Window) -> Result<(), String> {
+    // The hashes only need tiny images, so cached thumbnails spare decoding the whole folder again
+    let mut load_thumbnail = |path: &Path| thumbnails::get_or_create(path, SIMILARITY_THUMBNAIL_SIZE, |path| load_rgba(path, wind));
+    let reference = similarity::perceptual_hash(&load_thumbnail(&image_files[image_order[*current_index]])?);
+    let mut distances: Vec<(usize, u32)> = image_order.iter()
+        .map(|&index| {
+            let distance = match load_thumbnail(&image_files[index]) {
+                Ok(image) => similarity::hash_distance(reference, similarity::perceptual_hash(&image)),
+                Err(err) => {
+                    log::debug!("Skipping {} in similarity search: {}", image_files[index].display(), err);
+                    u32::MAX
+                }
+            };
+            (index, distance)
+        })
+        .collect();
+    // Stable sort, so equally similar images stay in their current order and the current image stays first
+    let original_index = image_order[*current_index];
+    distances.sort_by_key(|&(index, distance)| (index != original_index, distance));
+    *image_order = distances.iter().map(|&(index, _)| index).collect();
+    *current_index = 0;
+    *is_randomized = false;
+    log::debug!("Image ordering sorted by similarity: {:?}", distances);
+    Ok(())
+}
+
+// Sort or filter the image_order list using the analysis results.
+// Returns Ok(true) if the currently viewed image was filtered out and a different image needs to be displayed.
+fn apply_analysis_view(view: AnalysisView, worker: &AnalysisWorker, image_files: &Vec<PathBuf>, image_order: &mut Vec<usize>, current_index: &mut usize, is_randomized: &mut bool, file_type_filter: FileTypeFilter) -> Result<bool, String> {
+    let original_index = image_order[*current_index];
+    match view {
+        AnalysisView::SortBySharpness => {
+            // Blurriest first for culling, images that couldn't be analyzed go to the end
+            let mut sharpness: Vec<(usize, f64)> = image_order.iter()
+                .map(|&index| (index, worker.stats(&image_files[index]).map_or(f64::INFINITY, |stats| stats.sharpness)))
+                .collect();
+            sharpness.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+            *image_order = sharpness.iter().map(|&(index, _)| index).collect();
+            *is_randomized = false;
+            log::debug!("Image ordering sorted by sharpness");
+        }
+        AnalysisView::BlurryOnly | AnalysisView::ProblemExposures => {
+            let threshold = worker.blurry_threshold().ok_or("None of the images could be analyzed".to_string())?;
+            let is_shown = |stats: analysis::ImageStats| match view {
+                AnalysisView::BlurryOnly => stats.sharpness < threshold,
+                _ => stats.is_problem_exposure(),
+            };
+            // Start over from the file type filter, so switching between these filters doesn't combine them
+            let mut new_order: Vec<usize> = (0..image_files.len())
+                .filter(|&index| file_type_filter.matches(&image_files[index]) && worker.stats(&image_files[index]).is_some_and(|stats| is_shown(stats)))
+                .collect();
+            if new_order.is_empty() {
+                return Err(if view == AnalysisView::BlurryOnly { "No blurry images found" } else { "No badly exposed images found" }.to_string());
+            }
+            if *is_randomized {
+                let mut rng = rand::thread_rng();
+                new_order.shuffle(&mut rng);
+            }
+            log::debug!("Showing {} images for {:?}", new_order.len(), view);
+            *image_order = new_order;
+        }
+        AnalysisView::Duplicates => {
+            // One group after another, so the copies can be compared by stepping through them and the unwanted ones deleted
+            let candidates: Vec<(usize, analysis::ImageStats)> = (0..image_files.len())
+                .filter(|&index| file_type_filter.matches(&image_files[index]))
+                .filter_map(|index| worker.stats(&image_files[index]).map(|stats| (index, stats)))
+                .collect();
+            let hashes: Vec<(u64, u64)> = candidates.iter().map(|(_, stats)| (stats.content_hash, stats.perceptual_hash)).collect();
+            let groups = similarity::duplicate_groups(&hashes);
+            if groups.is_empty() {
+                return Err("No duplicates found".to_string());
+            }
+            log::debug!("Found {} groups of duplicates", groups.len());
+            *image_order = groups.iter().flatten().map(|&candidate| candidates[candidate].0).collect();
+        }
+    }
+    let new_position = image_order.iter().position(|&index| index == original_index);
+    *current_index = new_position.unwrap_or(0);
+    Ok(new_position.is_none())
+}
+
+// Make sure the images of the current directory are being analyzed in the background
+fn ensure_analysis_worker(analysis_worker: &mut Option<AnalysisWorker>, current_directory: &Path, image_files: &Vec<PathBuf>) {
+    if analysis_worker.as_ref().is_none_or(|worker| worker.directory != current_directory) {
+        log::debug!("Analyzing {} images in the background", image_files.len());
+        *analysis_worker = Some(AnalysisWorker::start(current_directory, image_files.clone()));
+    }
+}
+
+// Rebuild the image_order list so it only contains files matching the filter, keeping the current sort mode.
+// Returns Ok(true) if the currently viewed image was filtered out and a different image needs to be displayed.
+fn apply_filter(image_files: &Vec<PathBuf>, image_order: &mut Vec<usize>, current_index: &mut usize, is_randomized: bool, filter: FileTypeFilter) -> Result<bool, String> {
+    let original_index = image_order.get(*current_index).copied();
+    let mut new_order: Vec<usize> = (0..image_files.len())
+        .filter(|&index| filter.matches(&image_files[index]))
+        .collect();
+    if new_order.is_empty() {
+        return Err(format!("No images match the filter {:?}", filter));
+    }
+    if is_randomized {
+        let mut rng = rand::thread_rng();
+        new_order.shuffle(&mut rng);
+    }
+    // Stay on the image we're currently viewing if it's still part of the list
+    let new_position = new_order.iter().position(|&index| Some(index) == original_index);
+    *current_index = new_position.unwrap_or(0);
+    *image_order = new_order;
+    log::debug!("Navigation filter set to {:?}, {} images", filter, image_order.len());
+    Ok(new_position.is_none())
+}
+
+// Remove the currently viewed image from the lists, e.g. after it was deleted
+fn remove_current_image(image_files: &mut Vec<PathBuf>, image_order: &mut Vec<usize>, current_index: &mut usize) {
+    let removed_index = image_order.remove(*current_index);
+    image_files.remove(removed_index);
+    // All indices after the removed file have shifted down by one
+    for index in image_order.iter_mut() {
+        if *index > removed_index {
+            *index -= 1;
+        }
+    }
+    if !image_order.is_empty() {
+        *current_index = *current_index % image_order.len();
+    }
+}
+
+// Copy the visible region as "x,y,w,h" to the clipboard
+fn copy_visible_region(original_image: &ImageType, frame: &Frame, wind: &Window) {
+    match visible_region(original_image, frame, wind) {
+        Some((x, y, width, height)) => {
+            if let Err(err) = copy_text_to_clipboard(&format!("{},{},{},{}", x, y, width, height)) {
+                log::error!("{}", err);
+            }
+        },
+        None => log::debug!("No part of the image is visible"),
+    }
+}
+
+// Take over the images listed by a scan of the directory, e.g. after files were added, removed or renamed, staying
+// on the current image if it still exists. Returns true if the currently viewed image is gone and a different image
+// needs to be displayed.
+fn merge_image_list(new_files: Vec<PathBuf>, image_files: &mut Vec<PathBuf>, image_order: &mut Vec<usize>, current_index: &mut usize, is_randomized: bool, analysis_filter: &mut Option<AnalysisView>, file_type_filter: &mut FileTypeFilter) -> bool {
+    let current_path = image_order.get(*current_index).map(|&index| image_files[index].clone());
+
+    // Keep the existing (possibly random) order for files we already knew about, and add new files at the end
+    let new_indices: HashMap<&PathBuf, usize> = new_files.iter().enumerate().map(|(index, path)| (path, index)).collect();
+    let mut new_order: Vec<usize> = Vec::new();
+    for &index in image_order.iter() {
+        if let Some(&new_index) = new_indices.get(&image_files[index]) {
+            new_order.push(new_index);
+        }
+    }
+    let known: HashSet<usize> = new_order.iter().copied().collect();
+    // Added files haven't been analyzed, so they don't show up while the blurry, badly exposed or duplicate images are
+    let mut added: Vec<usize> = (0..new_files.len())
+        .filter(|&index| analysis_filter.is_none() && file_type_filter.matches(&new_files[index]) && !known.contains(&index))
+        .collect();
+    if is_randomized {
+        let mut rng = rand::thread_rng();
+        added.shuffle(&mut rng);
+    }
+    // Unless shuffled or sorted by similarity or sharpness, the known files are in the order of the listing, where the added ones
+    // are sorted in. Any other order is kept, with the added files at the end.
+    let is_listed_order = !is_randomized && new_order.is_sorted();
+    new_order.extend(added);
+    if is_listed_order {
+        new_order.sort();
+    }
+    log::debug!("Directory listed, {} images now", new_files.len());
+    *image_files = new_files;
+
+    if new_order.is_empty() {
+        // None of the remaining files match the filters, show everything again
+        *analysis_filter = None;
+        *file_type_filter = FileTypeFilter::All;
+        let _ = apply_filter(image_files, image_order, current_index, is_randomized, *file_type_filter);
+        return true;
+    }
+
+    match new_order.iter().position(|&index| Some(&image_files[index]) == current_path.as_ref()) {
+        Some(position) => {
+            *current_index = position;
+            *image_order = new_order;
+            false
+        },
+        None => {
+            *current_index = (*current_index).min(new_order.len() - 1);
+            *image_order = new_order;
+            true
+        }
+    }
+}
+
+// Watches a directory for changes, and notifies the main window when the file list needs to be refreshed
+struct DirectoryWatcher {
+    _watcher: notify::RecommendedWatcher,
+    poll_timeout: app::TimeoutHandle,
+}
+
+impl DirectoryWatcher {
+    fn new(directory: &Path) -> notify::Result<DirectoryWatcher> {
+        use notify::Watcher;
+
+        let changed = Arc::new(AtomicBool::new(false));
+        let changed_flag = Arc::clone(&changed);
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            match res {
+                Ok(event) if !event.kind.is_access() => changed_flag.store(true, Ordering::Relaxed),
+                Ok(_) => {},
+                Err(err) => log::error!("Directory watch error: {}", err),
+            }
+        })?;
+        let mode = if scan::is_recursive() { notify::RecursiveMode::Recursive } else { notify::RecursiveMode::NonRecursive };
+        watcher.watch(directory, mode)?;
+
+        // The watcher runs on its own thread, so hand the change over to the UI thread. Polling also
+        // collapses bursts of events (e.g. while a file is being copied) into a single refresh.
+        let poll_timeout = app::add_timeout3(DIRECTORY_POLL_INTERVAL, move |handle| {
+            if changed.swap(false, Ordering::Relaxed) {
+                let _ = app::handle_main(DIRECTORY_CHANGED_EVENT);
+            }
+            app::repeat_timeout3(DIRECTORY_POLL_INTERVAL, handle);
+        });
+        Ok(DirectoryWatcher { _watcher: watcher, poll_timeout })
+    }
+}
+
+impl Drop for DirectoryWatcher {
+    fn drop(&mut self) {
+        app::remove_timeout3(self.poll_timeout);
+    }
+}
+
+// Pick up files that are added, removed or renamed while browsing
+fn start_directory_watcher(directory: &Path) -> Option<DirectoryWatcher> {
+    match DirectoryWatcher::new(directory) {
+        Ok(watcher) => Some(watcher),
+        Err(err) => {
+            log::error!("Failed to watch directory for changes: {}", err);
+            None
+        }
+    }
+}
+
+// Find the images to browse for a file or folder. A file is shown within its directory, a folder starts at its first image.
+// Returns the directory, its images and the index of the image to show first.
+fn images_for_path(path: &Path) -> Result<(PathBuf, Vec<PathBuf>, usize), String> {
+    let absolute_path = get_absolute_path(&path.to_string_lossy());
+    let directory = if absolute_path.is_dir() {
+        absolute_path.clone()
+    } else {
+        absolute_path.parent().ok_or("Failed to get the parent directory.".to_string())?.to_path_buf()
+    };
+    let image_files = gather_images_from_directory(&directory)
+        .map_err(|err| format!("Failed to read directory {}: {}", directory.display(), err))?;
+    if image_files.is_empty() {
+        return Err(format!("No images found in {}", directory.display()));
+    }
+    let index = image_files.iter().position(|path| path == &absolute_path).unwrap_or(0);
+    Ok((directory, image_files, index))
+}
+
+// Combine the images of several files and folders into one list, in the order they're given and each folder's
+// images sorted like a single folder's. Images given more than once are listed only the first time.
+fn images_for_paths(paths: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut image_files: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let absolute_path = get_absolute_path(path);
+        if absolute_path.is_dir() {
+            let files = gather_images_from_directory(&absolute_path)
+                .map_err(|err| format!("Failed to read directory {}: {}", absolute_path.display(), err))?;
+            image_files.extend(files);
+        } else if absolute_path.is_file() && is_supported_image(&absolute_path) {
+            image_files.push(absolute_path);
+        } else {
+            return Err(format!("Not a supported image or a folder: {}", path));
+        }
+    }
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    image_files.retain(|path| seen.insert(path.clone()));
+    if image_files.is_empty() {
+        return Err("No images found".to_string());
+    }
+    Ok(image_files)
+}
+
+// Remember an opened file or folder in the recently opened list
+fn add_recent(path: &Path) {
+    let absolute_path = get_absolute_path(&path.to_string_lossy());
+    if let Err(err) = config::add_recent(&absolute_path) {
+        log::error!("{}", err);
+    }
+    #[cfg(target_os = "windows")]
+    add_to_recent_documents(&absolute_path);
+}
+
+// Switch to browsing a different file or folder, keeping the current sort mode and filter if possible
+fn open_path(path: &Path, image_files: &mut Vec<PathBuf>, image_order: &mut Vec<usize>, current_index: &mut usize, current_directory: &mut PathBuf, directory_watcher: &mut Option<DirectoryWatcher>, is_randomized: bool, file_type_filter: &mut FileTypeFilter) -> Result<(), String> {
+    let (directory, files, index) = images_for_path(path)?;
+    log::debug!("Opening {} in {}", path.display(), directory.display());
+    add_recent(path);
+    *image_files = files;
+    *image_order = (0..image_files.len()).collect();
+    *current_index = index;
+    if apply_filter(image_files, image_order, current_index, is_randomized, *file_type_filter).is_err() {
+        *file_type_filter = FileTypeFilter::All;
+        apply_filter(image_files, image_order, current_index, is_randomized, *file_type_filter)?;
+    }
+    if *current_directory != directory {
+        // Drop the old watcher first so its poll timer is gone before the new one starts
+        *directory_watcher = None;
+        *directory_watcher = start_directory_watcher(&directory);
+        *current_directory = directory;
+    }
+    Ok(())
+}
+
+// Browse the built-in monitor test patterns instead of a directory
+fn open_test_patterns(image_files: &mut Vec<PathBuf>, image_order: &mut Vec<usize>, current_index: &mut usize, current_directory: &mut PathBuf, directory_watcher: &mut Option<DirectoryWatcher>, file_type_filter: &mut FileTypeFilter) {
+    *image_files = testpattern::test_pattern_paths();
+    *image_order = (0..image_files.len()).collect();
+    *current_index = 0;
+    *file_type_filter = FileTypeFilter::All;
+    // There is no directory to watch, and opening any real directory afterwards has to start a new watcher
+    *directory_watcher = None;
+    *current_directory = PathBuf::new();
+}
+
+// Extract the paths from text dropped onto the window. Depending on the platform this is either
+// a plain path per line or a list of file:// URIs.
+fn dropped_paths(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.strip_prefix("file://") {
+            Some(uri_path) => PathBuf::from(percent_decode(uri_path)),
+            None => PathBuf::from(line),
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = hex {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Enable or disable zoom/pan synchronization with other instances
+fn toggle_view_sync(view_sync: &mut Option<ViewSync>, sync_poll_timeout: &mut Option<app::TimeoutHandle>) {
+    if view_sync.is_some() {
+        *view_sync = None;
+        if let Some(handle) = sync_poll_timeout.take() {
+            app::remove_timeout3(handle);
+        }
+        log::debug!("View sync disabled");
+    } else {
+        match ViewSync::new() {
+            Ok(sync) => {
+                *view_sync = Some(sync);
+                // Regularly ask the window to check for view updates from other instances
+                *sync_poll_timeout = Some(app::add_timeout3(SYNC_POLL_INTERVAL, |handle| {
+                    let _ = app::handle_main(SYNC_EVENT);
+                    app::repeat_timeout3(SYNC_POLL_INTERVAL, handle);
+                }));
+                log::debug!("View sync enabled");
+            },
+            Err(err) => log::error!("Failed to enable view sync: {}", err),
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+//    std::env::set_var("RUST_LOG", "debug");
+    env_logger::init();
+
+    let cli = cli::parse();
+    let mut is_fullscreen = !cli.windowed;
+    let mut is_randomized = false; // Whether to start with the images in random order
+    let mut is_scaled_to_fit = true; // Whether to start with the image zoomed in to fit the screen
+    let mut file_type_filter = FileTypeFilter::All; // Which file types to show when navigating
+    let mut view_sync: Option<ViewSync> = None; // Zoom/pan synchronization with other instances, if enabled
+    let mut sync_poll_timeout: Option<app::TimeoutHandle> = None;
+    let mut is_dnd_pending = false; // Set when files are dropped onto the window, the paths follow in a paste event
+    let mut is_measuring = false; // Whether dragging measures distances instead of panning
+    let mut measure_start: Option<(i32, i32)> = None;
+    let mut measure_scale: Option<PixelScale> = None;
+    let mut frame_animation: Option<FrameAnimation> = None; // Sprite sheet preview or blink comparison, stopped by any key
+    let mut is_aligning = true; // Whether to align images before blink comparing them
+    let mut is_always_on_top = false; // Whether the window floats above other windows
+    let mut normal_map_marks: HashMap<PathBuf, bool> = HashMap::new(); // Images marked as normal maps or not, overriding the file name
+    let mut output_target: Option<Target> = None; // Output format whose crop is shown on top of the image
+    let mut analysis_worker: Option<AnalysisWorker> = None; // Sharpness analysis of the current directory, started on first use
+    let mut pending_analysis_view: Option<AnalysisView> = None; // Applied as soon as the analysis is done
+    let mut analysis_filter: Option<AnalysisView> = None; // Blurry or badly exposed images only
+    #[cfg(feature = "tagging")]
+    let mut tagger: Option<tagging::Tagger> = None; // Loaded when tags are first requested
+    let mut image_order:Vec<usize> = Vec::new();
+
+    // Headless rendering for scripts, without opening a window
+    if let Some(cli::Command::Render(options)) = &cli.command {
+        let is_using_sidecars = config::load_config().map(|config| config.rotation_sidecars).unwrap_or(false);
+        if let Err(err) = render::render(options, is_using_sidecars) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if cli.list_formats {
+        // One line per decoder, e.g. "fits: fits fit", easy to read from scripts
+        for (decoder, formats) in formats_by_decoder() {
+            println!("{}: {}", decoder, formats.join(" "));
+        }
+        return Ok(());
+    }
+    if let Some(paths) = &cli.thumbnail {
+        let is_using_sidecars = config::load_config().map(|config| config.rotation_sidecars).unwrap_or(false);
+        if let Err(err) = render::thumbnail(&paths[0], &paths[1], cli.thumbnail_size.max(1), is_using_sidecars) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(paths) = &cli.convert {
+        if let Err(err) = convert_image(&paths[0].to_string_lossy(), &paths[1]) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(image_file) = &cli.info {
+        match info::describe(Path::new(image_file)) {
+            Ok(description) => println!("{}", description),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    // An image piped in is saved to a file first, as everything else works on files
+    let image_paths: Vec<String> = cli.image_files.into_iter()
+        .map(|path| {
+            if path != stdin::STDIN_ARGUMENT {
+                return path;
+            }
+            match stdin::save_to_file() {
+                Ok(saved) => saved.to_string_lossy().into_owned(),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            }
+        })
+        .collect();
+    let is_forced_windowed = cli.windowed;
+    let is_forced_fullscreen = cli.fullscreen;
+    let mut is_resuming = cli.resume; // Whether to restore the state of the last session
+    // Monitors are numbered from 1 for the user
+    let fullscreen_monitor = cli.monitor.map(|number| number - 1); // Monitor used for fullscreen, counted from 0
+    if cli.debug_perf {
+        perf::enable();
+    }
+    if let Some(mode) = cli.sort {
+        scan::set_sort_mode(mode);
+    }
+    scan::set_recursive(cli.recursive);
+
+    #[cfg(target_os = "windows")]
+    {
+        if cli.register {
+            match register_urlhandler() {
+                Ok(_) => println!("Success! LightningView egistered as image viewer."),
+                Err(err) => println!("Failed to register as image viewer: {}", err),
+            }
+            std::process::exit(0);
+        } else if cli.unregister {
+            unregister_urlhandler();
+            println!("LightningView unregistered as image viewer.");
+            std::process::exit(0);
         } 
     }
 
+    let app = app::App::default();
+
+    // Settings problems are shown in a dialog, as there is usually no console to log to
+    let config = config::load_config().unwrap_or_else(|err| {
+        dialog::alert_default(&err);
+        config::Config::default()
+    });
+
+    // Images given by their web address are downloaded first, with a progress dialog
+    let image_paths: Vec<String> = image_paths.into_iter()
+        .map(|path| {
+            if !download::is_url(&path) {
+                return path;
+            }
+            match download::download(&path) {
+                Ok(downloaded) => downloaded.to_string_lossy().into_owned(),
+                Err(err) => {
+                    dialog::alert_default(&err);
+                    std::process::exit(1);
+                }
+            }
+        })
+        .collect();
+    let mut image_file = image_paths.first().cloned();
+
+    let (keymap, mut keymap_warnings) = Keymap::from_config(&config.keys);
+    let (mouse_bindings, mouse_warnings) = MouseBindings::from_config(&config.mouse);
+    keymap_warnings.extend(mouse_warnings);
+    if !keymap_warnings.is_empty() {
+        dialog::alert_default(&format!("Problems with the key bindings in the config file:\n{}", keymap_warnings.join("\n")));
+    }
+    let mut rotations = Rotations::new(config.rotation_sidecars);
+    let mut is_status_bar_shown = config.status_bar; // Whether file and loading information is shown at the bottom
+    if is_status_bar_shown {
+        perf::enable();
+    }
+    let mut view_lock = ViewLock::default();
+    let mut history = History::default(); // Images in the order they were viewed
+    let mut shuffle: Option<Shuffle> = None; // Slideshow shuffle without repeats, if enabled
+    let removed_files: Rc<RefCell<Vec<RemovedFile>>> = Rc::new(RefCell::new(Vec::new())); // Deleted or moved away this session, for restoring
+
     // Create an empty mutable image to be able to modify it later
     let empty_img = fltk::image::RgbImage::new(&[0; 4], 1, 1, fltk::enums::ColorDepth::Rgb8).unwrap();
     let mut original_image = ImageType::Shared(SharedImage::from_image(empty_img).unwrap());
 
-    let app = app::App::default();
-
-    // Enable bilinear filtering for scaling operations
+    // Enable bilinear filtering for scaling operations, the images being viewed use the configured scaling filter
     fltk::image::RgbImage::set_scaling_algorithm(fltk::image::RgbScaling::Bilinear);
+    if let Some(name) = &config.scaling_filter {
+        match ScalingFilter::parse(name) {
+            Some(filter) => scaling::set_filter(filter),
+            None => log::error!("Unknown scaling filter \"{}\", use auto, nearest, bilinear or lanczos", name),
+        }
+    }
 
     let mut zoom_factor = 1.0;
     let mut pan_origin: Option<(i32, i32)> = None;
+    let mut glide = Glide::default(); // Keeps the image moving for a moment after a fast pan
+    let mut loader = Loader::default(); // Decodes RAW, FITS and other slow formats in the background
+    let mut scrub = Scrub::default(); // Tells when an arrow key is held down to skip through images
+    let mut click_origin: Option<(i32, i32)> = None; // Where the left button was pressed, to tell clicks from drags
+    let mut zoom_box_start: Option<(i32, i32)> = None; // Corner of the rectangle being drawn with the middle button to zoom into
+    let mut swipe_origin: Option<(i32, i32)> = None; // Where a finger touched the screen, for swiping to other images
+    let mut gesture_origin: Option<(i32, i32)> = None;
+    let mut screen_capture: Option<image::RgbaImage> = None; // Screenshot shown while a region of it is selected
+    let mut capture_start: Option<(i32, i32)> = None;
+    let mut live_view: Option<LiveView> = None; // Tethered camera feed, Enter or Space saves a frame and any other key stops it
     let mut current_index = 0;
     let mut image_files: Vec<PathBuf> = Vec::new();
     
@@ -354,76 +2281,258 @@ fn main() -> Result<(), Box<dyn Error>> {
         (800, 600) // Default dimensions
     };
 
-    log::debug!("Image file: {}", image_file);
+    // When started without a file, offer to continue the last session
+    let last_session = config::load_session().filter(|session| session.file.exists());
+    if image_file.is_none() && !is_resuming {
+        if let Some(session) = &last_session {
+            let question = format!("Resume the last session at {}?", session.file.display());
+            is_resuming = dialog::choice2_default(&question, "No", "Resume", "") == Some(1);
+        }
+    }
+    let resumed_session = if is_resuming { last_session } else { None };
+    if let Some(session) = &resumed_session {
+        log::debug!("Resuming session: {:?}", session);
+        image_file = Some(session.file.to_string_lossy().into_owned());
+        is_fullscreen = (session.is_fullscreen || is_forced_fullscreen) && !is_forced_windowed;
+        is_scaled_to_fit = session.is_scaled_to_fit;
+    }
 
-    let absolute_path = get_absolute_path(image_file);
-    let parent_dir = absolute_path.parent().unwrap_or_else(|| {
-        println!("Failed to get the parent directory.");
-        std::process::exit(1);
-    });
+    // When started without a file (e.g. from the start menu), ask which image to open
+    let image_file = match image_file {
+        Some(image_file) => image_file,
+        None => match choose_image_file() {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => std::process::exit(0),
+        },
+    };
 
-    log::debug!("Parent dir: {:?}", parent_dir);
-
-    // Get a list of all image files in the directory
-    if let Ok(entries) = fs::read_dir(parent_dir) {
-        let mut all_supported_formats: Vec<&str> = Vec::new();
-        all_supported_formats.extend(&IMAGEREADER_SUPPORTED_FORMATS);
-        all_supported_formats.extend(&ANIM_SUPPORTED_FORMATS);
-        all_supported_formats.extend(&FLTK_SUPPORTED_FORMATS);
-        all_supported_formats.extend(&RAW_SUPPORTED_FORMATS);
-        all_supported_formats.extend(&FITS_SUPPORTED_FORMATS);
-        image_files = entries
-            .filter_map(|entry| entry.ok().map(|e| e.path()))
-            .filter(|path| {
-                path.is_file()
-                    && all_supported_formats.iter().any(|&format| path.to_string_lossy().to_lowercase().ends_with(format) 
-                )
-            })
-            .collect();
+    log::debug!("Image file: {}", image_file);
 
-        //Sort files by name, case insensitive
-        image_files.sort_by_key(|name| name.to_string_lossy().to_lowercase());
-        
-        // Find out where in the list our initially loaded file is, so we can navigate to the next/previous image
-        if let Some(index) = image_files.iter().position(|path| path == &absolute_path) {
-            current_index = index;
-        }
-    } else {
-        println!("Failed to read directory.");
-        app.quit();
-    }
+    // Get a list of all image files in the directory, and find out where in the list our initially loaded
+    // file is, so we can navigate to the next/previous image. A file is shown right away, while the rest
+    // of its directory is listed in the background.
+    let mut directory_scan: Option<DirectoryScan> = None;
+    let absolute_path = get_absolute_path(&image_file);
+    let scanned_directory = absolute_path.parent().filter(|_| absolute_path.is_file() && is_supported_image(&absolute_path));
+    let mut current_directory = match scanned_directory {
+        // Several files and folders are browsed as one list, which has no directory to watch or list again
+        _ if image_paths.len() > 1 && resumed_session.is_none() => match images_for_paths(&image_paths) {
+            Ok(files) => {
+                image_files = files;
+                PathBuf::new()
+            },
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        Some(directory) => {
+            image_files = vec![absolute_path.clone()];
+            directory_scan = Some(DirectoryScan::start(directory, Some(&absolute_path), is_supported_image));
+            add_recent(&absolute_path);
+            directory.to_path_buf()
+        },
+        None => match images_for_path(Path::new(&image_file)) {
+            Ok((directory, files, index)) => {
+                image_files = files;
+                current_index = index;
+                add_recent(Path::new(&image_file));
+                directory
+            },
+            Err(err) => {
+                println!("{}", err);
+                std::process::exit(1);
+            }
+        },
+    };
 
-    if image_files.is_empty() {
-        println!("No images found in the directory. Exiting.");
-        app.quit()
-    }
+    log::debug!("Parent dir: {:?}", current_directory);
 
     // Initialize the image_order list with a sequential index so they are browsed in-sequence
     for (i, _path) in image_files.iter().enumerate() {
         image_order.push(i);
     }
+    if cli.random || resumed_session.as_ref().is_some_and(|session| session.is_randomized) {
+        order_random(&mut image_order, &mut current_index, &mut is_randomized);
+    }
+
+    let mut directory_watcher = if current_directory.as_os_str().is_empty() { None } else { start_directory_watcher(&current_directory) };
 
     let mut wind = Window::new(0, 0, screen_width, screen_height, "Lightning View");
+    // The window is placed where it was when the viewer was last closed, a resumed session brings its own placement
+    let last_geometry = config::load_window_geometry();
+    if let Some(geometry) = &last_geometry {
+        if geometry.width > 0 && geometry.height > 0 {
+            wind.resize(geometry.x, geometry.y, geometry.width, geometry.height);
+        }
+    }
+    if let Some(session) = &resumed_session {
+        if session.window_width > 0 && session.window_height > 0 {
+            wind.resize(session.window_x, session.window_y, session.window_width, session.window_height);
+        }
+    }
+    if let Some(monitor) = fullscreen_monitor {
+        if let Err(err) = set_fullscreen_monitor(&mut wind, monitor) {
+            log::error!("{}", err);
+        }
+    }
     wind.make_resizable(true);
-    wind.set_color(Color::Black);
+    wind.set_color(cli.background.unwrap_or(Color::Black));
     wind.fullscreen(is_fullscreen);
     let mut frame = Frame::default_fill();
+    // Transparent frame on top of the image for drawing measurements and other information
+    let overlay = Rc::new(RefCell::new(Overlay::default()));
+    let mut overlay_frame = Frame::default_fill();
+    let overlay_draw = Rc::clone(&overlay);
+    overlay_frame.draw(move |overlay_frame| {
+        overlay_draw.borrow().draw(overlay_frame.w(), overlay_frame.h());
+        if let Some(progress) = loader::progress() {
+            overlay::draw_progress(progress, overlay_frame.w(), overlay_frame.h());
+        }
+    });
+    let mut toolbar = Toolbar::new(&wind, config.toolbar);
+    // Seconds each image is shown in the slideshow, from the command line or the config file
+    let slideshow_interval = cli.slideshow.flatten().or(config.slideshow_interval).filter(|&interval| interval > 0.0).unwrap_or(SLIDESHOW_INTERVAL);
+    let mut slideshow: Option<app::TimeoutHandle> = cli.slideshow.map(|_| start_slideshow(slideshow_interval)); // Advances to the next image while the slideshow runs
     wind.end(); // Finish adding UI components to the window
+    if perf::is_enabled() {
+        let perf_overlay = Rc::clone(&overlay);
+        let mut perf_window = wind.clone();
+        app::add_timeout3(perf::REFRESH_INTERVAL, move |handle| {
+            perf_overlay.borrow_mut().perf_report = Some(perf::report());
+            perf_window.redraw();
+            app::repeat_timeout3(perf::REFRESH_INTERVAL, handle);
+        });
+    }
 
     // Load and display the initial image
-    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen,is_scaled_to_fit);
+    display_image_file(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen,is_scaled_to_fit, &rotations, &mut view_lock);
+    if let Some(session) = &resumed_session {
+        if session.zoom_factor > 1.0 {
+            zoom_factor = session.zoom_factor;
+            set_frame_zoom(&original_image, &mut frame, zoom_factor);
+        }
+        frame.set_pos(session.offset_x, session.offset_y);
+    }
 
     wind.show();
+    if last_geometry.is_some_and(|geometry| geometry.is_maximized) && !is_fullscreen {
+        wind.maximize();
+    }
+    // FLTK turns touches into mouse events, but ignores pinching
+    #[cfg(target_os = "windows")]
+    if let Err(err) = watch_pinch(wind.raw_handle(), PINCH_EVENT) {
+        log::error!("{}", err);
+    }
+
+    // Snapshot of the viewer state, saved on exit so the session can be resumed
+    let session: Rc<RefCell<Option<config::Session>>> = Rc::new(RefCell::new(None));
+    let session_snapshot = Rc::clone(&session);
+    // Last windowed placement, saved on exit
+    let window_geometry: Rc<RefCell<Option<config::WindowGeometry>>> = Rc::new(RefCell::new(last_geometry));
+    let window_geometry_snapshot = Rc::clone(&window_geometry);
 
 
     wind.handle(move |mut wind, event| {
+        // Remember the state before handling the event, as the event might be the one closing the viewer
+        if let Some(&index) = image_order.get(current_index) {
+            let (window_x, window_y, window_width, window_height) = if is_fullscreen {
+                // Keep the windowed geometry of the previous snapshot
+                session_snapshot.borrow().as_ref()
+                    .map(|session| (session.window_x, session.window_y, session.window_width, session.window_height))
+                    .unwrap_or((0, 0, 0, 0))
+            } else {
+                *window_geometry_snapshot.borrow_mut() = Some(config::WindowGeometry {
+                    x: wind.x(),
+                    y: wind.y(),
+                    width: wind.w(),
+                    height: wind.h(),
+                    is_maximized: wind.maximize_active(),
+                });
+                (wind.x(), wind.y(), wind.w(), wind.h())
+            };
+            *session_snapshot.borrow_mut() = Some(config::Session {
+                file: image_files[index].clone(),
+                zoom_factor,
+                offset_x: frame.x(),
+                offset_y: frame.y(),
+                window_x,
+                window_y,
+                window_width,
+                window_height,
+                is_fullscreen,
+                is_scaled_to_fit,
+                is_randomized,
+            });
+        }
+
+        // Every image that was shown becomes part of the history, however it was reached
+        if let Some(&index) = image_order.get(current_index) {
+            history.visit(&image_files[index]);
+        }
+
+        if is_status_bar_shown {
+            let new_status = image_order.get(current_index).map(|&index| status_text(&image_files[index], current_index, image_order.len(), &original_image));
+            if overlay.borrow().status_bar != new_status {
+                overlay.borrow_mut().status_bar = new_status;
+                wind.redraw();
+            }
+        }
+
+        // Keep the output target frame on the image as it's panned, zoomed or replaced
+        if let Some(target) = output_target {
+            let new_target_frame = target_frame(target, &original_image, &frame);
+            if overlay.borrow().target_frame != new_target_frame {
+                overlay.borrow_mut().target_frame = new_target_frame;
+                wind.redraw();
+            }
+        }
+
         match event {
             Event::Focus => true,
-            Event::Leave => true,
+            Event::Leave => {
+                let had_arrow = overlay.borrow_mut().edge_arrow.take().is_some();
+                if toolbar.update(i32::MAX, wind.w()) || had_arrow {
+                    wind.redraw();
+                }
+                true
+            }
+            Event::Move => {
+                // Hint at the edges that navigate when clicked
+                let edge_arrow = match mouse_bindings.edge_action(app::event_x(), wind.w()) {
+                    Some(Action::Previous) => Some(EdgeArrow::Previous),
+                    Some(_) => Some(EdgeArrow::Next),
+                    None => None,
+                };
+                let is_arrow_changed = overlay.borrow().edge_arrow != edge_arrow;
+                overlay.borrow_mut().edge_arrow = edge_arrow;
+                if toolbar.update(app::event_y(), wind.w()) || is_arrow_changed {
+                    wind.redraw();
+                }
+                // The toolbar buttons need the moves too, for their tooltips
+                false
+            }
             Event::MouseWheel => {
+                glide.stop();
                 let dy = app::event_dy();
+                match mouse_bindings.wheel_action(dy == MouseWheel::Down, app::event_state()) {
+                    Some(Action::Next) => {
+                        current_index = next_index(current_index, &image_files, &image_order, &mut shuffle);
+                        log::debug!("Loading next image: {}", image_files[image_order[current_index]].display());
+                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                        return true;
+                    }
+                    Some(_) => {
+                        current_index = (current_index + image_order.len() - 1) % image_order.len();
+                        log::debug!("Loading previous image: {}", image_files[image_order[current_index]].display());
+                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                        return true;
+                    }
+                    None => {}
+                }
                 let mouse_pos = (app::event_x(), app::event_y());
                 let base_zoom_speed = 0.2;
+                overlay.borrow_mut().measure_line = None;
                 let mut relative_pos = (0, 0);
                 log::debug!("Wind width/height: {}, {}", wind.width(), wind.height());
 
@@ -441,23 +2550,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                     zoom_factor = 1.0; // Don't zoom out beyond the original size
                 }
 
-                match &original_image {
-                    ImageType::Shared(img) => {
-                        let new_image = img.clone();
-                        let new_width = (new_image.width() as f64 * zoom_factor) as i32;
-                        let new_height = (new_image.height() as f64 * zoom_factor) as i32;
-                        log::debug!("New width/height: {}, {}", new_width, new_height);
-                        frame.set_image(Some(new_image.copy_sized(new_width, new_height)));
-                    },
-                    ImageType::AnimatedGif(anim_img) => {
-                        let new_image = anim_img.clone();
-                        let new_width = (new_image.width() as f64 * zoom_factor) as i32;
-                        let new_height = (new_image.height() as f64 * zoom_factor) as i32;
-                        log::debug!("New width/height: {}, {}", new_width, new_height);
-                        frame.set_image(Some(new_image.copy_sized(new_width, new_height)));
-                    }
-                
-                }
+                set_frame_zoom(&original_image, &mut frame, zoom_factor);
 
                 let new_pos_x = frame.x() - relative_pos.0/2;
                 let new_pos_y = frame.y() - relative_pos.1/2;
@@ -472,13 +2565,165 @@ fn main() -> Result<(), Box<dyn Error>> {
                 log::debug!("Zoom factor: {}", zoom_factor);
                 log::debug!("New X/Y: {}, {}", new_pos_x, new_pos_y);
 
+                if let Some(sync) = &view_sync {
+                    sync.broadcast(ViewState { zoom_factor, x: frame.x(), y: frame.y() });
+                }
+
                 wind.redraw(); 
                 true
             }
-            Event::Push => {
-                if app::event_mouse_button() == app::MouseButton::Left {
+            Event::Push | Event::Released if toolbar.contains(app::event_x(), app::event_y()) => false,
+            Event::Push | Event::Released => {
+                let button = app::event_mouse_button();
+                let mut is_menu_requested = false;
+                if event == Event::Push {
+                    // Grabbing the image catches it
+                    glide.stop();
+                }
+                if event == Event::Released {
+                    if pan_origin.take().is_some() {
+                        glide.start();
+                    }
+                    // Swiping left shows the next image and right the previous one, unless the image is larger than the window to pan it
+                    if let Some((start_x, start_y)) = swipe_origin.take().filter(|_| is_scaled_to_fit && zoom_factor <= 1.0) {
+                        let swipe = mouse_bindings.gesture_action(start_x - app::event_x(), app::event_y() - start_y);
+                        if swipe.is_some() {
+                            click_origin = None;
+                        }
+                        match swipe {
+                            Some(Action::Next) => {
+                                current_index = next_index(current_index, &image_files, &image_order, &mut shuffle);
+                                log::debug!("Loading next image: {}", image_files[image_order[current_index]].display());
+                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                            }
+                            Some(_) => {
+                                current_index = (current_index + image_order.len() - 1) % image_order.len();
+                                log::debug!("Loading previous image: {}", image_files[image_order[current_index]].display());
+                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                            }
+                            None => {}
+                        }
+                    }
+                    // A left click without dragging on a window edge navigates, if enabled
+                    if let Some((start_x, start_y)) = click_origin.take() {
+                        if (app::event_x() - start_x).abs() < CLICK_DISTANCE && (app::event_y() - start_y).abs() < CLICK_DISTANCE {
+                            match mouse_bindings.edge_action(app::event_x(), wind.w()) {
+                                Some(Action::Previous) => {
+                                    current_index = (current_index + image_order.len() - 1) % image_order.len();
+                                    log::debug!("Loading previous image: {}", image_files[image_order[current_index]].display());
+                                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                                }
+                                Some(_) => {
+                                    current_index = next_index(current_index, &image_files, &image_order, &mut shuffle);
+                                    log::debug!("Loading next image: {}", image_files[image_order[current_index]].display());
+                                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                                }
+                                None => {}
+                            }
+                        }
+                    }
+                    // Zoom so the rectangle drawn with the middle button fills the window
+                    if let Some((start_x, start_y)) = zoom_box_start.take() {
+                        overlay.borrow_mut().selection = None;
+                        let (box_width, box_height) = ((app::event_x() - start_x).abs(), (app::event_y() - start_y).abs());
+                        if box_width > CLICK_DISTANCE && box_height > CLICK_DISTANCE {
+                            let (center_x, center_y) = ((start_x + app::event_x()) / 2, (start_y + app::event_y()) / 2);
+                            let ratio = (wind.w() as f64 / box_width as f64).min(wind.h() as f64 / box_height as f64);
+                            zoom_around(&original_image, &mut frame, &mut zoom_factor, ratio, center_x, center_y);
+                            if zoom_factor > 1.0 {
+                                frame.set_pos(frame.x() + wind.w() / 2 - center_x, frame.y() + wind.h() / 2 - center_y);
+                            }
+                            if let Some(sync) = &view_sync {
+                                sync.broadcast(ViewState { zoom_factor, x: frame.x(), y: frame.y() });
+                            }
+                        }
+                        wind.redraw();
+                    }
+                    // Finish selecting the screen region, and show only that part of the screenshot
+                    if let (Some(start), Some(capture)) = (capture_start.take(), screen_capture.take()) {
+                        overlay.borrow_mut().selection = None;
+                        let corners = (
+                            window_to_image_coords(&original_image, &frame, start.0, start.1),
+                            window_to_image_coords(&original_image, &frame, app::event_x(), app::event_y()),
+                        );
+                        let region_bounds = corners.0.zip(corners.1).map(|((x1, y1), (x2, y2))| {
+                            let (left, top) = (x1.min(x2).max(0.0) as u32, y1.min(y2).max(0.0) as u32);
+                            let width = (x1.max(x2) as u32).min(capture.width()).saturating_sub(left);
+                            let height = (y1.max(y2) as u32).min(capture.height()).saturating_sub(top);
+                            (left, top, width, height)
+                        });
+                        match region_bounds {
+                            Some((left, top, width, height)) if width > 1 && height > 1 => {
+                                    let region = image::imageops::crop_imm(&capture, left, top, width, height).to_image();
+                                match rgba_to_shared_image(&region) {
+                                    Ok(image) => display_image(ImageType::Shared(image), &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit),
+                                    Err(err) => log::error!("Failed to show the captured region: {}", err),
+                                }
+                            }
+                            // A click without dragging, keep selecting
+                            _ => screen_capture = Some(capture),
+                        }
+                        wind.redraw();
+                    }
+                    // A right-drag that is too short for a gesture counts as a click and opens the menu
+                    if let Some((start_x, start_y)) = gesture_origin.take() {
+                        match mouse_bindings.gesture_action(app::event_x() - start_x, app::event_y() - start_y) {
+                            Some(Action::Next) => {
+                                current_index = next_index(current_index, &image_files, &image_order, &mut shuffle);
+                                log::debug!("Loading next image: {}", image_files[image_order[current_index]].display());
+                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                            }
+                            Some(_) => {
+                                current_index = (current_index + image_order.len() - 1) % image_order.len();
+                                log::debug!("Loading previous image: {}", image_files[image_order[current_index]].display());
+                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                            }
+                            None => is_menu_requested = true,
+                        }
+                    }
+                } else if button == app::MouseButton::Back || button == app::MouseButton::Forward {
+                    // The side buttons show the previous or next image, or retrace the viewed images like in a web browser
+                    let position = match mouse_bindings.side_button_action(button == app::MouseButton::Forward) {
+                        Action::Next => Some(next_index(current_index, &image_files, &image_order, &mut shuffle)),
+                        Action::Previous => Some((current_index + image_order.len() - 1) % image_order.len()),
+                        Action::Forward => history.forward(&image_files, &image_order),
+                        _ => history.back(&image_files, &image_order),
+                    };
+                    if let Some(position) = position {
+                        current_index = position;
+                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                    }
+                } else if button == app::MouseButton::Left && app::event_clicks() && is_touch() {
+                    // Double tap
+                    is_scaled_to_fit = !is_scaled_to_fit;
+                    log::debug!("Toggling image scaling to fit the screen: {}", is_scaled_to_fit);
+                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                } else if button == app::MouseButton::Left && app::event_clicks() && screen_capture.is_none() && !is_measuring
+                    && mouse_bindings.edge_action(app::event_x(), wind.w()).is_none() {
+                    // Double click, holding the button down after a single click still pans
+                    wind.make_resizable(true);
+                    is_fullscreen = !is_fullscreen;
+                    wind.fullscreen(is_fullscreen);
+                    log::debug!("Toggling fullscreen: {}", is_fullscreen);
+                } else if button == app::MouseButton::Left && screen_capture.is_some() {
+                    capture_start = Some((app::event_x(), app::event_y()));
+                } else if button == app::MouseButton::Left && is_measuring {
+                    measure_start = Some((app::event_x(), app::event_y()));
+                    measure_scale = measure::pixel_scale(&image_files[image_order[current_index]]);
+                } else if button == app::MouseButton::Middle && mouse_bindings.middle_drag == MiddleDragMode::ZoomBox {
+                    zoom_box_start = Some((app::event_x(), app::event_y()));
+                } else if button == app::MouseButton::Left || (button == app::MouseButton::Middle && mouse_bindings.middle_drag == MiddleDragMode::Pan) {
                     pan_origin = Some((app::event_x(), app::event_y()));
-                } else if app::event_mouse_button() == app::MouseButton::Right {
+                    if button == app::MouseButton::Left {
+                        click_origin = pan_origin;
+                        swipe_origin = if is_touch() { pan_origin } else { None };
+                    }
+                } else if button == app::MouseButton::Right && mouse_bindings.right_drag == RightDragMode::Gestures {
+                    gesture_origin = Some((app::event_x(), app::event_y()));
+                } else if button == app::MouseButton::Right {
+                    is_menu_requested = true;
+                }
+                if is_menu_requested {
                     let coords = app::event_coords();
                     log::debug!("coords: {:?}", coords);
                     let mut checkbox_scale_to_fit = "☐ Scale to fit";
@@ -493,7 +2738,95 @@ fn main() -> Result<(), Box<dyn Error>> {
                     if is_randomized {
                         checkbox_randomize = "☑ Random order";
                     }
-                    let popup_menu = fltk::menu::MenuItem::new(&[checkbox_fullscreen, checkbox_scale_to_fit, checkbox_randomize]);
+                    let mut checkbox_raw_only = "☐ RAW only";
+                    if file_type_filter == FileTypeFilter::RawOnly {
+                        checkbox_raw_only = "☑ RAW only";
+                    }
+                    let mut checkbox_jpeg_only = "☐ JPEG only";
+                    if file_type_filter == FileTypeFilter::JpegOnly {
+                        checkbox_jpeg_only = "☑ JPEG only";
+                    }
+                    let mut checkbox_sync_view = "☐ Sync view";
+                    if view_sync.is_some() {
+                        checkbox_sync_view = "☑ Sync view";
+                    }
+                    let mut checkbox_shuffle = "☐ Shuffle without repeats";
+                    if shuffle.is_some() {
+                        checkbox_shuffle = "☑ Shuffle without repeats";
+                    }
+                    let mut checkbox_toolbar = "☐ Toolbar";
+                    if toolbar.is_enabled {
+                        checkbox_toolbar = "☑ Toolbar";
+                    }
+                    let mut checkbox_status_bar = "☐ Status bar";
+                    if is_status_bar_shown {
+                        checkbox_status_bar = "☑ Status bar";
+                    }
+                    let mut checkbox_sharpen = "☐ Sharpen when shrinking";
+                    if scaling::is_sharpening() {
+                        checkbox_sharpen = "☑ Sharpen when shrinking";
+                    }
+                    let mut checkbox_lock_view = "☐ Lock view";
+                    if view_lock.is_locked {
+                        checkbox_lock_view = "☑ Lock view";
+                    }
+                    let mut checkbox_always_on_top = "☐ Always on top";
+                    if is_always_on_top {
+                        checkbox_always_on_top = "☑ Always on top";
+                    }
+                    // The filters end when a different directory is opened
+                    let active_analysis_filter = analysis_filter.filter(|_| analysis_worker.as_ref().is_some_and(|worker| worker.directory == current_directory));
+                    let mut checkbox_blurry_only = "☐ Blurry only";
+                    if active_analysis_filter == Some(AnalysisView::BlurryOnly) {
+                        checkbox_blurry_only = "☑ Blurry only";
+                    }
+                    let mut checkbox_problem_exposures = "☐ Problem exposures";
+                    if active_analysis_filter == Some(AnalysisView::ProblemExposures) {
+                        checkbox_problem_exposures = "☑ Problem exposures";
+                    }
+                    let mut checkbox_duplicates = "☐ Duplicates";
+                    if active_analysis_filter == Some(AnalysisView::Duplicates) {
+                        checkbox_duplicates = "☑ Duplicates";
+                    }
+                    let mut checkbox_align = "☐ Align when blinking";
+                    if is_aligning {
+                        checkbox_align = "☑ Align when blinking";
+                    }
+                    let recent_labels: Vec<String> = config::load_recent().iter()
+                        .map(|path| format!("Recent: {}", path.display()))
+                        .collect();
+                    let mut menu_labels = vec![checkbox_fullscreen, checkbox_scale_to_fit, checkbox_randomize, checkbox_shuffle, checkbox_raw_only, checkbox_jpeg_only, checkbox_sync_view, checkbox_lock_view, checkbox_always_on_top, checkbox_toolbar, checkbox_status_bar, checkbox_sharpen, "New window", "Copy file path", "Show in file manager", "Edit with external editor", "Rename...", "Copy to...", "Move to...", "Removed files...", "Capture screen region", "Capture window...", "Acquire from scanner...", "Camera live view", "Extract color palette...", "Image statistics", "Gray levels window...", "Hillshade relief...", "Tiling preview", "Tiling preview with seams", "Copy visible region", "Append visible region to CSV", "Export tiles...", "Export animation frames...", "Prepare for sharing...", "Preview sprite animation...", "Test patterns", "Open folder...", checkbox_align, "Onion skin with next image", "Focus stack preview...", "HDR merge preview", "Panorama preview", "Find similar", "Sort by sharpness", checkbox_blurry_only, checkbox_problem_exposures, checkbox_duplicates];
+                    // Display-only upscalers for pixel art, applied to the current image until the next one is loaded
+                    let scaler_labels: Vec<String> = pixelart::PixelArtScaler::ALL.iter()
+                        .map(|scaler| format!("Pixel art: {}", scaler.name()))
+                        .collect();
+                    menu_labels.extend(scaler_labels.iter().map(|label| label.as_str()));
+                    let monitor_labels: Vec<String> = (1..=app::screen_count())
+                        .filter(|_| app::screen_count() > 1)
+                        .map(|monitor| format!("Fullscreen on monitor {}", monitor))
+                        .collect();
+                    menu_labels.extend(monitor_labels.iter().map(|label| label.as_str()));
+                    let mut target_labels: Vec<String> = target::PRESETS.iter().map(|(name, _)| format!("Target: {}", name)).collect();
+                    target_labels.push("Target: custom size...".to_string());
+                    if output_target.is_some() {
+                        target_labels.push("Target: none".to_string());
+                    }
+                    menu_labels.extend(target_labels.iter().map(|label| label.as_str()));
+                    // Normal maps are recognized by their file name, or marked by hand
+                    let current_file = &image_files[image_order[current_index]];
+                    let is_normal_map = normal_map_marks.get(current_file).copied().unwrap_or_else(|| normalmap::is_normal_map_name(current_file));
+                    menu_labels.push(if is_normal_map { "☑ Normal map" } else { "☐ Normal map" });
+                    let normal_map_labels: Vec<String> = normalmap::NormalMapView::ALL.iter()
+                        .filter(|_| is_normal_map)
+                        .map(|view| format!("Normal map: {}", view.name()))
+                        .collect();
+                    menu_labels.extend(normal_map_labels.iter().map(|label| label.as_str()));
+                    #[cfg(feature = "tagging")]
+                    menu_labels.push("Suggest tags");
+                    #[cfg(feature = "webcam")]
+                    menu_labels.push("Webcam snapshot");
+                    menu_labels.extend(recent_labels.iter().map(|label| label.as_str()));
+                    let popup_menu = fltk::menu::MenuItem::new(&menu_labels);
                     match popup_menu.popup(coords.0, coords.1) {
                         None => log::debug!("No menu item selected."),
                         Some(val) => {
@@ -502,9 +2835,18 @@ fn main() -> Result<(), Box<dyn Error>> {
                             if label.ends_with("Scale to fit") {
                                 is_scaled_to_fit = !is_scaled_to_fit;
                                 log::debug!("{}", format!("Toggling image scaling to fit the screen: {}", is_scaled_to_fit).as_str());
-                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
                             }
                             // If label ends with "Fullscreen", toggle fullscreen
+                            else if let Some(monitor) = label.strip_prefix("Fullscreen on monitor ").and_then(|number| number.parse::<i32>().ok()) {
+                                match set_fullscreen_monitor(&mut wind, monitor - 1) {
+                                    Ok(()) => {
+                                        is_fullscreen = true;
+                                        wind.fullscreen(true);
+                                    }
+                                    Err(err) => log::error!("{}", err),
+                                }
+                            }
                             else if label.ends_with("Fullscreen") {
                                 is_fullscreen = !is_fullscreen;
                                 wind.fullscreen(is_fullscreen);
@@ -517,6 +2859,288 @@ fn main() -> Result<(), Box<dyn Error>> {
                                     order_random(&mut image_order, &mut current_index, &mut is_randomized);
                                 }
                             }
+                            else if label.ends_with("RAW only") || label.ends_with("JPEG only") {
+                                let selected_filter = if label.ends_with("RAW only") { FileTypeFilter::RawOnly } else { FileTypeFilter::JpegOnly };
+                                // Selecting the active filter again switches back to showing all images
+                                let new_filter = if file_type_filter == selected_filter { FileTypeFilter::All } else { selected_filter };
+                                match apply_filter(&image_files, &mut image_order, &mut current_index, is_randomized, new_filter) {
+                                    Ok(needs_reload) => {
+                                        file_type_filter = new_filter;
+                                        if needs_reload {
+                                            load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                                        }
+                                    },
+                                    Err(err) => log::warn!("{}", err),
+                                }
+                            }
+                            else if label.ends_with("Sync view") {
+                                toggle_view_sync(&mut view_sync, &mut sync_poll_timeout);
+                            }
+                            else if label.ends_with("Shuffle without repeats") {
+                                shuffle = if shuffle.is_some() { None } else { Some(Shuffle::default()) };
+                            }
+                            else if label.ends_with("Toolbar") {
+                                toolbar.is_enabled = !toolbar.is_enabled;
+                            }
+                            else if label.ends_with("Status bar") {
+                                is_status_bar_shown = toggle_status_bar(is_status_bar_shown, &overlay, &mut wind);
+                            }
+                            else if label.ends_with("Sharpen when shrinking") {
+                                toggle_sharpening(&original_image, &mut frame, zoom_factor);
+                            }
+                            else if label.ends_with("Lock view") {
+                                view_lock.toggle();
+                            }
+                            else if label.ends_with("Always on top") {
+                                match set_always_on_top(&wind, !is_always_on_top) {
+                                    Ok(()) => is_always_on_top = !is_always_on_top,
+                                    Err(err) => dialog::alert_default(&err),
+                                }
+                            }
+                            else if label == "New window" {
+                                if let Err(err) = open_new_window(&image_files[image_order[current_index]]) {
+                                    log::error!("{}", err);
+                                }
+                            }
+                            else if label == "Copy file path" {
+                                if let Err(err) = copy_text_to_clipboard(&image_files[image_order[current_index]].to_string_lossy()) {
+                                    log::error!("{}", err);
+                                }
+                            }
+                            else if label == "Capture screen region" {
+                                match capture_screen(&mut wind).and_then(|capture| rgba_to_shared_image(&capture).map(|image| (capture, image))) {
+                                    Ok((capture, image)) => {
+                                        display_image(ImageType::Shared(image), &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                                        screen_capture = Some(capture);
+                                    },
+                                    Err(err) => dialog::alert_default(&err),
+                                }
+                            }
+                            else if label == "Rename..." {
+                                rename_interactive(&mut image_files, image_order[current_index]);
+                            }
+                            else if label == "Capture window..." {
+                                if let Some(capture) = capture_window_interactive(coords.0, coords.1) {
+                                    match rgba_to_shared_image(&capture) {
+                                        Ok(image) => display_image(ImageType::Shared(image), &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit),
+                                        Err(err) => log::error!("Failed to show the captured window: {}", err),
+                                    }
+                                }
+                            }
+                            else if label == "Acquire from scanner..." {
+                                // The scan is opened like a file, so it can be moved or copied to its final place
+                                match scanner::acquire().and_then(|scan| open_path(&scan, &mut image_files, &mut image_order, &mut current_index, &mut current_directory, &mut directory_watcher, is_randomized, &mut file_type_filter)) {
+                                    Ok(()) => load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader),
+                                    Err(err) => dialog::alert_default(&format!("Scanning failed: {}", err)),
+                                }
+                            }
+                            else if label == "Camera live view" {
+                                match LiveView::start(&frame, wind.width(), wind.height()) {
+                                    Ok(view) => live_view = Some(view),
+                                    Err(err) => dialog::alert_default(&err),
+                                }
+                            }
+                            else if label == "Extract color palette..." {
+                                show_palette_interactive(&original_image);
+                            }
+                            else if label == "Image statistics" {
+                                match image_to_rgba(&original_image).map(|image| statistics::ImageStatistics::compute(&image)) {
+                                    Ok(Some(statistics)) => dialog::message_default(&statistics.describe()),
+                                    Ok(None) => dialog::alert_default("The image is completely transparent"),
+                                    Err(err) => dialog::alert_default(&err),
+                                }
+                            }
+                            else if label == "Copy to..." {
+                                transfer_file_interactive(&image_files[image_order[current_index]], false, config.confirm.overwrite);
+                            }
+                            else if label == "Move to..." {
+                                if let Some(target) = transfer_file_interactive(&image_files[image_order[current_index]], true, config.confirm.overwrite) {
+                                    removed_files.borrow_mut().push(RemovedFile::moved(&image_files[image_order[current_index]], target));
+                                    remove_current_image(&mut image_files, &mut image_order, &mut current_index);
+                                    if image_files.is_empty() {
+                                        app.quit();
+                                    } else {
+                                        if image_order.is_empty() {
+                                            // The last image matching the filter is gone, show everything again
+                                            file_type_filter = FileTypeFilter::All;
+                                            let _ = apply_filter(&image_files, &mut image_order, &mut current_index, is_randomized, file_type_filter);
+                                        }
+                                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                                    }
+                                }
+                            }
+                            else if label == "Removed files..." {
+                                removed::show_panel(Rc::clone(&removed_files));
+                            }
+                            else if label == "Edit with external editor" {
+                                if let Err(err) = open_in_editor(&config.editor, &image_files[image_order[current_index]]) {
+                                    dialog::alert_default(&err);
+                                }
+                            }
+                            else if label == "Show in file manager" {
+                                if let Err(err) = reveal_in_file_manager(&image_files[image_order[current_index]]) {
+                                    dialog::alert_default(&err);
+                                }
+                            }
+                            else if label == "Gray levels window..." {
+                                if let Some(windowed) = gray16_window_interactive(&image_files[image_order[current_index]]) {
+                                    display_image(windowed, &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                                }
+                            }
+                            else if label == "Hillshade relief..." {
+                                if let Some(shaded) = hillshade_interactive(&original_image, &image_files[image_order[current_index]]) {
+                                    display_image(shaded, &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                                }
+                            }
+                            else if label == "Tiling preview" || label == "Tiling preview with seams" {
+                                // Large textures are reduced to the preview size first, nine copies of them would use a lot of memory
+                                let result = image_to_rgba(&original_image)
+                                    .map(|image| downscale_to_preview(&image))
+                                    .and_then(|image| rgba_to_shared_image(&tiling::tile(&image, label == "Tiling preview with seams")));
+                                match result {
+                                    Ok(tiled) => display_image(ImageType::Shared(tiled), &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit),
+                                    Err(err) => log::error!("Failed to show the tiling preview: {}", err),
+                                }
+                            }
+                            else if label == "Copy visible region" {
+                                copy_visible_region(&original_image, &frame, &wind);
+                            }
+                            else if label == "Append visible region to CSV" {
+                                if let Some(region) = visible_region(&original_image, &frame, &wind) {
+                                    match append_region_to_csv(&image_files[image_order[current_index]], region) {
+                                        Ok(csv_path) => log::debug!("Region {:?} appended to {}", region, csv_path.display()),
+                                        Err(err) => log::error!("{}", err),
+                                    }
+                                }
+                            }
+                            else if label == "Export tiles..." {
+                                export_tiles_interactive(&original_image, &image_files[image_order[current_index]], config.confirm.overwrite);
+                            }
+                            else if label == "Export animation frames..." {
+                                export_frames_interactive(&image_files[image_order[current_index]], config.confirm.overwrite);
+                            }
+                            else if label == "Prepare for sharing..." {
+                                prepare_for_sharing_interactive(&image_files, &image_order, current_index, config.confirm.overwrite);
+                            }
+                            else if label == "Preview sprite animation..." {
+                                frame_animation = start_sprite_animation_interactive(&original_image, &frame, &wind);
+                            }
+                            else if label.ends_with("Align when blinking") {
+                                is_aligning = !is_aligning;
+                            }
+                            else if label == "Onion skin with next image" {
+                                show_onion_skin_interactive(&image_files, &image_order, current_index, is_aligning, &mut wind);
+                            }
+                            else if label == "Focus stack preview..." {
+                                if let Some(stacked) = focus_stack_interactive(&image_files, &image_order, current_index, &mut wind) {
+                                    display_image(stacked, &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                                }
+                            }
+                            else if label == "HDR merge preview" {
+                                if let Some(merged) = hdr_merge_interactive(&image_files, &image_order, current_index, &mut wind) {
+                                    display_image(merged, &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                                }
+                            }
+                            else if label == "Panorama preview" {
+                                if let Some(stitched) = panorama_preview_interactive(&image_files, &image_order, current_index, &mut wind) {
+                                    display_image(stitched, &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                                }
+                            }
+                            else if label == "Find similar" {
+                                if let Err(err) = order_by_similarity(&image_files, &mut image_order, &mut current_index, &mut is_randomized, &mut wind) {
+                                    dialog::alert_default(&format!("Similarity search failed: {}", err));
+                                }
+                            }
+                            else if label == "Sort by sharpness" || label == "☐ Blurry only" || label == "☐ Problem exposures" || label == "☐ Duplicates" {
+                                let view = match label.as_str() {
+                                    "Sort by sharpness" => AnalysisView::SortBySharpness,
+                                    "☐ Blurry only" => AnalysisView::BlurryOnly,
+                                    "☐ Problem exposures" => AnalysisView::ProblemExposures,
+                                    _ => AnalysisView::Duplicates,
+                                };
+                                ensure_analysis_worker(&mut analysis_worker, &current_directory, &image_files);
+                                if let Some(worker) = analysis_worker.as_ref().filter(|worker| worker.is_finished()) {
+                                    match apply_analysis_view(view, worker, &image_files, &mut image_order, &mut current_index, &mut is_randomized, file_type_filter) {
+                                        Ok(needs_reload) => {
+                                            if view != AnalysisView::SortBySharpness {
+                                                analysis_filter = Some(view);
+                                            }
+                                            if needs_reload {
+                                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                                            }
+                                        },
+                                        Err(err) => dialog::alert_default(&err),
+                                    }
+                                } else if let Some(worker) = &analysis_worker {
+                                    let (analyzed, total) = worker.progress();
+                                    dialog::alert_default(&format!("Analyzing images in the background ({} of {} done), the list is updated when it's finished", analyzed, total));
+                                    pending_analysis_view = Some(view);
+                                }
+                            }
+                            else if label == "☑ Blurry only" || label == "☑ Problem exposures" || label == "☑ Duplicates" {
+                                // Rebuild the list from the file type filter to show everything again
+                                analysis_filter = None;
+                                pending_analysis_view = None;
+                                if let Ok(true) = apply_filter(&image_files, &mut image_order, &mut current_index, is_randomized, file_type_filter) {
+                                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                                }
+                            }
+                            else if label == "Webcam snapshot" {
+                                // Saved into the current directory and shown at 100% to check the focus
+                                #[cfg(feature = "webcam")]
+                                match webcam::snapshot(&current_directory).and_then(|snapshot| open_path(&snapshot, &mut image_files, &mut image_order, &mut current_index, &mut current_directory, &mut directory_watcher, is_randomized, &mut file_type_filter)) {
+                                    Ok(()) => load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, false, &rotations, &mut view_lock, &mut loader),
+                                    Err(err) => dialog::alert_default(&err),
+                                }
+                            }
+                            else if let Some(scaler) = pixelart::PixelArtScaler::ALL.iter().find(|scaler| label == format!("Pixel art: {}", scaler.name())) {
+                                match image_to_rgba(&original_image).and_then(|image| rgba_to_shared_image(&scaler.scale(&image))) {
+                                    Ok(scaled) => display_image(ImageType::Shared(scaled), &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit),
+                                    Err(err) => log::error!("Failed to scale the image: {}", err),
+                                }
+                            }
+                            else if label.starts_with("Target: ") {
+                                output_target = match label.as_str() {
+                                    "Target: none" => None,
+                                    "Target: custom size..." => dialog::input_default("Target size in pixels (e.g. 1200x628):", "1200x628")
+                                        .and_then(|text| Target::parse(&text))
+                                        .or(output_target),
+                                    _ => target::PRESETS.iter().find(|(name, _)| label == format!("Target: {}", name)).map(|(_, target)| *target),
+                                };
+                                overlay.borrow_mut().target_frame = output_target.and_then(|target| target_frame(target, &original_image, &frame));
+                                wind.redraw();
+                            }
+                            else if label.ends_with("Normal map") {
+                                normal_map_marks.insert(image_files[image_order[current_index]].clone(), label.starts_with("☐"));
+                            }
+                            else if let Some(view) = normalmap::NormalMapView::ALL.iter().find(|view| label == format!("Normal map: {}", view.name())) {
+                                match image_to_rgba(&original_image).and_then(|image| rgba_to_shared_image(&view.render(&image))) {
+                                    Ok(rendered) => display_image(ImageType::Shared(rendered), &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit),
+                                    Err(err) => log::error!("Failed to render the normal map: {}", err),
+                                }
+                            }
+                            else if label == "Suggest tags" {
+                                #[cfg(feature = "tagging")]
+                                suggest_tags_interactive(&mut tagger, &original_image);
+                            }
+                            else if label == "Test patterns" {
+                                open_test_patterns(&mut image_files, &mut image_order, &mut current_index, &mut current_directory, &mut directory_watcher, &mut file_type_filter);
+                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                            }
+                            else if label == "Open folder..." {
+                                if let Some(directory) = choose_directory("Open folder") {
+                                    match open_path(&directory, &mut image_files, &mut image_order, &mut current_index, &mut current_directory, &mut directory_watcher, is_randomized, &mut file_type_filter) {
+                                        Ok(()) => load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader),
+                                        Err(err) => dialog::alert_default(&format!("Failed to open folder: {}", err)),
+                                    }
+                                }
+                            }
+                            else if let Some(recent_path) = label.strip_prefix("Recent: ") {
+                                match open_path(Path::new(recent_path), &mut image_files, &mut image_order, &mut current_index, &mut current_directory, &mut directory_watcher, is_randomized, &mut file_type_filter) {
+                                    Ok(()) => load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader),
+                                    Err(err) => dialog::alert_default(&format!("Failed to open {}: {}", recent_path, err)),
+                                }
+                            }
                             log::debug!("Menu item selected: {:?}", val.label());
                         }
                     }
@@ -524,118 +3148,583 @@ fn main() -> Result<(), Box<dyn Error>> {
                 true
             }
             Event::Drag => {
-                if let Some((start_x, start_y)) = pan_origin {
+                if gesture_origin.is_some() {
+                    // Gestures are only evaluated when the button is released
+                    true
+                } else if let Some((start_x, start_y)) = capture_start.or(zoom_box_start) {
+                    let (x, y) = (app::event_x(), app::event_y());
+                    overlay.borrow_mut().selection = Some((start_x.min(x), start_y.min(y), (x - start_x).abs(), (y - start_y).abs()));
+                    wind.redraw();
+                    true
+                } else if is_measuring {
+                    if let Some(start) = measure_start {
+                        let end = (app::event_x(), app::event_y());
+                        let image_start = window_to_image_coords(&original_image, &frame, start.0, start.1);
+                        let image_end = window_to_image_coords(&original_image, &frame, end.0, end.1);
+                        if let (Some(image_start), Some(image_end)) = (image_start, image_end) {
+                            let measurement = Measurement { start: image_start, end: image_end };
+                            overlay.borrow_mut().measure_line = Some(MeasureLine { start, end, label: measurement.describe(measure_scale) });
+                            wind.redraw();
+                        }
+                    }
+                    true
+                } else if let Some((start_x, start_y)) = pan_origin {
                     let dx = app::event_x() - start_x;
                     let dy = app::event_y() - start_y;
                     frame.set_pos(frame.x() + dx, frame.y() + dy);
                     pan_origin = Some((app::event_x(), app::event_y()));
+                    glide.track(dx, dy);
+                    if let Some(sync) = &view_sync {
+                        sync.broadcast(ViewState { zoom_factor, x: frame.x(), y: frame.y() });
+                    }
                     wind.redraw();
                     true
                 } else {
                     false
                 }
             }
-            Event::KeyDown => {
+            Event::KeyUp => {
+                scrub.key_up();
+                // Load the image the key was let go at, the ones passed while it was held only got placeholders
+                if scrub.settle() && !image_order.is_empty() {
+                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                }
+                false
+            }
+            // Toolbar buttons do the same as the keys of their actions
+            _ if event == Event::KeyDown || event.bits() == TOOLBAR_EVENT => {
+                let toolbar_action = toolbar.take_action();
+                if event != Event::KeyDown && toolbar_action.is_none() {
+                    return true;
+                }
+                glide.stop();
                 let key = app::event_key();
+                let is_key_repeat = toolbar_action.is_none() && scrub.key_down(key);
+                if overlay.borrow_mut().measure_line.take().is_some() {
+                    wind.redraw();
+                }
+                if toolbar_action.is_none() && live_view.is_some() && (key == Key::Enter || key == Key::from_char(' ')) {
+                    // Save the frame into the current directory, the directory watcher then adds it to the list
+                    if let Some(Err(err)) = live_view.as_ref().map(|view| view.save_frame(&current_directory)) {
+                        dialog::alert_default(&err);
+                    }
+                    return true;
+                }
+                if frame_animation.take().is_some() || screen_capture.take().is_some() || live_view.take().is_some() {
+                    // Stop the sprite preview, blink comparison or region capture and go back to showing the current image
+                    capture_start = None;
+                    overlay.borrow_mut().selection = None;
+                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                    return true;
+                }
 
                 if image_files.is_empty() {                            
                     app.quit();
                 }
-                match key {
-                    fltk::enums::Key::Left => {
-                        current_index = (current_index + image_files.len() - 1) % image_files.len();
+                match toolbar_action.or_else(|| keymap.action_for(key, app::event_state())) {
+                    Some(Action::Previous) => {
+                        current_index = (current_index + image_order.len() - 1) % image_order.len();
                         log::debug!("Loading previous image: {}", image_files[image_order[current_index]].display());
-                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                        if is_key_repeat {
+                            show_scrub_placeholder(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, &rotations, &mut loader);
+                            scrub.skip();
+                        } else {
+                            load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                        }
                     }
-                    fltk::enums::Key::Right => {
-                        current_index = (current_index + 1) % image_files.len();
+                    Some(Action::Next) => {
+                        current_index = next_index(current_index, &image_files, &image_order, &mut shuffle);
                         log::debug!("Loading next image: {}", image_files[image_order[current_index]].display());
-                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                        if is_key_repeat {
+                            show_scrub_placeholder(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, &rotations, &mut loader);
+                            scrub.skip();
+                        } else {
+                            load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                        }
                     }
-                    fltk::enums::Key::Home => {
+                    Some(Action::First) => {
                         current_index = 0;
                         log::debug!("Loading first image: {}", image_files[image_order[current_index]].display());
-                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
                     }
-                    fltk::enums::Key::End => {
-                        current_index = image_files.len() - 1;
+                    Some(Action::Last) => {
+                        current_index = image_order.len() - 1;
                         log::debug!("Loading last image: {}", image_files[image_order[current_index]].display());
-                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
                     }
-                    fltk::enums::Key::Enter => {
+                    Some(Action::ToggleScaleToFit) => {
                         is_scaled_to_fit = !is_scaled_to_fit;
                         log::debug!("{}", format!("Toggling image scaling to fit the screen: {}", is_scaled_to_fit).as_str());
-                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
                     }
-                    fltk::enums::Key::Delete => {
-                        if dialog::choice2(wind.width()/2 - 200, wind.height()/2 - 100, format!("Do you want to delete {}?", image_files[image_order[current_index]].display()).as_str(), "Cancel", "Delete", "") == Some(1) {
+                    Some(Action::Delete) => {
+                        if !config.confirm.delete || dialog::choice2(wind.width()/2 - 200, wind.height()/2 - 100, format!("Move {} to the recycle bin?", image_files[image_order[current_index]].display()).as_str(), "Cancel", "Move to recycle bin", "") == Some(1) {
                             log::debug!("Delete image: {}", image_files[image_order[current_index]].display());
-                            if let Err(err) = fs::remove_file(&image_files[image_order[current_index]]) {
-                                println!("Failed to delete image: {}", err);
+                            if let Err(err) = removed::move_to_trash(&image_files[image_order[current_index]]) {
+                                println!("Failed to move image to the recycle bin: {}", err);
                             } else {
-                                image_files.remove(image_order[current_index]);
+                                removed_files.borrow_mut().push(RemovedFile::trashed(&image_files[image_order[current_index]]));
+                                remove_current_image(&mut image_files, &mut image_order, &mut current_index);
                                 if image_files.is_empty() {
                                     app.quit();
                                 } else {
-                                    current_index = current_index % image_files.len();
-                                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                                    if image_order.is_empty() {
+                                        // The last image matching the filter is gone, show everything again
+                                        file_type_filter = FileTypeFilter::All;
+                                        let _ = apply_filter(&image_files, &mut image_order, &mut current_index, is_randomized, file_type_filter);
+                                    }
+                                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
                                 }
                             }
                         } else {
                             log::debug!("Delete cancelled");
                         };
                     }
-                    fltk::enums::Key::Escape => {
+                    Some(Action::Quit) => {
                         app.quit();
                     }
-                    KEY_C => {
-                        let eventstate = app::event_state();
-                        //Check if the Control key was held down when the 'C' key was pressed
-                        if eventstate.contains(fltk::enums::Shortcut::Ctrl) {
-                            let clipboard = Arc::new(Mutex::new(Clipboard::new()));
-                            match Arc::clone(&clipboard).lock() {
-                                Ok(mut clipboard_lock) => {
-                                    let mut clipboard = clipboard_lock.as_mut().unwrap();
-                                    log::debug!("Copy image to clipboard");
-                                    match copy_to_clipboard(&mut original_image, &mut clipboard) {
-                                        Ok(_) => {
-                                            log::debug!("Image copied to clipboard");
-                                        },
-                                        Err(err) => {
-                                            log::error!("Failed to copy image to clipboard: {}", err);
-                                        }
+                    Some(Action::OpenFolder) => {
+                        if let Some(directory) = choose_directory("Open folder") {
+                            match open_path(&directory, &mut image_files, &mut image_order, &mut current_index, &mut current_directory, &mut directory_watcher, is_randomized, &mut file_type_filter) {
+                                Ok(()) => load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader),
+                                Err(err) => dialog::alert_default(&format!("Failed to open folder: {}", err)),
+                            }
+                        }
+                    }
+                    Some(Action::CopyImage) => {
+                        let clipboard = Arc::new(Mutex::new(Clipboard::new()));
+                        match Arc::clone(&clipboard).lock() {
+                            Ok(mut clipboard_lock) => {
+                                let mut clipboard = clipboard_lock.as_mut().unwrap();
+                                log::debug!("Copy image to clipboard");
+                                match copy_to_clipboard(&mut original_image, &mut clipboard) {
+                                    Ok(_) => {
+                                        log::debug!("Image copied to clipboard");
+                                    },
+                                    Err(err) => {
+                                        log::error!("Failed to copy image to clipboard: {}", err);
+                                    }
+                                }
+                            },
+                            Err(err) => {
+                                log::error!("Failed to initialize clipboard: {}", err);
+                            }
+                        }
+                    }
+                    Some(Action::BlinkCompare) => { // Blink compare with the next image, pressing any key stops it
+                        let next_index = (current_index + 1) % image_order.len();
+                        match start_blink_comparison(&original_image, &image_files[image_order[next_index]], is_aligning, &frame, &mut wind) {
+                            Ok(animation) => frame_animation = Some(animation),
+                            Err(err) => log::error!("Failed to start blink comparison: {}", err),
+                        }
+                    }
+                    Some(Action::ToggleFullscreen) => {
+                        //Toggle fullscreen
+                        wind.make_resizable(true);
+                        is_fullscreen = !is_fullscreen;
+                        wind.fullscreen(is_fullscreen);
+                    }
+                    Some(Action::ToggleAlwaysOnTop) => {
+                        match set_always_on_top(&wind, !is_always_on_top) {
+                            Ok(()) => is_always_on_top = !is_always_on_top,
+                            Err(err) => log::error!("{}", err),
+                        }
+                    }
+                    Some(Action::ToggleShuffle) => {
+                        shuffle = if shuffle.is_some() { None } else { Some(Shuffle::default()) };
+                        log::debug!("Shuffle: {}", shuffle.is_some());
+                    }
+                    Some(direction @ (Action::Back | Action::Forward)) => {
+                        let position = if direction == Action::Back {
+                            history.back(&image_files, &image_order)
+                        } else {
+                            history.forward(&image_files, &image_order)
+                        };
+                        if let Some(position) = position {
+                            current_index = position;
+                            load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                        }
+                    }
+                    Some(Action::ToggleLockView) => {
+                        view_lock.toggle();
+                    }
+                    Some(nudge @ (Action::NudgeLeft | Action::NudgeRight | Action::NudgeUp | Action::NudgeDown)) => {
+                        // Only while locked, otherwise the nudge would be lost on the next image anyway
+                        if view_lock.is_locked {
+                            let (dx, dy) = match nudge {
+                                Action::NudgeLeft => (-1, 0),
+                                Action::NudgeRight => (1, 0),
+                                Action::NudgeUp => (0, -1),
+                                _ => (0, 1),
+                            };
+                            let (old_x, old_y) = nudge_offset(view_lock.current_nudge(), &original_image, &frame);
+                            let new_nudge = view_lock.nudge(&image_files[image_order[current_index]], dx, dy);
+                            let (new_x, new_y) = nudge_offset(new_nudge, &original_image, &frame);
+                            frame.set_pos(frame.x() - old_x + new_x, frame.y() - old_y + new_y);
+                            wind.redraw();
+                        }
+                    }
+                    Some(rotate @ (Action::RotateClockwise | Action::RotateCounterClockwise)) => {
+                        rotations.rotate(&image_files[image_order[current_index]], rotate == Action::RotateClockwise);
+                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                    }
+                    Some(Action::RandomOrder) => { //Randomize the sequence of images in the directory when viewing the next/prev image
+                        order_random(&mut image_order, &mut current_index, &mut is_randomized);
+                    }
+                    Some(Action::ToggleMeasure) => { // Toggle measure mode, dragging then measures distances and angles
+                        is_measuring = !is_measuring;
+                        measure_start = None;
+                        log::debug!("Measure mode: {}", is_measuring);
+                    }
+                    Some(Action::SortByName) => { // Sort images by name when viewing the next/prev image
+                        order_by_name(&mut image_order, &mut current_index, &mut is_randomized);
+                    }
+                    Some(Action::ToggleSyncView) => { // Synchronize zoom and pan with other instances
+                        toggle_view_sync(&mut view_sync, &mut sync_poll_timeout);
+                    }
+                    Some(Action::CopyVisibleRegion) => { // Copy the visible region's pixel coordinates
+                        copy_visible_region(&original_image, &frame, &wind);
+                    }
+                    Some(Action::CopyPath) => { // Copy the full path of the current image as text
+                        if let Err(err) = copy_text_to_clipboard(&image_files[image_order[current_index]].to_string_lossy()) {
+                            log::error!("{}", err);
+                        }
+                    }
+                    Some(Action::CaptureRegion) => { // Show a screenshot to select a region from, pressing any key cancels
+                        match capture_screen(&mut wind).and_then(|capture| rgba_to_shared_image(&capture).map(|image| (capture, image))) {
+                            Ok((capture, image)) => {
+                                display_image(ImageType::Shared(image), &mut original_image, &mut frame, &mut wind, &mut zoom_factor, is_fullscreen, is_scaled_to_fit);
+                                screen_capture = Some(capture);
+                            },
+                            Err(err) => dialog::alert_default(&err),
+                        }
+                    }
+                    Some(Action::NewWindow) => {
+                        if let Err(err) = open_new_window(&image_files[image_order[current_index]]) {
+                            log::error!("{}", err);
+                        }
+                    }
+                    Some(Action::Rename) => {
+                        rename_interactive(&mut image_files, image_order[current_index]);
+                    }
+                    Some(Action::CopyTo) => {
+                        transfer_file_interactive(&image_files[image_order[current_index]], false, config.confirm.overwrite);
+                    }
+                    Some(Action::MoveTo) => {
+                        if let Some(target) = transfer_file_interactive(&image_files[image_order[current_index]], true, config.confirm.overwrite) {
+                            removed_files.borrow_mut().push(RemovedFile::moved(&image_files[image_order[current_index]], target));
+                            remove_current_image(&mut image_files, &mut image_order, &mut current_index);
+                            if image_files.is_empty() {
+                                app.quit();
+                            } else {
+                                if image_order.is_empty() {
+                                    // The last image matching the filter is gone, show everything again
+                                    file_type_filter = FileTypeFilter::All;
+                                    let _ = apply_filter(&image_files, &mut image_order, &mut current_index, is_randomized, file_type_filter);
+                                }
+                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                            }
+                        }
+                    }
+                    Some(Action::EditExternally) => {
+                        if let Err(err) = open_in_editor(&config.editor, &image_files[image_order[current_index]]) {
+                            dialog::alert_default(&err);
+                        }
+                    }
+                    Some(Action::ToggleSlideshow) => {
+                        if let Some(handle) = slideshow.take() {
+                            app::remove_timeout3(handle);
+                        } else {
+                            slideshow = Some(start_slideshow(slideshow_interval));
+                        }
+                        log::debug!("Slideshow: {}", slideshow.is_some());
+                    }
+                    Some(Action::ToggleStatusBar) => {
+                        is_status_bar_shown = toggle_status_bar(is_status_bar_shown, &overlay, &mut wind);
+                    }
+                    Some(Action::RandomImage) => {
+                        // Any image but the one being shown
+                        if image_order.len() > 1 {
+                            current_index = (current_index + rand::thread_rng().gen_range(1..image_order.len())) % image_order.len();
+                            log::debug!("Loading random image: {}", image_files[image_order[current_index]].display());
+                            load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                        }
+                    }
+                    Some(Action::JumpTo) => {
+                        if let Some(position) = jump_to_interactive(&image_files, &image_order, "") {
+                            current_index = position;
+                            load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                        }
+                    }
+                    Some(Action::RevealInFileManager) => {
+                        if let Err(err) = reveal_in_file_manager(&image_files[image_order[current_index]]) {
+                            dialog::alert_default(&err);
+                        }
+                    }
+                    Some(Action::CycleScalingFilter) => {
+                        let filter = scaling::filter().next();
+                        scaling::set_filter(filter);
+                        log::debug!("Scaling filter: {}", filter.name());
+                        // Scaled again at the same zoom, so the view stays where it is
+                        set_frame_zoom(&original_image, &mut frame, zoom_factor);
+                        wind.redraw();
+                    }
+                    Some(Action::ToggleSharpening) => {
+                        toggle_sharpening(&original_image, &mut frame, zoom_factor);
+                        wind.redraw();
+                    }
+                    Some(Action::CycleFilter) => { // Cycle through the file type filters, skipping filters without matches
+                        let mut new_filter = file_type_filter.next();
+                        while new_filter != file_type_filter {
+                            match apply_filter(&image_files, &mut image_order, &mut current_index, is_randomized, new_filter) {
+                                Ok(needs_reload) => {
+                                    file_type_filter = new_filter;
+                                    if needs_reload {
+                                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
                                     }
+                                    break;
                                 },
                                 Err(err) => {
-                                    log::error!("Failed to initialize clipboard: {}", err);
+                                    log::debug!("{}", err);
+                                    new_filter = new_filter.next();
                                 }
                             }
                         }
-                        return true;
                     }
-                    _ => {
-                        if let Some(ch) = app::event_text().chars().next() {
-                            if ch.eq_ignore_ascii_case(&'F') {
-                                //Toggle fullscreen
-                                wind.make_resizable(true);
-                                is_fullscreen = !is_fullscreen;
-                                wind.fullscreen(is_fullscreen);
+                    None => {
+                        // Number keys move the image to the folders from the config file, then show the next one.
+                        // Numbers without a folder start a jump to that image number instead.
+                        let digit = key.to_char().filter(|ch| ch.is_ascii_digit());
+                        let destination = digit
+                            .filter(|ch| *ch != '0')
+                            .and_then(|ch| config.move_to.get(&ch.to_string()));
+                        if let (Some(digit), None) = (digit, destination) {
+                            if let Some(position) = jump_to_interactive(&image_files, &image_order, &digit.to_string()) {
+                                current_index = position;
+                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
                             }
-                            if ch.eq_ignore_ascii_case(&'R') { //Randomize the sequence of images in the directory when viewing the next/prev image
-                                order_random(&mut image_order, &mut current_index, &mut is_randomized);
+                        }
+                        let is_confirmed = |destination: &PathBuf| {
+                            !config.confirm.move_to
+                                || dialog::choice2_default(&format!("Move {} to {}?", image_files[image_order[current_index]].display(), destination.display()), "Cancel", "Move", "") == Some(1)
+                        };
+                        if let Some(destination) = destination.filter(|destination| is_confirmed(destination)) {
+                            match transfer_file(&image_files[image_order[current_index]], destination, true, CollisionPolicy::Rename) {
+                                Ok(target) => {
+                                    if let Some(target) = target {
+                                        removed_files.borrow_mut().push(RemovedFile::moved(&image_files[image_order[current_index]], target));
+                                    }
+                                    remove_current_image(&mut image_files, &mut image_order, &mut current_index);
+                                    if image_files.is_empty() {
+                                        app.quit();
+                                    } else {
+                                        if image_order.is_empty() {
+                                            // The last image matching the filter is gone, show everything again
+                                            file_type_filter = FileTypeFilter::All;
+                                            let _ = apply_filter(&image_files, &mut image_order, &mut current_index, is_randomized, file_type_filter);
+                                        }
+                                        load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                                    }
+                                },
+                                Err(err) => dialog::alert_default(&err),
+                            }
+                        }
+                    }
+                }
+                true
+            }
+            Event::DndEnter | Event::DndDrag | Event::DndLeave => true,
+            Event::DndRelease => {
+                is_dnd_pending = true;
+                true
+            }
+            Event::Paste if is_dnd_pending => {
+                is_dnd_pending = false;
+                if let Some(path) = dropped_paths(&app::event_text()).first() {
+                    match open_path(path, &mut image_files, &mut image_order, &mut current_index, &mut current_directory, &mut directory_watcher, is_randomized, &mut file_type_filter) {
+                        Ok(()) => load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader),
+                        Err(err) => log::error!("Failed to open dropped file: {}", err),
+                    }
+                }
+                true
+            }
+            #[cfg(target_os = "macos")]
+            _ if event.bits() == ZOOM_GESTURE_EVENT => {
+                // Zoom around the mouse pointer like the wheel does, but smoothly with the fingers
+                let ratio = 1.0 + app::event_dy_value() as f64 / 1000.0;
+                if ratio > 0.0 {
+                    overlay.borrow_mut().measure_line = None;
+                    zoom_around(&original_image, &mut frame, &mut zoom_factor, ratio, app::event_x(), app::event_y());
+                    if let Some(sync) = &view_sync {
+                        sync.broadcast(ViewState { zoom_factor, x: frame.x(), y: frame.y() });
+                    }
+                    wind.redraw();
+                }
+                true
+            }
+            #[cfg(target_os = "windows")]
+            _ if event.bits() == PINCH_EVENT => {
+                if let Some(pinch) = take_pinch() {
+                    // Ignore the swipe the fingers started, the pinch zooms instead
+                    swipe_origin = None;
+                    zoom_around(&original_image, &mut frame, &mut zoom_factor, pinch.ratio, pinch.x - wind.x_root(), pinch.y - wind.y_root());
+                    if let Some(sync) = &view_sync {
+                        sync.broadcast(ViewState { zoom_factor, x: frame.x(), y: frame.y() });
+                    }
+                    wind.redraw();
+                }
+                true
+            }
+            _ if event.bits() == LOADED_EVENT => {
+                if let Some((path, result)) = loader.take_finished() {
+                    wind.set_cursor(Cursor::Default);
+                    match result.and_then(decoded_to_shared_image) {
+                        Ok(image) => show_loaded_image(ImageType::Shared(image), &mut original_image, &mut frame, &mut wind, &path, &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock),
+                        Err(err) => log::error!("Error loading {}: {}", path.display(), err),
+                    }
+                    Loader::schedule_preload();
+                }
+                true
+            }
+            _ if event.bits() == PRELOAD_EVENT => {
+                // The images before and after the current one, in the order they're browsed
+                let count = config.preload.unwrap_or(DEFAULT_PRELOAD).min(image_order.len() / 2);
+                let length = image_order.len();
+                let neighbors = (1..=count)
+                    .flat_map(|offset| [(current_index + offset) % length, (current_index + length - offset) % length])
+                    .filter_map(|position| {
+                        let path = &image_files[image_order[position]];
+                        Some((path.clone(), preload_decoder(&path.to_string_lossy())?))
+                    })
+                    .collect();
+                loader.preload(neighbors);
+                true
+            }
+            _ if event.bits() == GLIDE_EVENT => {
+                if let Some((dx, dy)) = glide.step() {
+                    frame.set_pos(frame.x() + dx, frame.y() + dy);
+                    if let Some(sync) = &view_sync {
+                        sync.broadcast(ViewState { zoom_factor, x: frame.x(), y: frame.y() });
+                    }
+                    wind.redraw();
+                }
+                true
+            }
+            _ if event.bits() == SLIDESHOW_EVENT => {
+                glide.stop();
+                if !image_order.is_empty() {
+                    current_index = next_index(current_index, &image_files, &image_order, &mut shuffle);
+                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                }
+                true
+            }
+            _ if event.bits() == SCRUB_EVENT => {
+                if scrub.settle() && !image_order.is_empty() {
+                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                }
+                true
+            }
+            _ if event.bits() == DIRECTORY_CHANGED_EVENT => {
+                // Re-read the directory in the background, a scan that is still running is outdated now
+                if !current_directory.as_os_str().is_empty() {
+                    directory_scan = Some(DirectoryScan::start(&current_directory, None, is_supported_image));
+                }
+                true
+            }
+            _ if event.bits() == SCAN_EVENT => {
+                // A scan of a directory that was left in the meantime is dropped
+                if directory_scan.as_ref().is_some_and(|scan| scan.directory != current_directory) {
+                    directory_scan = None;
+                }
+                if let Some((files, is_done)) = directory_scan.as_mut().and_then(|scan| scan.take_update()) {
+                    if is_done {
+                        directory_scan = None;
+                    }
+                    match files {
+                        Ok(files) if files.is_empty() => log::debug!("No images left in {}, keeping the current list", current_directory.display()),
+                        Ok(files) => {
+                            // The analysis filters end when a different directory is opened
+                            if !analysis_worker.as_ref().is_some_and(|worker| worker.directory == current_directory) {
+                                analysis_filter = None;
                             }
-                            if ch.eq_ignore_ascii_case(&'N') { // Sort images by name when viewing the next/prev image
-                                order_by_name(&mut image_order, &mut current_index, &mut is_randomized);
+                            if merge_image_list(files, &mut image_files, &mut image_order, &mut current_index, is_randomized, &mut analysis_filter, &mut file_type_filter) {
+                                load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
                             }
+                        },
+                        Err(err) => log::error!("Failed to read directory: {}", err),
+                    }
+                }
+                true
+            }
+            _ if event.bits() == ANALYSIS_EVENT => {
+                // Apply the sorting or filter that was requested while the analysis was still running
+                if let (Some(view), Some(worker)) = (pending_analysis_view.take(), analysis_worker.as_ref()) {
+                    if worker.directory == current_directory {
+                        match apply_analysis_view(view, worker, &image_files, &mut image_order, &mut current_index, &mut is_randomized, file_type_filter) {
+                            Ok(needs_reload) => {
+                                if view != AnalysisView::SortBySharpness {
+                                    analysis_filter = Some(view);
+                                }
+                                if needs_reload {
+                                    load_and_display_image(&mut original_image, &mut frame, &mut wind, &image_files[image_order[current_index]], &mut zoom_factor, is_fullscreen, is_scaled_to_fit, &rotations, &mut view_lock, &mut loader);
+                                }
+                            },
+                            Err(err) => dialog::alert_default(&err),
                         }
                     }
                 }
                 true
             }
+            _ if event.bits() == SYNC_EVENT => {
+                // Apply the latest zoom/pan state broadcast by another instance
+                if let Some(state) = view_sync.as_ref().and_then(|sync| sync.poll()) {
+                    log::debug!("Received view state: {:?}", state);
+                    zoom_factor = state.zoom_factor;
+                    set_frame_zoom(&original_image, &mut frame, zoom_factor);
+                    frame.set_pos(state.x, state.y);
+                    wind.redraw();
+                }
+                true
+            }
             _ => false,
         }
     });
 
     app.run()?;
+    stdin::remove_file();
+    download::remove_files();
+
+    if let Some(session) = session.borrow().as_ref() {
+        if let Err(err) = config::save_session(session) {
+            log::error!("{}", err);
+        }
+    }
+    if let Some(geometry) = window_geometry.borrow().as_ref() {
+        if let Err(err) = config::save_window_geometry(geometry) {
+            log::error!("{}", err);
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("/home/me/My%20Photos/a%2Bb.jpg"), "/home/me/My Photos/a+b.jpg");
+        assert_eq!(percent_decode("/tmp/caf%C3%A9.png"), "/tmp/café.png");
+        // Anything that isn't a valid escape is kept as it is
+        assert_eq!(percent_decode("100%zz"), "100%zz");
+        assert_eq!(percent_decode("/tmp/a%2"), "/tmp/a%2");
+        assert_eq!(percent_decode("/tmp/b%"), "/tmp/b%");
+    }
+
+    #[test]
+    fn extracts_dropped_paths() {
+        let text = "file:///tmp/a%20b.jpg\r\n\n  /tmp/c.png  \nfile:///tmp/d.tif\n";
+        assert_eq!(dropped_paths(text), [PathBuf::from("/tmp/a b.jpg"), PathBuf::from("/tmp/c.png"), PathBuf::from("/tmp/d.tif")]);
+        assert!(dropped_paths("\n \n").is_empty());
+    }
+}
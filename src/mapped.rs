@@ -0,0 +1,43 @@
+use std::{fs::File, io, path::Path};
+
+use memmap2::Mmap;
+
+// Bytes around the tags of a TIFF based file that are parsed, enough for the first IFDs and their values
+const TIFF_TAG_BYTES: usize = 256 * 1024;
+
+/// Map the file into memory, so only the parts that are actually read get loaded from disk, without
+/// copying them into a buffer first. Decoders that seek around, like TIFF, and hashing of large RAW
+/// files get through big files faster this way.
+pub fn map(path: &Path) -> io::Result<Mmap> {
+    let file = File::open(path)?;
+    // Safety: the mapping is only read. A file truncated by another program while it's mapped can
+    // crash the viewer, the same risk every program reading mapped image files takes.
+    unsafe { Mmap::map(&file) }
+}
+
+/// The tags of a TIFF based file (TIFF and most RAW formats) from its mapped data, without copying
+/// the whole file. The tags are usually near the start, but writers may put the first IFD after the
+/// image data, in which case only the part around it is copied, at its place in an otherwise
+/// untouched zeroed buffer. Tags with values outside of the copied part are left out.
+pub fn read_tiff_tags(data: &[u8]) -> Result<exif::Exif, String> {
+    let ifd_offset = match data.get(..8) {
+        Some([b'I', b'I', _, _, offset @ ..]) => u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize,
+        Some([b'M', b'M', _, _, offset @ ..]) => u32::from_be_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize,
+        _ => return Err("Not a TIFF file".to_string()),
+    };
+    let tags = if ifd_offset < TIFF_TAG_BYTES {
+        data[..data.len().min(TIFF_TAG_BYTES)].to_vec()
+    } else {
+        let start = ifd_offset.saturating_sub(TIFF_TAG_BYTES / 2).min(data.len());
+        let end = data.len().min(ifd_offset + TIFF_TAG_BYTES / 2);
+        let mut window = vec![0u8; end];
+        window[..8].copy_from_slice(&data[..8]);
+        window[start..end].copy_from_slice(&data[start..end]);
+        window
+    };
+    match exif::Reader::new().continue_on_error(true).read_raw(tags) {
+        Ok(exif) => Ok(exif),
+        Err(exif::Error::PartialResult(partial)) => Ok(partial.into_inner().0),
+        Err(err) => Err(format!("Invalid TIFF file: {}", err)),
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::FITS_SUPPORTED_FORMATS;
+
+/// Physical size of one image pixel, if known from the file's metadata
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelScale {
+    pub per_pixel: f64,
+    pub unit: &'static str,
+}
+
+/// A measurement between two points, in image pixel coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Measurement {
+    pub start: (f64, f64),
+    pub end: (f64, f64),
+}
+
+impl Measurement {
+    pub fn distance(&self) -> f64 {
+        let dx = self.end.0 - self.start.0;
+        let dy = self.end.1 - self.start.1;
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    // Angle in degrees, counter-clockwise from the positive x axis like on a protractor
+    pub fn angle(&self) -> f64 {
+        let dx = self.end.0 - self.start.0;
+        let dy = self.end.1 - self.start.1;
+        (-dy).atan2(dx).to_degrees()
+    }
+
+    pub fn describe(&self, scale: Option<PixelScale>) -> String {
+        let distance = self.distance();
+        match scale {
+            Some(scale) => format!("{:.1} px ({:.2} {}), {:.1}°", distance, distance * scale.per_pixel, scale.unit, self.angle()),
+            None => format!("{:.1} px, {:.1}°", distance, self.angle()),
+        }
+    }
+}
+
+/// Read the physical pixel size from FITS plate scale keywords, or the DPI stored in JPEG and PNG files
+pub fn pixel_scale(image_file: &Path) -> Option<PixelScale> {
+    let filename = image_file.to_string_lossy().to_lowercase();
+    if FITS_SUPPORTED_FORMATS.iter().any(|&format| filename.ends_with(format)) {
+        fits_plate_scale(image_file)
+    } else if filename.ends_with("jpg") || filename.ends_with("jpeg") {
+        jfif_pixel_scale(image_file)
+    } else if filename.ends_with("png") {
+        png_pixel_scale(image_file)
+    } else {
+        None
+    }
+}
+
+/// The header of one HDU of a FITS file, its cards as written up to END
+pub struct FitsHeader {
+    pub cards: Vec<String>,
+}
+
+impl FitsHeader {
+    /// The value of the keyword, without the comment and the quotes around string values
+    pub fn value(&self, key: &str) -> Option<String> {
+        let card = self.cards.iter().find(|card| card.get(..8).is_some_and(|keyword| keyword.trim_end() == key) && card.get(8..10) == Some("= "))?;
+        Some(card.get(10..)?.split('/').next()?.trim().trim_matches('\'').trim().to_string())
+    }
+
+    pub fn number(&self, key: &str) -> Option<i64> {
+        self.value(key)?.parse().ok()
+    }
+
+    // Bytes of data following the header, before padding to whole blocks
+    fn data_bytes(&self) -> u64 {
+        let axes = self.number("NAXIS").unwrap_or(0);
+        if axes == 0 {
+            return 0;
+        }
+        let elements: i64 = (1..=axes).map(|axis| self.number(&format!("NAXIS{}", axis)).unwrap_or(0)).product();
+        let bytes_per_element = self.number("BITPIX").unwrap_or(0).unsigned_abs() / 8;
+        bytes_per_element * (self.number("GCOUNT").unwrap_or(1) * (self.number("PCOUNT").unwrap_or(0) + elements)) as u64
+    }
+}
+
+/// Read the headers of the HDUs of a FITS file, the primary one first, skipping the data between them
+pub fn fits_headers(image_file: &Path) -> Result<Vec<FitsHeader>, String> {
+    const BLOCK_SIZE: usize = 2880;
+    const CARD_SIZE: usize = 80;
+
+    let mut file = File::open(image_file).map_err(|err| format!("Failed to open {}: {}", image_file.display(), err))?;
+    let file_size = file.metadata().map_err(|err| err.to_string())?.len();
+    let mut headers = Vec::new();
+    let mut cards = Vec::new();
+    let mut block = [0u8; BLOCK_SIZE];
+    loop {
+        if file.read_exact(&mut block).is_err() {
+            // The end of the file is only expected between HDUs
+            if cards.is_empty() && !headers.is_empty() {
+                return Ok(headers);
+            }
+            return Err(format!("Truncated FITS header in {}", image_file.display()));
+        }
+        for card in block.chunks(CARD_SIZE) {
+            let card = String::from_utf8_lossy(card).trim_end().to_string();
+            if card == "END" {
+                let header = FitsHeader { cards: std::mem::take(&mut cards) };
+                let padded_bytes = header.data_bytes().div_ceil(BLOCK_SIZE as u64) * BLOCK_SIZE as u64;
+                headers.push(header);
+                let position = file.seek(SeekFrom::Current(padded_bytes as i64)).map_err(|err| err.to_string())?;
+                if position >= file_size {
+                    return Ok(headers);
+                }
+                // Cards after END in the same block are padding
+                break;
+            }
+            if !card.is_empty() {
+                cards.push(card);
+            }
+        }
+    }
+}
+
+fn fits_plate_scale(image_file: &Path) -> Option<PixelScale> {
+    let headers = fits_headers(image_file).ok()?;
+    let primary = headers.first()?;
+    let value = |key: &str| primary.value(key).and_then(|value| value.parse::<f64>().ok());
+
+    // Plate scale in arcseconds per pixel, either given directly or derived from the WCS increment in degrees
+    let arcsec_per_pixel = value("PIXSCALE")
+        .or_else(|| value("SECPIX"))
+        .or_else(|| value("CDELT1").map(|degrees| degrees.abs() * 3600.0))?;
+    Some(PixelScale { per_pixel: arcsec_per_pixel, unit: "arcsec" })
+}
+
+// Convert a density (pixels per inch or per centimeter) to millimeters per pixel
+fn density_to_scale(density: f64, mm_per_unit: f64) -> Option<PixelScale> {
+    if density > 0.0 {
+        Some(PixelScale { per_pixel: mm_per_unit / density, unit: "mm" })
+    } else {
+        None
+    }
+}
+
+fn jfif_pixel_scale(image_file: &Path) -> Option<PixelScale> {
+    let mut header = [0u8; 18];
+    File::open(image_file).ok()?.read_exact(&mut header).ok()?;
+    // SOI marker followed by an APP0 segment with the JFIF identifier
+    if header[0..4] != [0xFF, 0xD8, 0xFF, 0xE0] || &header[6..11] != b"JFIF\0" {
+        return None;
+    }
+    let units = header[13];
+    let x_density = u16::from_be_bytes([header[14], header[15]]) as f64;
+    match units {
+        1 => density_to_scale(x_density, 25.4),
+        2 => density_to_scale(x_density, 10.0),
+        _ => None,
+    }
+}
+
+fn png_pixel_scale(image_file: &Path) -> Option<PixelScale> {
+    // pHYs has to appear before the image data, so the start of the file is enough
+    let mut data = Vec::new();
+    File::open(image_file).ok()?.take(65536).read_to_end(&mut data).ok()?;
+    // Walk the chunks after the 8 byte signature until the pHYs chunk shows up
+    let mut pos = 8;
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let chunk_type = &data[pos + 4..pos + 8];
+        if chunk_type == b"pHYs" && pos + 8 + 9 <= data.len() {
+            let chunk = &data[pos + 8..pos + 8 + 9];
+            let pixels_per_unit = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as f64;
+            // Unit 1 is the meter, 0 means only the aspect ratio is known
+            return if chunk[8] == 1 { density_to_scale(pixels_per_unit, 1000.0) } else { None };
+        }
+        if chunk_type == b"IDAT" {
+            return None;
+        }
+        pos += 12 + length;
+    }
+    None
+}
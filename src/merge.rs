@@ -0,0 +1,118 @@
+use image::{imageops, GrayImage, RgbaImage};
+
+// Per-pixel sharpness as the absolute Laplacian of the luminance, smoothed so whole
+// regions are taken from the same image instead of switching between noisy pixels
+fn sharpness_map(image: &RgbaImage) -> GrayImage {
+    let gray = imageops::grayscale(image);
+    let (width, height) = gray.dimensions();
+    let laplacian = GrayImage::from_fn(width, height, |x, y| {
+        if x == 0 || y == 0 || x + 1 >= width || y + 1 >= height {
+            return image::Luma([0]);
+        }
+        let center = gray.get_pixel(x, y)[0] as i32 * 4;
+        let neighbors = gray.get_pixel(x - 1, y)[0] as i32
+            + gray.get_pixel(x + 1, y)[0] as i32
+            + gray.get_pixel(x, y - 1)[0] as i32
+            + gray.get_pixel(x, y + 1)[0] as i32;
+        image::Luma([(center - neighbors).unsigned_abs().min(255) as u8])
+    });
+    imageops::blur(&laplacian, 3.0)
+}
+
+/// Combine a focus bracketed burst by taking every pixel from the image where it is sharpest
+pub fn focus_stack(images: &[RgbaImage]) -> Result<RgbaImage, String> {
+    let first = images.first().ok_or("No images to stack".to_string())?;
+    let (width, height) = first.dimensions();
+    if let Some(mismatch) = images.iter().find(|image| image.dimensions() != (width, height)) {
+        return Err(format!("All images need the same size, found {}x{} and {}x{}", width, height, mismatch.width(), mismatch.height()));
+    }
+
+    let mut result = first.clone();
+    let mut best_sharpness = sharpness_map(first);
+    for image in images.iter().skip(1) {
+        let sharpness = sharpness_map(image);
+        for (x, y, pixel) in sharpness.enumerate_pixels() {
+            if pixel[0] > best_sharpness.get_pixel(x, y)[0] {
+                best_sharpness.put_pixel(x, y, *pixel);
+                result.put_pixel(x, y, *image.get_pixel(x, y));
+            }
+        }
+    }
+    Ok(result)
+}
+
+// Approximate conversion between sRGB values and linear light
+fn to_linear(value: u8) -> f32 {
+    (value as f32 / 255.0).powf(2.2)
+}
+
+fn from_linear(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0).round() as u8
+}
+
+// Trust mid tones the most, and clipped shadows and highlights the least
+fn hdr_weight(value: u8) -> f32 {
+    let centered = value as f32 / 127.5 - 1.0;
+    (1.0 - centered.abs().powi(12)).max(0.001)
+}
+
+/// Merge an exposure bracket given as images with their relative exposures, and tone map the result
+/// with the global Reinhard operator for display
+pub fn hdr_merge(images: &[(RgbaImage, f64)]) -> Result<RgbaImage, String> {
+    let (first, _) = images.first().ok_or("No images to merge".to_string())?;
+    let (width, height) = first.dimensions();
+    if let Some((mismatch, _)) = images.iter().find(|(image, _)| image.dimensions() != (width, height)) {
+        return Err(format!("All images need the same size, found {}x{} and {}x{}", width, height, mismatch.width(), mismatch.height()));
+    }
+
+    // Estimate the scene radiance of every pixel from all exposures
+    let pixel_count = (width * height) as usize;
+    let mut radiance = vec![[0f32; 3]; pixel_count];
+    for (index, radiance_pixel) in radiance.iter_mut().enumerate() {
+        let (x, y) = (index as u32 % width, index as u32 / width);
+        let mut sum = [0f32; 3];
+        let mut weight_sum = 0f32;
+        for (image, exposure) in images {
+            let pixel = image.get_pixel(x, y);
+            let weight = hdr_weight(pixel[0].max(pixel[1]).max(pixel[2]));
+            for channel in 0..3 {
+                sum[channel] += weight * to_linear(pixel[channel]) / *exposure as f32;
+            }
+            weight_sum += weight;
+        }
+        for channel in 0..3 {
+            radiance_pixel[channel] = sum[channel] / weight_sum;
+        }
+    }
+
+    // Scale the log-average luminance to middle gray, then compress with L / (1 + L)
+    let luminance = |pixel: &[f32; 3]| 0.2126 * pixel[0] + 0.7152 * pixel[1] + 0.0722 * pixel[2];
+    let log_average = (radiance.iter().map(|pixel| (luminance(pixel) + 1e-6).ln()).sum::<f32>() / pixel_count as f32).exp();
+    let key = 0.18 / log_average;
+    Ok(RgbaImage::from_fn(width, height, |x, y| {
+        let pixel = &radiance[(y * width + x) as usize];
+        let scaled = luminance(pixel) * key;
+        let ratio = if scaled > 0.0 { (scaled / (1.0 + scaled)) / luminance(pixel) } else { 0.0 };
+        image::Rgba([from_linear(pixel[0] * ratio), from_linear(pixel[1] * ratio), from_linear(pixel[2] * ratio), 255])
+    }))
+}
+
+// Size of the checkerboard squares shown through transparent areas
+const CHECKER_SIZE: u32 = 16;
+
+/// Blend `overlay` over `base` with the given opacity between 0 and 1, like the onion skin of an animation tool.
+/// Transparent areas of both images show a checkerboard, so they can be told apart from black or white.
+pub fn onion_skin(base: &RgbaImage, overlay: &RgbaImage, opacity: f32) -> RgbaImage {
+    RgbaImage::from_fn(base.width(), base.height(), |x, y| {
+        let checker = if (x / CHECKER_SIZE + y / CHECKER_SIZE) % 2 == 0 { 200.0 } else { 150.0 };
+        let under = base.get_pixel(x, y);
+        let over = overlay.get_pixel_checked(x, y).copied().unwrap_or(image::Rgba([0, 0, 0, 0]));
+        let over_alpha = over[3] as f32 / 255.0 * opacity.clamp(0.0, 1.0);
+        let under_alpha = under[3] as f32 / 255.0;
+        let blend = |channel: usize| {
+            let base_value = under[channel] as f32 * under_alpha + checker * (1.0 - under_alpha);
+            (over[channel] as f32 * over_alpha + base_value * (1.0 - over_alpha)).round() as u8
+        };
+        image::Rgba([blend(0), blend(1), blend(2), 255])
+    })
+}
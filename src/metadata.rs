@@ -0,0 +1,63 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+// Longest exposure bracket considered when looking for bracketed sets
+const MAX_BRACKET_SIZE: usize = 9;
+
+/// Exposure settings from the EXIF data of a photo
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Exposure {
+    pub exposure_time: Option<f64>,
+    pub exposure_bias: Option<f64>,
+}
+
+impl Exposure {
+    /// Exposure relative to a 0 EV shot, preferring the exposure time over the bias as it is what actually changed
+    pub fn relative_exposure(&self) -> Option<f64> {
+        self.exposure_time.or(self.exposure_bias.map(|bias| 2f64.powf(bias)))
+    }
+}
+
+pub fn read_exif(image_file: &Path) -> Option<exif::Exif> {
+    let file = File::open(image_file).ok()?;
+    exif::Reader::new().read_from_container(&mut BufReader::new(&file)).ok()
+}
+
+fn field_f64(exif: &exif::Exif, tag: exif::Tag) -> Option<f64> {
+    match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Rational(values) => values.first().map(|value| value.to_f64()),
+        exif::Value::SRational(values) => values.first().map(|value| value.to_f64()),
+        _ => None,
+    }
+}
+
+pub fn read_exposure(image_file: &Path) -> Option<Exposure> {
+    let exif = read_exif(image_file)?;
+    let exposure = Exposure {
+        exposure_time: field_f64(&exif, exif::Tag::ExposureTime),
+        exposure_bias: field_f64(&exif, exif::Tag::ExposureBiasValue),
+    };
+    exposure.relative_exposure().map(|_| exposure)
+}
+
+/// Find the exposure bracket starting with the first file: following shots belong to it as long as
+/// each one has a different exposure bias. Returns the files with their relative exposures.
+pub fn find_exposure_bracket<'a>(image_files: &[&'a Path]) -> Vec<(&'a Path, f64)> {
+    let mut bracket: Vec<(&Path, Exposure)> = Vec::new();
+    for &image_file in image_files.iter().take(MAX_BRACKET_SIZE) {
+        let exposure = match read_exposure(image_file) {
+            Some(exposure) => exposure,
+            None => break,
+        };
+        let is_repeated = bracket.iter().any(|(_, seen)| seen.exposure_bias == exposure.exposure_bias && seen.exposure_time == exposure.exposure_time);
+        if is_repeated {
+            break;
+        }
+        bracket.push((image_file, exposure));
+    }
+    if bracket.len() < 2 {
+        return Vec::new();
+    }
+    bracket.into_iter()
+        .filter_map(|(image_file, exposure)| exposure.relative_exposure().map(|relative| (image_file, relative)))
+        .collect()
+}
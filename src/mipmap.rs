@@ -0,0 +1,42 @@
+use std::cell::RefCell;
+
+use fltk::{image::{RgbImage, RgbScaling, SharedImage}, prelude::*};
+
+// Images smaller than this are scaled directly, halving them isn't worth the memory
+const MIN_MIPMAP_SIZE: i32 = 2048;
+
+thread_local! {
+    // Halved copies of the image being shown, largest first. FLTK images live on the UI thread only.
+    static LEVELS: RefCell<Vec<SharedImage>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Forget the copies of the previous image, done whenever a different image is shown
+pub fn clear() {
+    LEVELS.with(|levels| levels.borrow_mut().clear());
+}
+
+/// The smallest halved copy of the image that still has at least width x height pixels, so it can be
+/// shrunk further without aliasing and without sampling every pixel of a huge image. The copies are
+/// made when they're first needed. None if the image itself is the best source.
+pub fn level_for(image: &SharedImage, width: i32, height: i32) -> Option<SharedImage> {
+    if image.data_w().max(image.data_h()) < MIN_MIPMAP_SIZE || image.data_w() / 2 < width || image.data_h() / 2 < height {
+        return None;
+    }
+    LEVELS.with(|levels| {
+        let mut levels = levels.borrow_mut();
+        loop {
+            let halved = {
+                let source = levels.last().unwrap_or(image);
+                let (half_w, half_h) = (source.data_w() / 2, source.data_h() / 2);
+                if half_w < width.max(1) || half_h < height.max(1) {
+                    break;
+                }
+                // Bilinear sampling at exactly half the size averages each 2x2 block
+                RgbImage::set_scaling_algorithm(RgbScaling::Bilinear);
+                source.copy_sized(half_w, half_h)
+            };
+            levels.push(halved);
+        }
+        levels.iter().rev().find(|level| level.data_w() >= width && level.data_h() >= height).cloned()
+    })
+}
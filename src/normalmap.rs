@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use image::{imageops, Rgba, RgbaImage};
+
+// File name endings that game engines and texture tools use for tangent space normal maps
+const NORMAL_MAP_SUFFIXES: [&str; 6] = ["_n", "_nm", "_nrm", "_norm", "_normal", "_normals"];
+// Light from the top left and in front, like in most material previews
+const LIGHT_DIRECTION: [f32; 3] = [-0.5, 0.5, 0.7];
+// Size of the lit sphere preview, and how often the texture wraps around it
+const SPHERE_SIZE: u32 = 512;
+const SPHERE_TILES: f32 = 2.0;
+
+/// Ways to look at a normal map
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalMapView {
+    X,
+    Y,
+    Z,
+    Lit,
+    Sphere,
+}
+
+impl NormalMapView {
+    pub const ALL: [NormalMapView; 5] = [NormalMapView::X, NormalMapView::Y, NormalMapView::Z, NormalMapView::Lit, NormalMapView::Sphere];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NormalMapView::X => "X (red)",
+            NormalMapView::Y => "Y (green)",
+            NormalMapView::Z => "Z (blue)",
+            NormalMapView::Lit => "lit",
+            NormalMapView::Sphere => "lit sphere",
+        }
+    }
+
+    pub fn render(&self, image: &RgbaImage) -> RgbaImage {
+        match self {
+            NormalMapView::X => channel(image, 0),
+            NormalMapView::Y => channel(image, 1),
+            NormalMapView::Z => channel(image, 2),
+            NormalMapView::Lit => lit(image),
+            NormalMapView::Sphere => lit_sphere(image),
+        }
+    }
+}
+
+/// Whether the file name marks the image as a normal map, like "brick_normal.png" or "rock_n.tga"
+pub fn is_normal_map_name(path: &Path) -> bool {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_lowercase()).unwrap_or_default();
+    NORMAL_MAP_SUFFIXES.iter().any(|suffix| stem.ends_with(suffix))
+}
+
+// Tangent space normal stored in a pixel
+fn decode(pixel: &Rgba<u8>) -> [f32; 3] {
+    let normal = [0, 1, 2].map(|channel| pixel[channel] as f32 / 127.5 - 1.0);
+    let length = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt().max(f32::EPSILON);
+    normal.map(|component| component / length)
+}
+
+fn light() -> [f32; 3] {
+    let length = LIGHT_DIRECTION.iter().map(|component| component * component).sum::<f32>().sqrt();
+    LIGHT_DIRECTION.map(|component| component / length)
+}
+
+fn shade(normal: [f32; 3], light: [f32; 3]) -> Rgba<u8> {
+    let value = (normal[0] * light[0] + normal[1] * light[1] + normal[2] * light[2]).max(0.0);
+    let value = (value * 255.0).round() as u8;
+    Rgba([value, value, value, 255])
+}
+
+// A single channel as grayscale, to check the direction of one axis
+fn channel(image: &RgbaImage, channel: usize) -> RgbaImage {
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let value = image.get_pixel(x, y)[channel];
+        Rgba([value, value, value, 255])
+    })
+}
+
+// The surface detail of the texture lit from the top left, as it would look on a flat wall
+fn lit(image: &RgbaImage) -> RgbaImage {
+    let light = light();
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| shade(decode(image.get_pixel(x, y)), light))
+}
+
+// The texture wrapped around a lit sphere, which shows whether its normals point the right way from all sides
+fn lit_sphere(image: &RgbaImage) -> RgbaImage {
+    let texture = imageops::resize(image, SPHERE_SIZE, SPHERE_SIZE, imageops::FilterType::Triangle);
+    let light = light();
+    let radius = SPHERE_SIZE as f32 / 2.0;
+    RgbaImage::from_fn(SPHERE_SIZE, SPHERE_SIZE, |x, y| {
+        let nx = (x as f32 + 0.5 - radius) / radius;
+        let ny = (radius - y as f32 - 0.5) / radius;
+        let distance = nx * nx + ny * ny;
+        if distance > 1.0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let nz = (1.0 - distance).sqrt();
+        // Tangent frame of the sphere: the tangent follows the longitude, the bitangent the latitude
+        let tangent = {
+            let length = (nz * nz + nx * nx).sqrt().max(f32::EPSILON);
+            [nz / length, 0.0, -nx / length]
+        };
+        let normal = [nx, ny, nz];
+        let bitangent = [
+            normal[1] * tangent[2] - normal[2] * tangent[1],
+            normal[2] * tangent[0] - normal[0] * tangent[2],
+            normal[0] * tangent[1] - normal[1] * tangent[0],
+        ];
+        let u = (nx.atan2(nz) / std::f32::consts::PI * 0.5 + 0.5) * SPHERE_TILES;
+        let v = (0.5 - ny.asin() / std::f32::consts::PI) * SPHERE_TILES / 2.0;
+        let texel = texture.get_pixel(
+            ((u.fract() * SPHERE_SIZE as f32) as u32).min(SPHERE_SIZE - 1),
+            ((v.fract() * SPHERE_SIZE as f32) as u32).min(SPHERE_SIZE - 1),
+        );
+        let detail = decode(texel);
+        let perturbed = [0, 1, 2].map(|axis| tangent[axis] * detail[0] + bitangent[axis] * detail[1] + normal[axis] * detail[2]);
+        shade(perturbed, light)
+    })
+}
@@ -0,0 +1,115 @@
+use fltk::{draw, enums::{Align, Color, Font, LineStyle}};
+
+/// A measurement line in window coordinates, with its description
+pub struct MeasureLine {
+    pub start: (i32, i32),
+    pub end: (i32, i32),
+    pub label: String,
+}
+
+/// How the image would be cropped for an output target, in window coordinates as (x, y, width, height)
+#[derive(Clone, Debug, PartialEq)]
+pub struct TargetFrame {
+    pub crop: (i32, i32, i32, i32),
+    pub safe_area: (i32, i32, i32, i32),
+    pub label: String,
+}
+
+/// Arrow shown while the mouse is over a window edge that navigates when clicked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeArrow {
+    Previous,
+    Next,
+}
+
+/// Information drawn on top of the image
+#[derive(Default)]
+pub struct Overlay {
+    pub measure_line: Option<MeasureLine>,
+    // Region being selected for a screen capture, as x, y, width and height
+    pub selection: Option<(i32, i32, i32, i32)>,
+    pub target_frame: Option<TargetFrame>,
+    // Lines of the performance statistics shown with /debug-perf
+    pub perf_report: Option<Vec<String>>,
+    pub status_bar: Option<String>,
+    pub edge_arrow: Option<EdgeArrow>,
+}
+
+// Height of the status bar at the bottom of the window
+const STATUS_BAR_HEIGHT: i32 = 22;
+// Size of the navigation arrows at the window edges
+const ARROW_SIZE: i32 = 24;
+// Height of the progress bar of slow decodes, and its distance from the bottom of the window
+const PROGRESS_BAR_HEIGHT: i32 = 6;
+const PROGRESS_BAR_MARGIN: i32 = 40;
+
+impl Overlay {
+    pub fn draw(&self, width: i32, height: i32) {
+        if let Some(line) = &self.measure_line {
+            draw::set_draw_color(Color::Yellow);
+            draw::set_line_style(LineStyle::Solid, 2);
+            draw::draw_line(line.start.0, line.start.1, line.end.0, line.end.1);
+            draw::set_line_style(LineStyle::Solid, 0);
+            draw_label(&line.label, line.end.0 + 12, line.end.1 + 12);
+        }
+        if let Some(target_frame) = &self.target_frame {
+            let (x, y, width, height) = target_frame.crop;
+            draw::set_draw_color(Color::Yellow);
+            draw::set_line_style(LineStyle::Solid, 2);
+            draw::draw_rect(x, y, width, height);
+            let (x, y, width, height) = target_frame.safe_area;
+            draw::set_draw_color(Color::White);
+            draw::set_line_style(LineStyle::Dash, 1);
+            draw::draw_rect(x, y, width, height);
+            draw::set_line_style(LineStyle::Solid, 0);
+            draw_label(&target_frame.label, target_frame.crop.0 + 12, target_frame.crop.1 + 12);
+        }
+        if let Some((x, y, width, height)) = self.selection {
+            draw::set_draw_color(Color::White);
+            draw::set_line_style(LineStyle::Dash, 1);
+            draw::draw_rect(x, y, width, height);
+            draw::set_line_style(LineStyle::Solid, 0);
+        }
+        if let Some(lines) = &self.perf_report {
+            for (number, line) in lines.iter().enumerate() {
+                draw_label(line, 12, 12 + number as i32 * 22);
+            }
+        }
+        if let Some(arrow) = self.edge_arrow {
+            let (center_x, direction) = match arrow {
+                EdgeArrow::Previous => (ARROW_SIZE * 2, -1),
+                EdgeArrow::Next => (width - ARROW_SIZE * 2, 1),
+            };
+            let center_y = height / 2;
+            draw::draw_rect_fill(center_x - ARROW_SIZE, center_y - ARROW_SIZE, ARROW_SIZE * 2, ARROW_SIZE * 2, Color::from_rgb(32, 32, 32));
+            draw::set_draw_color(Color::White);
+            draw::draw_polygon(
+                center_x + direction * ARROW_SIZE / 2, center_y,
+                center_x - direction * ARROW_SIZE / 2, center_y - ARROW_SIZE / 2,
+                center_x - direction * ARROW_SIZE / 2, center_y + ARROW_SIZE / 2,
+            );
+        }
+        if let Some(text) = &self.status_bar {
+            draw::draw_rect_fill(0, height - STATUS_BAR_HEIGHT, width, STATUS_BAR_HEIGHT, Color::from_rgb(32, 32, 32));
+            draw::set_font(Font::Helvetica, 13);
+            draw::set_draw_color(Color::White);
+            draw::draw_text2(text, 8, height - STATUS_BAR_HEIGHT, width - 16, STATUS_BAR_HEIGHT, Align::Left | Align::Clip);
+        }
+    }
+}
+
+/// Draw a bar at the bottom of the window showing how far loading the image has come, from 0 to 1
+pub fn draw_progress(fraction: f64, width: i32, height: i32) {
+    let (bar_width, x, y) = (width / 3, width / 3, height - PROGRESS_BAR_MARGIN);
+    draw::draw_rect_fill(x - 2, y - 2, bar_width + 4, PROGRESS_BAR_HEIGHT + 4, Color::Black);
+    draw::draw_rect_fill(x, y, (bar_width as f64 * fraction.clamp(0.0, 1.0)) as i32, PROGRESS_BAR_HEIGHT, Color::White);
+}
+
+// Draw text on a dark background so it stays readable on any image
+pub fn draw_label(text: &str, x: i32, y: i32) {
+    draw::set_font(Font::Helvetica, 14);
+    let (width, height) = draw::measure(text, false);
+    draw::draw_rect_fill(x - 4, y - 2, width + 8, height + 4, Color::Black);
+    draw::set_draw_color(Color::White);
+    draw::draw_text2(text, x, y, width, height, Align::Left);
+}
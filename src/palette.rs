@@ -0,0 +1,91 @@
+use image::{imageops, RgbaImage};
+
+// Images are reduced to about this many pixels before clustering, which is plenty to find the dominant colors
+const SAMPLE_PIXELS: u32 = 10_000;
+const MAX_ITERATIONS: usize = 20;
+
+fn distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+fn nearest(centers: &[[f32; 3]], color: &[f32; 3]) -> usize {
+    centers.iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| distance(a, color).total_cmp(&distance(b, color)))
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+/// Find the dominant colors of an image with k-means clustering.
+/// Returns the colors with the share of the image they cover, most common first.
+pub fn dominant_colors(image: &RgbaImage, count: usize) -> Vec<([u8; 3], f64)> {
+    let scale = (SAMPLE_PIXELS as f64 / (image.width() * image.height()).max(1) as f64).sqrt().min(1.0);
+    let sample = imageops::resize(
+        image,
+        ((image.width() as f64 * scale) as u32).max(1),
+        ((image.height() as f64 * scale) as u32).max(1),
+        imageops::FilterType::Nearest,
+    );
+    // Transparent pixels don't contribute to the visible colors
+    let pixels: Vec<[f32; 3]> = sample.pixels()
+        .filter(|pixel| pixel[3] > 127)
+        .map(|pixel| [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32])
+        .collect();
+    if pixels.is_empty() || count == 0 {
+        return Vec::new();
+    }
+
+    // Deterministic k-means++ style start: begin with the first pixel, then keep adding the pixel farthest from all centers
+    let mut centers = vec![pixels[0]];
+    while centers.len() < count.min(pixels.len()) {
+        let farthest = pixels.iter()
+            .max_by(|a, b| distance(&centers[nearest(&centers, a)], a).total_cmp(&distance(&centers[nearest(&centers, b)], b)))
+            .copied()
+            .unwrap_or(pixels[0]);
+        centers.push(farthest);
+    }
+
+    let mut assignments = vec![0; pixels.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut is_changed = false;
+        for (pixel, assignment) in pixels.iter().zip(assignments.iter_mut()) {
+            let cluster = nearest(&centers, pixel);
+            if cluster != *assignment {
+                *assignment = cluster;
+                is_changed = true;
+            }
+        }
+        let mut sums = vec![[0f32; 3]; centers.len()];
+        let mut sizes = vec![0usize; centers.len()];
+        for (pixel, &cluster) in pixels.iter().zip(assignments.iter()) {
+            for channel in 0..3 {
+                sums[cluster][channel] += pixel[channel];
+            }
+            sizes[cluster] += 1;
+        }
+        for (cluster, center) in centers.iter_mut().enumerate() {
+            if sizes[cluster] > 0 {
+                *center = sums[cluster].map(|sum| sum / sizes[cluster] as f32);
+            }
+        }
+        if !is_changed {
+            break;
+        }
+    }
+
+    let mut colors: Vec<([u8; 3], f64)> = centers.iter()
+        .enumerate()
+        .map(|(cluster, center)| {
+            let size = assignments.iter().filter(|&&assignment| assignment == cluster).count();
+            (center.map(|channel| channel.round().clamp(0.0, 255.0) as u8), size as f64 / pixels.len() as f64)
+        })
+        .filter(|(_, share)| *share > 0.0)
+        .collect();
+    colors.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    colors
+}
+
+/// Color as #rrggbb
+pub fn hex_code(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
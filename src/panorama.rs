@@ -0,0 +1,105 @@
+use image::{imageops, GrayImage, RgbaImage};
+
+use crate::align;
+
+// Size the overlap check is done at, matching the region of two shots doesn't need more detail
+const MATCH_SIZE: u32 = 256;
+// Mean absolute difference (0-255) in the overlap below which two shots count as overlapping
+const MAX_MATCH_DIFFERENCE: f64 = 24.0;
+// Part of the image that has to overlap, less is unreliable and more is rather a burst than a panorama
+const MIN_OVERLAP: f64 = 0.1;
+const MAX_OVERLAP: f64 = 0.9;
+
+// Mean absolute difference of the overlap of two equally scaled grayscale images, with `moving` placed at the offset
+fn overlap_difference(reference: &GrayImage, moving: &GrayImage, dx: i32, dy: i32) -> Option<f64> {
+    let (width, height) = (reference.width() as i32, reference.height() as i32);
+    let (left, top) = (dx.max(0), dy.max(0));
+    let (right, bottom) = ((dx + moving.width() as i32).min(width), (dy + moving.height() as i32).min(height));
+    if right <= left || bottom <= top {
+        return None;
+    }
+    let overlap = ((right - left) * (bottom - top)) as f64 / (width * height) as f64;
+    if !(MIN_OVERLAP..=MAX_OVERLAP).contains(&overlap) {
+        return None;
+    }
+
+    let mut sum = 0u64;
+    for y in top..bottom {
+        for x in left..right {
+            let a = reference.get_pixel(x as u32, y as u32)[0];
+            let b = moving.get_pixel((x - dx) as u32, (y - dy) as u32)[0];
+            sum += a.abs_diff(b) as u64;
+        }
+    }
+    Some(sum as f64 / ((right - left) * (bottom - top)) as f64)
+}
+
+/// Find where `moving` continues `reference` in a panorama, as the position of its top left
+/// corner relative to the reference. Returns None if the shots don't overlap convincingly.
+pub fn find_overlap(reference: &RgbaImage, moving: &RgbaImage) -> Option<(i32, i32)> {
+    if reference.dimensions() != moving.dimensions() {
+        return None;
+    }
+    let (dx, dy) = align::estimate_translation(reference, moving);
+
+    let scale = (MATCH_SIZE as f64 / reference.width().max(reference.height()) as f64).min(1.0);
+    let width = ((reference.width() as f64 * scale).round() as u32).max(1);
+    let height = ((reference.height() as f64 * scale).round() as u32).max(1);
+    let small_reference = imageops::grayscale(&imageops::resize(reference, width, height, imageops::FilterType::Triangle));
+    let small_moving = imageops::grayscale(&imageops::resize(moving, width, height, imageops::FilterType::Triangle));
+
+    // Phase correlation can't tell a shift from the same shift minus the image size, so check all candidates
+    let (full_width, full_height) = (reference.width() as i32, reference.height() as i32);
+    let wrapped = |shift: i32, size: i32| if shift > 0 { shift - size } else { shift + size };
+    let mut best: Option<((i32, i32), f64)> = None;
+    for candidate_x in [dx, wrapped(dx, full_width)] {
+        for candidate_y in [dy, wrapped(dy, full_height)] {
+            let small_x = (candidate_x as f64 * scale).round() as i32;
+            let small_y = (candidate_y as f64 * scale).round() as i32;
+            if let Some(difference) = overlap_difference(&small_reference, &small_moving, small_x, small_y) {
+                if difference <= MAX_MATCH_DIFFERENCE && best.is_none_or(|(_, best_difference)| difference < best_difference) {
+                    best = Some(((candidate_x, candidate_y), difference));
+                }
+            }
+        }
+    }
+    log::debug!("Panorama overlap for shift {}, {}: {:?}", dx, dy, best);
+    best.map(|(offset, _)| offset)
+}
+
+/// Roughly stitch the images at the given positions, blending the overlaps with weights that fall off
+/// towards the image borders so the seams are less visible
+pub fn stitch(images: &[RgbaImage], positions: &[(i32, i32)]) -> Result<RgbaImage, String> {
+    if images.is_empty() || images.len() != positions.len() {
+        return Err("No images to stitch".to_string());
+    }
+    let left = positions.iter().map(|(x, _)| *x).min().unwrap_or(0);
+    let top = positions.iter().map(|(_, y)| *y).min().unwrap_or(0);
+    let right = images.iter().zip(positions).map(|(image, (x, _))| x + image.width() as i32).max().unwrap_or(0);
+    let bottom = images.iter().zip(positions).map(|(image, (_, y))| y + image.height() as i32).max().unwrap_or(0);
+    let (width, height) = ((right - left) as u32, (bottom - top) as u32);
+
+    let mut sums = vec![[0f32; 4]; (width * height) as usize];
+    for (image, (x, y)) in images.iter().zip(positions) {
+        let (offset_x, offset_y) = ((x - left) as u32, (y - top) as u32);
+        for (image_x, image_y, pixel) in image.enumerate_pixels() {
+            let border_distance = image_x.min(image_y).min(image.width() - 1 - image_x).min(image.height() - 1 - image_y);
+            let weight = border_distance as f32 + 1.0;
+            let sum = &mut sums[((offset_y + image_y) * width + offset_x + image_x) as usize];
+            for channel in 0..3 {
+                sum[channel] += pixel[channel] as f32 * weight;
+            }
+            sum[3] += weight;
+        }
+    }
+
+    // Areas no shot covers stay transparent
+    Ok(RgbaImage::from_fn(width, height, |x, y| {
+        let sum = &sums[(y * width + x) as usize];
+        if sum[3] > 0.0 {
+            image::Rgba([(sum[0] / sum[3]).round() as u8, (sum[1] / sum[3]).round() as u8, (sum[2] / sum[3]).round() as u8, 255])
+        } else {
+            image::Rgba([0, 0, 0, 0])
+        }
+    }))
+}
@@ -0,0 +1,132 @@
+use std::{
+    sync::{atomic::{AtomicBool, Ordering}, Mutex},
+    time::{Duration, Instant},
+};
+
+// How often the overlay is refreshed, in seconds
+pub const REFRESH_INTERVAL: f64 = 0.5;
+// Weight of the newest timing in a stage's running average
+const AVERAGE_WEIGHT: f64 = 0.2;
+
+// Collected from the loading code and worker threads alike, so it lives here instead of being passed around
+static IS_ENABLED: AtomicBool = AtomicBool::new(false);
+static STATS: Mutex<Stats> = Mutex::new(Stats::new());
+
+struct StageTiming {
+    name: &'static str,
+    last: Duration,
+    average_ms: f64,
+}
+
+struct Stats {
+    stages: Vec<StageTiming>,
+    cache_hits: u64,
+    cache_misses: u64,
+    queue_depth: usize,
+    image_bytes: usize,
+    displayed_bytes: usize,
+}
+
+impl Stats {
+    const fn new() -> Stats {
+        Stats { stages: Vec::new(), cache_hits: 0, cache_misses: 0, queue_depth: 0, image_bytes: 0, displayed_bytes: 0 }
+    }
+}
+
+fn update(change: impl FnOnce(&mut Stats)) {
+    if !is_enabled() {
+        return;
+    }
+    if let Ok(mut stats) = STATS.lock() {
+        change(&mut stats);
+    }
+}
+
+/// Start collecting statistics, done for the /debug-perf argument
+pub fn enable() {
+    IS_ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    IS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Run one stage of showing an image, like decoding or scaling, and record how long it took
+pub fn time<T>(stage: &'static str, work: impl FnOnce() -> T) -> T {
+    if !is_enabled() {
+        return work();
+    }
+    let start = Instant::now();
+    let result = work();
+    let elapsed = start.elapsed();
+    update(|stats| {
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        match stats.stages.iter_mut().find(|timing| timing.name == stage) {
+            Some(timing) => {
+                timing.last = elapsed;
+                timing.average_ms += (elapsed_ms - timing.average_ms) * AVERAGE_WEIGHT;
+            }
+            None => stats.stages.push(StageTiming { name: stage, last: elapsed, average_ms: elapsed_ms }),
+        }
+    });
+    result
+}
+
+/// Count a lookup in a cache of decoded images
+pub fn cache_lookup(is_hit: bool) {
+    update(|stats| if is_hit { stats.cache_hits += 1 } else { stats.cache_misses += 1 });
+}
+
+/// Number of images waiting to be decoded in the background
+pub fn set_queue_depth(depth: usize) {
+    update(|stats| stats.queue_depth = depth);
+}
+
+/// Memory used by the pixels of the image being shown, decoded and as displayed at the current zoom
+pub fn set_image_memory(image_bytes: usize, displayed_bytes: usize) {
+    update(|stats| {
+        stats.image_bytes = image_bytes;
+        stats.displayed_bytes = displayed_bytes;
+    });
+}
+
+fn megabytes(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// The latest timings, memory use and cache status in one line for the status bar
+pub fn summary() -> String {
+    let Ok(stats) = STATS.lock() else {
+        return String::new();
+    };
+    let mut parts: Vec<String> = stats.stages.iter()
+        .map(|timing| format!("{} {:.1} ms", timing.name, timing.last.as_secs_f64() * 1000.0))
+        .collect();
+    parts.push(format!("{:.1} MB", megabytes(stats.image_bytes + stats.displayed_bytes)));
+    let lookups = stats.cache_hits + stats.cache_misses;
+    parts.push(if lookups == 0 {
+        "not cached".to_string()
+    } else {
+        format!("cache {:.0}% hits", stats.cache_hits as f64 * 100.0 / lookups as f64)
+    });
+    parts.join(", ")
+}
+
+/// The statistics as lines of text for the overlay
+pub fn report() -> Vec<String> {
+    let Ok(stats) = STATS.lock() else {
+        return Vec::new();
+    };
+    let mut lines: Vec<String> = stats.stages.iter()
+        .map(|timing| format!("{}: {:.1} ms (average {:.1} ms)", timing.name, timing.last.as_secs_f64() * 1000.0, timing.average_ms))
+        .collect();
+    let lookups = stats.cache_hits + stats.cache_misses;
+    if lookups == 0 {
+        lines.push("Cache: no lookups".to_string());
+    } else {
+        lines.push(format!("Cache: {} hits, {} misses ({:.0}% hit rate)", stats.cache_hits, stats.cache_misses, stats.cache_hits as f64 * 100.0 / lookups as f64));
+    }
+    lines.push(format!("Decode queue: {}", stats.queue_depth));
+    lines.push(format!("Image memory: {:.1} MB decoded, {:.1} MB displayed", megabytes(stats.image_bytes), megabytes(stats.displayed_bytes)));
+    lines
+}
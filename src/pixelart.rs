@@ -0,0 +1,95 @@
+use image::{Rgba, RgbaImage};
+
+/// Upscalers for pixel art that keep edges sharp instead of blurring them, like emulators do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelArtScaler {
+    Scale2x,
+    Scale3x,
+    Scale4x,
+}
+
+impl PixelArtScaler {
+    pub const ALL: [PixelArtScaler; 3] = [PixelArtScaler::Scale2x, PixelArtScaler::Scale3x, PixelArtScaler::Scale4x];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PixelArtScaler::Scale2x => "Scale2x",
+            PixelArtScaler::Scale3x => "Scale3x",
+            PixelArtScaler::Scale4x => "Scale4x",
+        }
+    }
+
+    pub fn scale(&self, image: &RgbaImage) -> RgbaImage {
+        match self {
+            PixelArtScaler::Scale2x => scale2x(image),
+            PixelArtScaler::Scale3x => scale3x(image),
+            // Scale4x is Scale2x applied twice
+            PixelArtScaler::Scale4x => scale2x(&scale2x(image)),
+        }
+    }
+}
+
+// The pixel at the offset from (x, y), repeating the edge pixels outside of the image
+fn neighbor(image: &RgbaImage, x: u32, y: u32, dx: i32, dy: i32) -> Rgba<u8> {
+    let nx = (x as i32 + dx).clamp(0, image.width() as i32 - 1) as u32;
+    let ny = (y as i32 + dy).clamp(0, image.height() as i32 - 1) as u32;
+    *image.get_pixel(nx, ny)
+}
+
+fn scale2x(image: &RgbaImage) -> RgbaImage {
+    let mut scaled = RgbaImage::new(image.width() * 2, image.height() * 2);
+    for (x, y, &e) in image.enumerate_pixels() {
+        let b = neighbor(image, x, y, 0, -1);
+        let d = neighbor(image, x, y, -1, 0);
+        let f = neighbor(image, x, y, 1, 0);
+        let h = neighbor(image, x, y, 0, 1);
+        let (e0, e1, e2, e3) = if b != h && d != f {
+            (
+                if d == b { d } else { e },
+                if b == f { f } else { e },
+                if d == h { d } else { e },
+                if h == f { f } else { e },
+            )
+        } else {
+            (e, e, e, e)
+        };
+        scaled.put_pixel(x * 2, y * 2, e0);
+        scaled.put_pixel(x * 2 + 1, y * 2, e1);
+        scaled.put_pixel(x * 2, y * 2 + 1, e2);
+        scaled.put_pixel(x * 2 + 1, y * 2 + 1, e3);
+    }
+    scaled
+}
+
+fn scale3x(image: &RgbaImage) -> RgbaImage {
+    let mut scaled = RgbaImage::new(image.width() * 3, image.height() * 3);
+    for (x, y, &e) in image.enumerate_pixels() {
+        let a = neighbor(image, x, y, -1, -1);
+        let b = neighbor(image, x, y, 0, -1);
+        let c = neighbor(image, x, y, 1, -1);
+        let d = neighbor(image, x, y, -1, 0);
+        let f = neighbor(image, x, y, 1, 0);
+        let g = neighbor(image, x, y, -1, 1);
+        let h = neighbor(image, x, y, 0, 1);
+        let i = neighbor(image, x, y, 1, 1);
+        let block = if b != h && d != f {
+            [
+                if d == b { d } else { e },
+                if (d == b && e != c) || (b == f && e != a) { b } else { e },
+                if b == f { f } else { e },
+                if (d == b && e != g) || (d == h && e != a) { d } else { e },
+                e,
+                if (b == f && e != i) || (h == f && e != c) { f } else { e },
+                if d == h { d } else { e },
+                if (d == h && e != i) || (h == f && e != g) { h } else { e },
+                if h == f { f } else { e },
+            ]
+        } else {
+            [e; 9]
+        };
+        for (index, pixel) in block.iter().enumerate() {
+            scaled.put_pixel(x * 3 + index as u32 % 3, y * 3 + index as u32 / 3, *pixel);
+        }
+    }
+    scaled
+}
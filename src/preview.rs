@@ -0,0 +1,68 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use exif::{In, Tag};
+use image::{imageops, RgbImage};
+
+use crate::{loader::DecodedImage, mapped};
+
+// TIFF Compression value of the JPEG previews some RAW formats store as the main image, e.g. CR2
+const COMPRESSION_JPEG: u32 = 6;
+
+/// Decode JPEG data at the smallest fraction of its size (1/8, 1/4 or 1/2) that still covers the
+/// width and height, which skips most of the work of a full decode
+pub fn decode_jpeg(reader: impl Read, width: i32, height: i32) -> Result<DecodedImage, String> {
+    let mut decoder = jpeg_decoder::Decoder::new(reader);
+    let clamp = |size: i32| size.clamp(1, u16::MAX as i32) as u16;
+    decoder.scale(clamp(width), clamp(height)).map_err(|err| format!("Decoding failed: {}", err))?;
+    let pixels = decoder.decode().map_err(|err| format!("Decoding failed: {}", err))?;
+    let info = decoder.info().ok_or("Missing image information".to_string())?;
+    let data = match info.pixel_format {
+        jpeg_decoder::PixelFormat::RGB24 => pixels,
+        jpeg_decoder::PixelFormat::L8 => pixels.iter().flat_map(|&gray| [gray, gray, gray]).collect(),
+        format => return Err(format!("Unsupported pixel format {:?}", format)),
+    };
+    Ok(DecodedImage { data, width: info.width as i32, height: info.height as i32 })
+}
+
+/// A JPEG file decoded at a fraction of its size, see `decode_jpeg`
+pub fn jpeg_file(path: &Path, width: i32, height: i32) -> Result<DecodedImage, String> {
+    let file = File::open(path).map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+    decode_jpeg(BufReader::new(file), width, height)
+}
+
+/// The largest JPEG preview embedded in a TIFF based RAW file, which cameras write for their own
+/// display, turned upright like the RAW decoding turns the full image
+pub fn raw_preview(path: &Path, width: i32, height: i32) -> Result<DecodedImage, String> {
+    let data = mapped::map(path).map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+    let exif = mapped::read_tiff_tags(&data).map_err(|err| format!("No preview in {}: {}", path.display(), err))?;
+    let field = |tag: Tag, ifd: In| exif.get_field(tag, ifd).and_then(|field| field.value.get_uint(0));
+
+    let mut candidates = vec![
+        (field(Tag::JPEGInterchangeFormat, In::PRIMARY), field(Tag::JPEGInterchangeFormatLength, In::PRIMARY)),
+        (field(Tag::JPEGInterchangeFormat, In::THUMBNAIL), field(Tag::JPEGInterchangeFormatLength, In::THUMBNAIL)),
+    ];
+    if field(Tag::Compression, In::PRIMARY) == Some(COMPRESSION_JPEG) {
+        candidates.push((field(Tag::StripOffsets, In::PRIMARY), field(Tag::StripByteCounts, In::PRIMARY)));
+    }
+    let (offset, length) = candidates.into_iter()
+        .filter_map(|(offset, length)| Some((offset? as usize, length? as usize)))
+        .filter(|&(offset, length)| length > 0 && offset + length <= data.len())
+        .max_by_key(|&(_, length)| length)
+        .ok_or(format!("No preview in {}", path.display()))?;
+
+    let preview = decode_jpeg(&data[offset..offset + length], width, height)?;
+    let image = RgbImage::from_raw(preview.width as u32, preview.height as u32, preview.data).ok_or("Invalid preview size".to_string())?;
+    // Mirrored orientations are left alone, cameras don't write them
+    let image = match field(Tag::Orientation, In::PRIMARY) {
+        Some(3) => imageops::rotate180(&image),
+        Some(6) => imageops::rotate90(&image),
+        Some(8) => imageops::rotate270(&image),
+        _ => image,
+    };
+    let (width, height) = (image.width() as i32, image.height() as i32);
+    Ok(DecodedImage { data: image.into_raw(), width, height })
+}
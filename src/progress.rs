@@ -0,0 +1,45 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+
+use fltk::{button::Button, frame::Frame, misc::Progress, prelude::*, window::Window};
+
+/// Modal window with a status line, a progress bar and a Cancel button, for work running in the
+/// background. Canceling, by the button or by closing the window, sets the flag the work checks.
+pub struct ProgressDialog {
+    window: Window,
+    status: Frame,
+    progress: Progress,
+}
+
+impl ProgressDialog {
+    pub fn show(title: &str, maximum: f64, is_canceled: &Arc<AtomicBool>) -> ProgressDialog {
+        let mut window = Window::default().with_size(400, 110).with_label(title);
+        let status = Frame::new(10, 10, 380, 25, None);
+        let mut progress = Progress::new(10, 40, 380, 25, None);
+        progress.set_minimum(0.0);
+        progress.set_maximum(maximum);
+        let mut cancel_button = Button::new(300, 75, 90, 25, "Cancel");
+        window.end();
+        window.make_modal(true);
+        window.show();
+
+        let button_canceled = Arc::clone(is_canceled);
+        cancel_button.set_callback(move |_| button_canceled.store(true, Ordering::Relaxed));
+        let window_canceled = Arc::clone(is_canceled);
+        window.set_callback(move |_| window_canceled.store(true, Ordering::Relaxed));
+        ProgressDialog { window, status, progress }
+    }
+
+    pub fn set_status(&mut self, text: &str) {
+        self.status.set_label(text);
+    }
+
+    /// Move the bar, with the text shown on it
+    pub fn set_progress(&mut self, value: f64, text: &str) {
+        self.progress.set_value(value);
+        self.progress.set_label(text);
+    }
+
+    pub fn hide(&mut self) {
+        self.window.hide();
+    }
+}
@@ -0,0 +1,64 @@
+use image::{GrayImage, Luma, RgbaImage};
+
+use crate::gray16::Gray16Image;
+
+// Light from the northwest, 45 degrees above the horizon, as in most printed maps
+const SUN_AZIMUTH: f32 = 315.0;
+const SUN_ALTITUDE: f32 = 45.0;
+// At an exaggeration of 1 the full height range is this fraction of the image width
+const RELIEF_HEIGHT: f32 = 0.1;
+
+/// Heights between 0 and 1 of a heightmap, row by row
+pub struct HeightMap {
+    width: u32,
+    height: u32,
+    heights: Vec<f32>,
+}
+
+impl HeightMap {
+    /// Heights from a 16 bit grayscale image, keeping its full precision
+    pub fn from_gray16(image: &Gray16Image) -> HeightMap {
+        HeightMap {
+            width: image.width(),
+            height: image.height(),
+            heights: image.pixels().map(|pixel| pixel[0] as f32 / u16::MAX as f32).collect(),
+        }
+    }
+
+    /// Heights from the luminance of any image
+    pub fn from_rgba(image: &RgbaImage) -> HeightMap {
+        HeightMap {
+            width: image.width(),
+            height: image.height(),
+            heights: image.pixels()
+                .map(|pixel| (0.2126 * pixel[0] as f32 + 0.7152 * pixel[1] as f32 + 0.0722 * pixel[2] as f32) / 255.0)
+                .collect(),
+        }
+    }
+
+    fn at(&self, x: i64, y: i64) -> f32 {
+        let x = x.clamp(0, self.width as i64 - 1) as u32;
+        let y = y.clamp(0, self.height as i64 - 1) as u32;
+        self.heights[(y * self.width + x) as usize]
+    }
+
+    /// Shade the terrain as if lit by a low sun, which shows slopes and ridges that are invisible as flat gray.
+    /// The exaggeration scales the heights, use more for flat terrain.
+    pub fn hillshade(&self, exaggeration: f32) -> GrayImage {
+        let z_factor = exaggeration * RELIEF_HEIGHT * self.width as f32;
+        let zenith = (90.0 - SUN_ALTITUDE).to_radians();
+        let azimuth = (360.0 - SUN_AZIMUTH + 90.0).to_radians();
+        GrayImage::from_fn(self.width, self.height, |x, y| {
+            let (x, y) = (x as i64, y as i64);
+            // Horn's method: the slope in each direction from the 3x3 neighborhood
+            let dx = ((self.at(x + 1, y - 1) + 2.0 * self.at(x + 1, y) + self.at(x + 1, y + 1))
+                - (self.at(x - 1, y - 1) + 2.0 * self.at(x - 1, y) + self.at(x - 1, y + 1))) / 8.0 * z_factor;
+            let dy = ((self.at(x - 1, y + 1) + 2.0 * self.at(x, y + 1) + self.at(x + 1, y + 1))
+                - (self.at(x - 1, y - 1) + 2.0 * self.at(x, y - 1) + self.at(x + 1, y - 1))) / 8.0 * z_factor;
+            let slope = (dx * dx + dy * dy).sqrt().atan();
+            let aspect = dy.atan2(-dx);
+            let shade = zenith.cos() * slope.cos() + zenith.sin() * slope.sin() * (azimuth - aspect).cos();
+            Luma([(shade.max(0.0) * 255.0).round() as u8])
+        })
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use fltk::{button::Button, enums::Align, frame::Frame, group::{Pack, Scroll}, prelude::*, window::Window};
+
+const PANEL_WIDTH: i32 = 560;
+const PANEL_HEIGHT: i32 = 400;
+const ROW_HEIGHT: i32 = 30;
+const BUTTON_WIDTH: i32 = 80;
+const BUTTON_HEIGHT: i32 = 25;
+
+/// Where an image went when it was removed from the folder
+#[derive(Clone, Debug, PartialEq)]
+pub enum Removal {
+    Trashed,
+    Moved(PathBuf),
+}
+
+/// An image that was deleted or moved away during this session
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemovedFile {
+    pub original: PathBuf,
+    pub removal: Removal,
+    pub is_restored: bool,
+}
+
+impl RemovedFile {
+    pub fn trashed(original: &Path) -> RemovedFile {
+        RemovedFile { original: original.to_path_buf(), removal: Removal::Trashed, is_restored: false }
+    }
+
+    pub fn moved(original: &Path, target: PathBuf) -> RemovedFile {
+        RemovedFile { original: original.to_path_buf(), removal: Removal::Moved(target), is_restored: false }
+    }
+
+    fn describe(&self) -> String {
+        let name = self.original.file_name().unwrap_or_default().to_string_lossy();
+        match &self.removal {
+            Removal::Trashed => format!("{}  (recycle bin)", name),
+            Removal::Moved(target) => format!("{}  (moved to {})", name, target.parent().unwrap_or(target).display()),
+        }
+    }
+
+    /// Put the image back where it was
+    pub fn restore(&mut self) -> Result<(), String> {
+        if self.original.exists() {
+            return Err(format!("{} already exists", self.original.display()));
+        }
+        match &self.removal {
+            Removal::Moved(target) => {
+                if fs::rename(target, &self.original).is_err() {
+                    fs::copy(target, &self.original).map_err(|err| format!("Failed to copy {} back: {}", target.display(), err))?;
+                    fs::remove_file(target).map_err(|err| format!("Failed to remove {} after copying it back: {}", target.display(), err))?;
+                }
+            }
+            Removal::Trashed => restore_from_trash(&self.original)?,
+        }
+        log::debug!("Restored {}", self.original.display());
+        self.is_restored = true;
+        Ok(())
+    }
+}
+
+/// Delete a file to the recycle bin, so it can be restored
+pub fn move_to_trash(path: &Path) -> Result<(), String> {
+    trash::delete(path).map_err(|err| format!("Failed to move {} to the recycle bin: {}", path.display(), err))
+}
+
+#[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))))]
+fn restore_from_trash(original: &Path) -> Result<(), String> {
+    let items = trash::os_limited::list().map_err(|err| format!("Failed to read the recycle bin: {}", err))?;
+    // The most recently deleted file of that name, in case it was deleted more than once
+    let item = items.into_iter()
+        .filter(|item| item.original_path() == original)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or(format!("{} is no longer in the recycle bin", original.display()))?;
+    trash::os_limited::restore_all([item]).map_err(|err| format!("Failed to restore {}: {}", original.display(), err))
+}
+
+#[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))))]
+fn restore_from_trash(original: &Path) -> Result<(), String> {
+    Err(format!("Restoring from the trash is not supported on this system, {} has to be put back from the Finder", original.display()))
+}
+
+fn mark_restored(button: &mut Button) {
+    button.set_label("Restored");
+    button.deactivate();
+}
+
+/// Show the images removed this session, each with a button to restore it, and one to restore them all
+pub fn show_panel(removed_files: Rc<RefCell<Vec<RemovedFile>>>) {
+    let count = removed_files.borrow().len();
+    let mut panel = Window::default().with_size(PANEL_WIDTH, PANEL_HEIGHT).with_label("Removed this session");
+    let scroll = Scroll::new(0, 0, PANEL_WIDTH, PANEL_HEIGHT - BUTTON_HEIGHT - 20, None);
+    let mut rows = Pack::new(0, 0, PANEL_WIDTH - 20, ROW_HEIGHT * count.max(1) as i32, None);
+    if count == 0 {
+        Frame::default().with_size(PANEL_WIDTH - 20, ROW_HEIGHT).with_label("No images were deleted or moved away yet");
+    }
+    let mut restore_buttons: Vec<(usize, Button)> = Vec::new();
+    // Most recent first, as those are the likely mistakes
+    for index in (0..count).rev() {
+        let file = removed_files.borrow()[index].clone();
+        let row = Pack::default().with_size(PANEL_WIDTH - 20, ROW_HEIGHT).with_type(fltk::group::PackType::Horizontal);
+        let mut name = Frame::default().with_size(PANEL_WIDTH - 20 - BUTTON_WIDTH, ROW_HEIGHT).with_label(&file.describe());
+        name.set_align(Align::Left | Align::Inside | Align::Clip);
+        let mut button = Button::default().with_size(BUTTON_WIDTH, ROW_HEIGHT).with_label("Restore");
+        if file.is_restored {
+            mark_restored(&mut button);
+        }
+        let button_files = Rc::clone(&removed_files);
+        button.set_callback(move |button| {
+            let result = button_files.borrow_mut()[index].restore();
+            match result {
+                Ok(()) => mark_restored(button),
+                Err(err) => fltk::dialog::alert_default(&err),
+            }
+        });
+        restore_buttons.push((index, button));
+        row.end();
+    }
+    rows.end();
+    rows.set_spacing(0);
+    scroll.end();
+    let mut restore_all_button = Button::new(PANEL_WIDTH - 130, PANEL_HEIGHT - BUTTON_HEIGHT - 10, 120, BUTTON_HEIGHT, "Restore all");
+    if count == 0 {
+        restore_all_button.deactivate();
+    }
+    restore_all_button.set_callback(move |_| {
+        let mut errors = Vec::new();
+        for (index, button) in restore_buttons.iter_mut() {
+            let mut removed_files = removed_files.borrow_mut();
+            if removed_files[*index].is_restored {
+                continue;
+            }
+            match removed_files[*index].restore() {
+                Ok(()) => mark_restored(button),
+                Err(err) => errors.push(err),
+            }
+        }
+        if !errors.is_empty() {
+            fltk::dialog::alert_default(&errors.join("\n"));
+        }
+    });
+    panel.end();
+    panel.show();
+}
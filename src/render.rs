@@ -0,0 +1,272 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use image::{imageops, ImageFormat, RgbImage, Rgba, RgbaImage};
+use rustronomy_fits as rsf;
+
+use crate::{analysis, preview, rotation::{self, Rotations}, target::Target, thumbnails, FITS_SUPPORTED_FORMATS, RAW_SUPPORTED_FORMATS};
+
+// Colors of the target frame annotation, the same as in the viewer's overlay
+const CROP_COLOR: Rgba<u8> = Rgba([255, 255, 0, 255]);
+const SAFE_AREA_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+// Length of the dashes of the safe area frame, in pixels
+const DASH_LENGTH: u32 = 4;
+
+// Polynomial fits of the matplotlib colormaps, coefficients from the constant term up
+const VIRIDIS: [[f32; 3]; 7] = [
+    [0.277_727_33, 0.005_407_345, 0.334_099_8],
+    [0.105_093_04, 1.404_613_5, 1.384_590_2],
+    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+    [-4.634_230_5, -5.799_101, -19.332_441],
+    [6.228_27, 14.179_933, 56.690_55],
+    [4.776_385, -13.745_145, -65.353_035],
+    [-5.435_456, 4.645_852_6, 26.312_435],
+];
+const INFERNO: [[f32; 3]; 7] = [
+    [0.000_218_940_37, 0.001_651_004_6, -0.019_480_899],
+    [0.106_513_42, 0.563_956_45, 3.932_712_4],
+    [11.602_493, -3.972_854, -15.942_394],
+    [-41.703_995, 17.436_4, 44.354_145],
+    [77.162_94, -33.402_36, -81.807_31],
+    [-71.319_43, 32.626_064, 73.209_52],
+    [25.131_126, -12.242_669, -23.070_325],
+];
+
+/// How data values are mapped to brightness, mostly for the wide range of astronomical images
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stretch {
+    Linear,
+    Sqrt,
+    Log,
+    Asinh,
+}
+
+impl Stretch {
+    pub fn parse(text: &str) -> Option<Stretch> {
+        match text.to_lowercase().as_str() {
+            "linear" => Some(Stretch::Linear),
+            "sqrt" => Some(Stretch::Sqrt),
+            "log" => Some(Stretch::Log),
+            "asinh" => Some(Stretch::Asinh),
+            _ => None,
+        }
+    }
+
+    /// Map a value normalized to 0..1 to a brightness of 0..1
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            Stretch::Linear => value,
+            Stretch::Sqrt => value.sqrt(),
+            Stretch::Log => (1.0 + 1000.0 * value).log10() / 1001.0_f32.log10(),
+            Stretch::Asinh => (10.0 * value).asinh() / 10.0_f32.asinh(),
+        }
+    }
+}
+
+/// Colors that brightness is shown with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Colormap {
+    Gray,
+    Viridis,
+    Inferno,
+}
+
+impl Colormap {
+    pub fn parse(text: &str) -> Option<Colormap> {
+        match text.to_lowercase().as_str() {
+            "gray" | "grey" => Some(Colormap::Gray),
+            "viridis" => Some(Colormap::Viridis),
+            "inferno" => Some(Colormap::Inferno),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self, value: f32) -> Rgba<u8> {
+        let value = value.clamp(0.0, 1.0);
+        let coefficients = match self {
+            Colormap::Gray => {
+                let gray = (value * 255.0).round() as u8;
+                return Rgba([gray, gray, gray, 255]);
+            }
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Inferno => &INFERNO,
+        };
+        let channel = |index: usize| {
+            let level = coefficients.iter().rev().fold(0.0, |sum, coefficient| sum * value + coefficient[index]);
+            (level.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        Rgba([channel(0), channel(1), channel(2), 255])
+    }
+}
+
+/// What to render for `render`, the image is decoded, stretched, colored, rotated as remembered in rotation
+/// sidecars and fitted into the size on black exactly like the viewer scales to fit
+#[derive(Args, Debug)]
+pub struct RenderOptions {
+    /// Image file to render
+    pub input: PathBuf,
+
+    /// File to write, in the format of its extension
+    #[arg(long = "out", short)]
+    pub output: PathBuf,
+
+    /// How values map to brightness: linear, sqrt, log or asinh. Without a stretch or colormap, the image is
+    /// rendered exactly as the viewer decodes it
+    #[arg(long, value_parser = parse_stretch)]
+    pub stretch: Option<Stretch>,
+
+    /// Colors brightness is shown with: gray, viridis or inferno
+    #[arg(long, value_parser = parse_colormap)]
+    pub colormap: Option<Colormap>,
+
+    /// Size of the window the image is fitted into, like the viewer scaled to fit, instead of the image's own size
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    pub size: Option<(u32, u32)>,
+
+    /// Output format whose crop is drawn on top of the image
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_target)]
+    pub target: Option<Target>,
+}
+
+fn parse_stretch(text: &str) -> Result<Stretch, String> {
+    Stretch::parse(text).ok_or(format!("unknown stretch \"{}\", use linear, sqrt, log or asinh", text))
+}
+
+fn parse_colormap(text: &str) -> Result<Colormap, String> {
+    Colormap::parse(text).ok_or(format!("unknown colormap \"{}\", use gray, viridis or inferno", text))
+}
+
+fn parse_size(text: &str) -> Result<(u32, u32), String> {
+    parse_target(text).map(|window| (window.width, window.height))
+}
+
+fn parse_target(text: &str) -> Result<Target, String> {
+    Target::parse(text).ok_or(format!("invalid size \"{}\", use WIDTHxHEIGHT", text))
+}
+
+// Values of the FITS image normalized to 0..1, row by row
+fn load_fits_values(image_file: &Path) -> Result<(u32, u32, Vec<f32>), String> {
+    let mut fits = rsf::Fits::open(image_file).map_err(|err| format!("Error opening {}: {}", image_file.display(), err))?;
+    let (_, data) = fits.remove_hdu(1).ok_or("No image data found".to_string())?.to_parts();
+    let array = match data {
+        Some(rsf::Extension::Image(image)) => image.as_owned_f32_array().map_err(|err| format!("Error reading array: {}", err))?,
+        _ => return Err("No image data found".to_string()),
+    };
+    let shape = array.shape().to_vec();
+    if shape.len() < 2 {
+        return Err(format!("{} has no two-dimensional image", image_file.display()));
+    }
+    let (height, width) = (shape[0], shape[1]);
+    let min = array.fold(f32::INFINITY, |a, &b| a.min(b));
+    let max = array.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+    let range = (max - min).max(f32::EPSILON);
+    let values = array.iter().take(width * height).map(|value| (value - min) / range).collect();
+    Ok((width as u32, height as u32, values))
+}
+
+// The image as the viewer decodes it, or with the stretch and colormap applied to its values instead
+fn load(input: &Path, stretch: Option<Stretch>, colormap: Option<Colormap>) -> Result<RgbaImage, String> {
+    if stretch.is_none() && colormap.is_none() {
+        return Ok(analysis::decode_image(input)?.to_rgba8());
+    }
+    let stretch = stretch.unwrap_or(Stretch::Linear);
+    let filename = input.to_string_lossy().to_lowercase();
+    if FITS_SUPPORTED_FORMATS.iter().any(|&format| filename.ends_with(format)) {
+        // The viewer's decode is stretched already, so the stretch starts from the values in the file
+        let (width, height, values) = load_fits_values(input)?;
+        let colormap = colormap.unwrap_or(Colormap::Gray);
+        return Ok(RgbaImage::from_fn(width, height, |x, y| colormap.color(stretch.apply(values[(y * width + x) as usize]))));
+    }
+    let gray = analysis::decode_image(input)?.to_luma32f();
+    let colormap = colormap.unwrap_or(Colormap::Gray);
+    Ok(RgbaImage::from_fn(gray.width(), gray.height(), |x, y| colormap.color(stretch.apply(gray.get_pixel(x, y)[0]))))
+}
+
+// The image decoded for a thumbnail of the size, taking the shortcuts of the viewer's quick preview where they
+// give enough pixels: JPEG images decoded at a fraction of their size, and the previews embedded in RAW files
+fn load_for_thumbnail(input: &Path, size: u32) -> Result<RgbaImage, String> {
+    let filename = input.to_string_lossy().to_lowercase();
+    let preview = if filename.ends_with("jpg") || filename.ends_with("jpeg") {
+        Some(preview::jpeg_file(input, size as i32, size as i32))
+    } else if RAW_SUPPORTED_FORMATS.iter().any(|&format| filename.ends_with(format)) {
+        Some(preview::raw_preview(input, size as i32, size as i32))
+    } else {
+        None
+    };
+    match preview {
+        Some(Ok(preview)) if preview.width.max(preview.height) as u32 >= size => {
+            let image = RgbImage::from_raw(preview.width as u32, preview.height as u32, preview.data).ok_or("Invalid preview size".to_string())?;
+            return Ok(image::DynamicImage::ImageRgb8(image).into_rgba8());
+        }
+        Some(Ok(_)) => log::debug!("The preview of {} is too small for the thumbnail", input.display()),
+        Some(Err(err)) => log::debug!("{}", err),
+        None => {}
+    }
+    load(input, None, None)
+}
+
+// Scale the image to fit the window and center it on black, like the viewer does when scaling to fit.
+// At the image's own size, this only fills transparent parts with black.
+// Returns the placement of the image as (x, y, scale) for drawing annotations.
+fn fit(image: &RgbaImage, width: u32, height: u32) -> (RgbaImage, (f64, f64, f64)) {
+    let scale = (width as f64 / image.width() as f64).min(height as f64 / image.height() as f64);
+    let scaled_width = ((image.width() as f64 * scale).round() as u32).clamp(1, width);
+    let scaled_height = ((image.height() as f64 * scale).round() as u32).clamp(1, height);
+    let scaled = imageops::resize(image, scaled_width, scaled_height, imageops::FilterType::Triangle);
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    let (x, y) = ((width - scaled_width) / 2, (height - scaled_height) / 2);
+    imageops::overlay(&mut canvas, &scaled, x as i64, y as i64);
+    (canvas, (x as f64, y as f64, scale))
+}
+
+fn draw_rect(image: &mut RgbaImage, (x, y, width, height): (f64, f64, f64, f64), color: Rgba<u8>, is_dashed: bool) {
+    let (left, top) = (x.round().max(0.0) as u32, y.round().max(0.0) as u32);
+    let right = ((x + width).round() as u32).min(image.width()).saturating_sub(1);
+    let bottom = ((y + height).round() as u32).min(image.height()).saturating_sub(1);
+    let is_drawn = |position: u32| !is_dashed || (position / DASH_LENGTH) % 2 == 0;
+    for px in (left..=right).filter(|&px| is_drawn(px)) {
+        image.put_pixel(px, top, color);
+        image.put_pixel(px, bottom, color);
+    }
+    for py in (top..=bottom).filter(|&py| is_drawn(py)) {
+        image.put_pixel(left, py, color);
+        image.put_pixel(right, py, color);
+    }
+}
+
+/// Render the image offscreen the way the viewer shows it and save the result
+pub fn render(options: &RenderOptions, is_using_sidecars: bool) -> Result<(), String> {
+    let mut image = load(&options.input, options.stretch, options.colormap)?;
+    let turns = Rotations::new(is_using_sidecars).get(&options.input);
+    if turns > 0 {
+        image = rotation::rotate_image(&image, turns);
+    }
+    let (data_width, data_height) = image.dimensions();
+    // Transparent parts show the black window background
+    let (width, height) = options.size.unwrap_or((data_width, data_height));
+    let (mut image, (offset_x, offset_y, scale)) = fit(&image, width, height);
+    if let Some(target) = options.target {
+        let to_output = |(x, y, width, height): (f64, f64, f64, f64)| (offset_x + x * scale, offset_y + y * scale, width * scale, height * scale);
+        let crop = target.crop(data_width as f64, data_height as f64);
+        draw_rect(&mut image, to_output(crop), CROP_COLOR, false);
+        draw_rect(&mut image, to_output(Target::safe_area(crop)), SAFE_AREA_COLOR, true);
+    }
+    image::DynamicImage::ImageRgba8(image).to_rgb8().save(&options.output).map_err(|err| format!("Failed to save {}: {}", options.output.display(), err))?;
+    log::debug!("Rendered {} to {}", options.input.display(), options.output.display());
+    Ok(())
+}
+
+/// Write a PNG thumbnail of the image at most `size` pixels wide and high, decoded like the viewer
+/// decodes it and turned like the viewer shows it. Thumbnails are shared with the viewer and other
+/// programs through the thumbnail cache.
+pub fn thumbnail(input: &Path, output: &Path, size: u32, is_using_sidecars: bool) -> Result<(), String> {
+    let mut image = thumbnails::get_or_create(input, size, |path| load_for_thumbnail(path, thumbnails::stored_size(size)))?;
+    let turns = Rotations::new(is_using_sidecars).get(input);
+    if turns > 0 {
+        image = rotation::rotate_image(&image, turns);
+    }
+    image.save_with_format(output, ImageFormat::Png).map_err(|err| format!("Failed to save {}: {}", output.display(), err))?;
+    log::debug!("Wrote a thumbnail of {} to {}", input.display(), output.display());
+    Ok(())
+}
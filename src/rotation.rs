@@ -0,0 +1,82 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::{imageops, RgbaImage};
+
+// Rotations of the images in a directory, kept next to them when sidecars are enabled
+const SIDECAR_FILENAME: &str = "lightningview_rotations.toml";
+
+/// Quarter turns clockwise the user rotated each image by, remembered for the session
+/// and optionally stored in a sidecar file in the image's directory
+pub struct Rotations {
+    turns: HashMap<PathBuf, u8>,
+    is_using_sidecars: bool,
+}
+
+fn sidecar_path(path: &Path) -> Option<PathBuf> {
+    Some(path.parent()?.join(SIDECAR_FILENAME))
+}
+
+// File name to quarter turns for all rotated images of the directory
+fn read_sidecar(sidecar: &Path) -> BTreeMap<String, u8> {
+    fs::read_to_string(sidecar).ok()
+        .and_then(|contents| toml::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+impl Rotations {
+    pub fn new(is_using_sidecars: bool) -> Rotations {
+        Rotations { turns: HashMap::new(), is_using_sidecars }
+    }
+
+    /// Quarter turns clockwise for the image, 0 if it wasn't rotated
+    pub fn get(&self, path: &Path) -> u8 {
+        if let Some(&turns) = self.turns.get(path) {
+            return turns;
+        }
+        if !self.is_using_sidecars {
+            return 0;
+        }
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        sidecar_path(path)
+            .and_then(|sidecar| read_sidecar(&sidecar).get(&name).copied())
+            .unwrap_or(0) % 4
+    }
+
+    /// Turn the image a quarter clockwise or counter-clockwise, returning its new rotation
+    pub fn rotate(&mut self, path: &Path, is_clockwise: bool) -> u8 {
+        let turns = (self.get(path) + if is_clockwise { 1 } else { 3 }) % 4;
+        self.turns.insert(path.to_path_buf(), turns);
+        if self.is_using_sidecars {
+            if let Err(err) = self.save_sidecar(path, turns) {
+                log::error!("{}", err);
+            }
+        }
+        turns
+    }
+
+    fn save_sidecar(&self, path: &Path, turns: u8) -> Result<(), String> {
+        let sidecar = sidecar_path(path).ok_or("Image has no directory".to_string())?;
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut rotations = read_sidecar(&sidecar);
+        if turns == 0 {
+            rotations.remove(&name);
+        } else {
+            rotations.insert(name, turns);
+        }
+        let contents = toml::to_string(&rotations).map_err(|err| format!("Failed to serialize rotations: {}", err))?;
+        fs::write(&sidecar, contents).map_err(|err| format!("Failed to save {}: {}", sidecar.display(), err))
+    }
+}
+
+pub fn rotate_image(image: &RgbaImage, turns: u8) -> RgbaImage {
+    match turns % 4 {
+        1 => imageops::rotate90(image),
+        2 => imageops::rotate180(image),
+        3 => imageops::rotate270(image),
+        _ => image.clone(),
+    }
+}
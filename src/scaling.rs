@@ -0,0 +1,126 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Mutex};
+
+use fltk::image::RgbScaling;
+use image::{imageops, RgbaImage};
+
+// Below this scale of displayed to image pixels, the automatic filter pre-scales with Lanczos
+const LANCZOS_BELOW: f64 = 0.5;
+// Blur radius and minimum brightness difference of the unsharp mask for shrunk images, kept light
+// so it brings back the crispness lost by shrinking without halos
+const SHARPEN_SIGMA: f32 = 0.5;
+const SHARPEN_THRESHOLD: i32 = 2;
+
+// FLTK's scaling algorithm is global as well, so the choice lives next to it instead of being passed around
+static FILTER: Mutex<ScalingFilter> = Mutex::new(ScalingFilter::Auto);
+static IS_SHARPENING: AtomicBool = AtomicBool::new(false);
+
+/// How images are resampled when they're shown larger or smaller than their own size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScalingFilter {
+    // Picked for each image and zoom level from how much it's scaled
+    Auto,
+    Nearest,
+    Bilinear,
+    Lanczos,
+}
+
+impl ScalingFilter {
+    pub fn parse(text: &str) -> Option<ScalingFilter> {
+        match text.to_lowercase().as_str() {
+            "auto" => Some(ScalingFilter::Auto),
+            "nearest" => Some(ScalingFilter::Nearest),
+            "bilinear" => Some(ScalingFilter::Bilinear),
+            "lanczos" => Some(ScalingFilter::Lanczos),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScalingFilter::Auto => "auto",
+            ScalingFilter::Nearest => "nearest",
+            ScalingFilter::Bilinear => "bilinear",
+            ScalingFilter::Lanczos => "lanczos",
+        }
+    }
+
+    // Cycle through the filters with the keyboard
+    pub fn next(&self) -> ScalingFilter {
+        match self {
+            ScalingFilter::Auto => ScalingFilter::Nearest,
+            ScalingFilter::Nearest => ScalingFilter::Bilinear,
+            ScalingFilter::Bilinear => ScalingFilter::Lanczos,
+            ScalingFilter::Lanczos => ScalingFilter::Auto,
+        }
+    }
+
+    /// The filter to use at a scale of displayed to image pixels: sharp pixels when magnified,
+    /// like for pixel art, and Lanczos for big downscales that would alias with bilinear filtering
+    pub fn resolve(&self, scale: f64) -> ScalingFilter {
+        match self {
+            ScalingFilter::Auto if scale > 1.0 => ScalingFilter::Nearest,
+            ScalingFilter::Auto if scale < LANCZOS_BELOW => ScalingFilter::Lanczos,
+            ScalingFilter::Auto => ScalingFilter::Bilinear,
+            // Lanczos only pays off when shrinking
+            ScalingFilter::Lanczos if scale >= 1.0 => ScalingFilter::Bilinear,
+            filter => *filter,
+        }
+    }
+
+    /// FLTK's own scaling for the filter, Lanczos images are pre-scaled so they aren't resampled by FLTK
+    pub fn rgb_scaling(&self) -> RgbScaling {
+        match self {
+            ScalingFilter::Nearest => RgbScaling::Nearest,
+            _ => RgbScaling::Bilinear,
+        }
+    }
+}
+
+pub fn filter() -> ScalingFilter {
+    FILTER.lock().map(|filter| *filter).unwrap_or(ScalingFilter::Auto)
+}
+
+pub fn set_filter(filter: ScalingFilter) {
+    if let Ok(mut current) = FILTER.lock() {
+        *current = filter;
+    }
+}
+
+pub fn is_sharpening() -> bool {
+    IS_SHARPENING.load(Ordering::Relaxed)
+}
+
+/// Switch sharpening of shrunk images on or off for the session
+pub fn set_sharpening(is_sharpening: bool) {
+    IS_SHARPENING.store(is_sharpening, Ordering::Relaxed);
+}
+
+/// Whether showing the image at the scale needs a resized copy, instead of FLTK's bilinear scaling while drawing
+pub fn needs_resample(scale: f64) -> bool {
+    filter().resolve(scale) != ScalingFilter::Bilinear || (is_sharpening() && scale < 1.0)
+}
+
+/// Resize the image with the filter resolved for the scale, sharpening it if it's shrunk and sharpening is on
+pub fn resize(image: &RgbaImage, width: u32, height: u32, filter: ScalingFilter) -> RgbaImage {
+    let resized = match filter {
+        ScalingFilter::Lanczos => lanczos(image, width, height),
+        ScalingFilter::Nearest => imageops::resize(image, width.max(1), height.max(1), imageops::FilterType::Nearest),
+        _ => imageops::resize(image, width.max(1), height.max(1), imageops::FilterType::Triangle),
+    };
+    if is_sharpening() && width < image.width() {
+        imageops::unsharpen(&resized, SHARPEN_SIGMA, SHARPEN_THRESHOLD)
+    } else {
+        resized
+    }
+}
+
+/// Shrink the image with a Lanczos filter. Very large reductions are first averaged down to twice
+/// the size, which is much faster and looks the same.
+fn lanczos(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let (width, height) = (width.max(1), height.max(1));
+    if image.width() > width * 2 && image.height() > height * 2 {
+        let averaged = imageops::thumbnail(image, width * 2, height * 2);
+        return imageops::resize(&averaged, width, height, imageops::FilterType::Lanczos3);
+    }
+    imageops::resize(image, width, height, imageops::FilterType::Lanczos3)
+}
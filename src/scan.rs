@@ -0,0 +1,271 @@
+use std::{
+    collections::HashSet,
+    fs::{self, DirEntry},
+    io, mem,
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex},
+    thread,
+    time::UNIX_EPOCH,
+};
+
+use fltk::app;
+
+// Custom FLTK event sent to the main window when the directory scan found more images or is done
+pub const SCAN_EVENT: i32 = 49;
+// How often found images are handed over to the image list, in seconds
+const SCAN_POLL_INTERVAL: f64 = 0.2;
+
+static SORT_MODE: Mutex<SortMode> = Mutex::new(SortMode::Name);
+static IS_RECURSIVE: AtomicBool = AtomicBool::new(false);
+
+/// The order images are listed in, which browsing follows unless they're shuffled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortMode {
+    Name,
+    // Oldest first
+    Date,
+    // Smallest first
+    Size,
+}
+
+impl SortMode {
+    pub fn parse(text: &str) -> Option<SortMode> {
+        match text.to_lowercase().as_str() {
+            "name" => Some(SortMode::Name),
+            "date" => Some(SortMode::Date),
+            "size" => Some(SortMode::Size),
+            _ => None,
+        }
+    }
+}
+
+pub fn set_sort_mode(mode: SortMode) {
+    if let Ok(mut current) = SORT_MODE.lock() {
+        *current = mode;
+    }
+}
+
+/// List the images of subfolders too, set for the --recursive argument
+pub fn set_recursive(is_recursive: bool) {
+    IS_RECURSIVE.store(is_recursive, Ordering::Relaxed);
+}
+
+pub fn is_recursive() -> bool {
+    IS_RECURSIVE.load(Ordering::Relaxed)
+}
+
+#[derive(Default)]
+struct ScanProgress {
+    // Images found since the last update with their sort keys, which take a file system query each in date
+    // and size order and so are worked out on the scan thread
+    found: Vec<(SortKey, PathBuf)>,
+    is_done: bool,
+    error: Option<String>,
+}
+
+/// Lists the images of a directory on a worker thread, so huge folders and slow network shares
+/// don't hold up the viewer. With the image being shown given, the images are handed over in
+/// batches while the scan runs, otherwise once the whole directory has been read.
+pub struct DirectoryScan {
+    pub directory: PathBuf,
+    shown: Option<(SortKey, PathBuf)>,
+    // Images found so far with their sort keys, kept sorted so new batches are merged in instead of sorting everything again
+    files: Vec<(SortKey, PathBuf)>,
+    seen: HashSet<PathBuf>,
+    progress: Arc<Mutex<ScanProgress>>,
+    is_stopped: Arc<AtomicBool>,
+    poll_timeout: app::TimeoutHandle,
+}
+
+/// Whether the directory entry is a file, which only takes an extra file system query for links
+/// as most systems tell the type along with the name
+pub fn is_file(entry: &DirEntry) -> bool {
+    match entry.file_type() {
+        Ok(file_type) if !file_type.is_symlink() => file_type.is_file(),
+        _ => entry.path().is_file(),
+    }
+}
+
+// Key the image list is sorted by: the modification time or size if sorted by those, then the name case insensitive
+fn sort_key(path: &Path) -> SortKey {
+    let mode = SORT_MODE.lock().map(|mode| *mode).unwrap_or(SortMode::Name);
+    let value = match mode {
+        SortMode::Name => 0,
+        SortMode::Date => fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |age| age.as_millis() as u64),
+        SortMode::Size => fs::metadata(path).map_or(0, |metadata| metadata.len()),
+    };
+    (value, path.to_string_lossy().to_lowercase())
+}
+
+type SortKey = (u64, String);
+
+/// Sort the images in the order they're listed in
+pub fn sort(files: &mut Vec<PathBuf>) {
+    files.sort_by_cached_key(|path| sort_key(path));
+}
+
+// Merge the sorted batch into the sorted files, files already there go first among equal keys
+fn merge_sorted(files: Vec<(SortKey, PathBuf)>, batch: Vec<(SortKey, PathBuf)>) -> Vec<(SortKey, PathBuf)> {
+    let mut merged = Vec::with_capacity(files.len() + batch.len());
+    let mut batch = batch.into_iter().peekable();
+    for file in files {
+        while let Some(next) = batch.next_if(|next| next.0 < file.0) {
+            merged.push(next);
+        }
+        merged.push(file);
+    }
+    merged.extend(batch);
+    merged
+}
+
+/// Go through the images of the directory, and of its subfolders when listing recursively. Linked
+/// folders are skipped, so links pointing back up don't make it go around in circles. Subfolders
+/// that can't be read are left out, only an unreadable directory itself is an error.
+pub fn walk(directory: &Path, is_image: fn(&Path) -> bool, is_stopped: &AtomicBool, mut found: impl FnMut(PathBuf)) -> io::Result<()> {
+    let is_recursive = is_recursive();
+    let mut folders = vec![directory.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        let entries = match fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(err) if folder == directory => return Err(err),
+            Err(err) => {
+                log::debug!("Skipping {}: {}", folder.display(), err);
+                continue;
+            }
+        };
+        for entry in entries {
+            if is_stopped.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let path = entry.path();
+            if is_recursive && entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                folders.push(path);
+            } else if is_image(&path) && is_file(&entry) {
+                found(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn scan(directory: &Path, is_image: fn(&Path) -> bool, progress: &Mutex<ScanProgress>, is_stopped: &AtomicBool) -> Result<(), String> {
+    walk(directory, is_image, is_stopped, |path| {
+        let key = sort_key(&path);
+        if let Ok(mut progress) = progress.lock() {
+            progress.found.push((key, path));
+        }
+    })
+    .map_err(|err| err.to_string())
+}
+
+impl DirectoryScan {
+    /// Start listing the images in the directory, `is_image` tells which files to keep
+    pub fn start(directory: &Path, shown: Option<&Path>, is_image: fn(&Path) -> bool) -> DirectoryScan {
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let is_stopped = Arc::new(AtomicBool::new(false));
+
+        let scan_directory = directory.to_path_buf();
+        let scan_progress = Arc::clone(&progress);
+        let scan_stopped = Arc::clone(&is_stopped);
+        thread::spawn(move || {
+            let result = scan(&scan_directory, is_image, &scan_progress, &scan_stopped);
+            if let Ok(mut progress) = scan_progress.lock() {
+                progress.error = result.err();
+                progress.is_done = true;
+            }
+        });
+
+        let is_incremental = shown.is_some();
+        let poll_progress = Arc::clone(&progress);
+        let poll_timeout = app::add_timeout3(SCAN_POLL_INTERVAL, move |handle| {
+            let (has_news, is_done) = poll_progress.lock()
+                .map(|progress| ((is_incremental && !progress.found.is_empty()) || progress.is_done, progress.is_done))
+                .unwrap_or((true, true));
+            if has_news {
+                let _ = app::handle_main(SCAN_EVENT);
+            }
+            if !is_done {
+                app::repeat_timeout3(SCAN_POLL_INTERVAL, handle);
+            }
+        });
+        DirectoryScan {
+            directory: directory.to_path_buf(),
+            shown: shown.map(|shown| (sort_key(shown), shown.to_path_buf())),
+            files: Vec::new(),
+            seen: HashSet::new(),
+            progress,
+            is_stopped,
+            poll_timeout,
+        }
+    }
+
+    /// All images found so far in the order they're listed in, and whether the scan is done. The image being shown
+    /// stays in the list until the scan is done, as it may simply not have been found yet.
+    pub fn take_update(&mut self) -> Option<(Result<Vec<PathBuf>, String>, bool)> {
+        let (found, is_done, error) = {
+            let mut progress = self.progress.lock().ok()?;
+            if progress.found.is_empty() && !progress.is_done {
+                return None;
+            }
+            (mem::take(&mut progress.found), progress.is_done, progress.error.take())
+        };
+        if let Some(err) = error {
+            return Some((Err(err), true));
+        }
+        // Only the new images are sorted
+        let mut batch: Vec<(SortKey, PathBuf)> = found.into_iter()
+            .filter(|(_, path)| self.seen.insert(path.clone()))
+            .collect();
+        batch.sort_by(|a, b| a.0.cmp(&b.0));
+        self.files = merge_sorted(mem::take(&mut self.files), batch);
+
+        let mut files: Vec<PathBuf> = self.files.iter().map(|(_, path)| path.clone()).collect();
+        if let Some((key, shown)) = self.shown.as_ref().filter(|(_, shown)| !is_done && !self.seen.contains(shown)) {
+            files.insert(self.files.partition_point(|(file_key, _)| file_key <= key), shown.clone());
+        }
+        Some((Ok(files), is_done))
+    }
+}
+
+impl Drop for DirectoryScan {
+    fn drop(&mut self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+        app::remove_timeout3(self.poll_timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(size: u64, name: &str) -> (SortKey, PathBuf) {
+        ((size, name.to_string()), PathBuf::from(name))
+    }
+
+    #[test]
+    fn merges_batches_into_the_sorted_files() {
+        let files = vec![entry(0, "a"), entry(0, "c"), entry(0, "e")];
+        let batch = vec![entry(0, "b"), entry(0, "d"), entry(0, "f")];
+        let names: Vec<PathBuf> = merge_sorted(files, batch).into_iter().map(|(_, path)| path).collect();
+        assert_eq!(names, ["a", "b", "c", "d", "e", "f"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn keeps_files_found_earlier_first_among_equal_keys() {
+        let files = vec![((1, "x".to_string()), PathBuf::from("first"))];
+        let batch = vec![((1, "x".to_string()), PathBuf::from("second")), entry(0, "a")];
+        let names: Vec<PathBuf> = merge_sorted(files, batch).into_iter().map(|(_, path)| path).collect();
+        assert_eq!(names, ["first", "second", "a"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn merges_into_and_from_empty_lists() {
+        assert_eq!(merge_sorted(Vec::new(), vec![entry(0, "a")]), vec![entry(0, "a")]);
+        assert_eq!(merge_sorted(vec![entry(0, "a")], Vec::new()), vec![entry(0, "a")]);
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Scanned pages are kept here, so they can be browsed and moved or copied elsewhere like any other file
+fn scan_directory() -> Result<PathBuf, String> {
+    let directory = env::temp_dir().join("lightningview-scans");
+    fs::create_dir_all(&directory).map_err(|err| format!("Failed to create {}: {}", directory.display(), err))?;
+    Ok(directory)
+}
+
+fn scan_path() -> Result<PathBuf, String> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default();
+    Ok(scan_directory()?.join(format!("scan-{}.png", timestamp)))
+}
+
+// Run the scanning command, and check that it actually produced the file
+fn run(command: &mut Command, path: PathBuf) -> Result<PathBuf, String> {
+    let output = command.output().map_err(|err| format!("Failed to start the scanner program: {}", err))?;
+    if !output.status.success() || !path.exists() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if message.is_empty() { "Scanning was cancelled or failed".to_string() } else { message });
+    }
+    log::debug!("Scanned page saved to {}", path.display());
+    Ok(path)
+}
+
+/// Scan a page with the Windows Image Acquisition dialog, which lets the user pick the scanner and settings
+#[cfg(target_os = "windows")]
+pub fn acquire() -> Result<PathBuf, String> {
+    let path = scan_path()?;
+    // WIA is a COM automation API, PowerShell is the simplest way to drive it
+    let script = format!(
+        "$ErrorActionPreference = 'Stop'; \
+         $image = (New-Object -ComObject WIA.CommonDialog).ShowAcquireImage(1, 0, 0, '{{B96B3CAF-0728-11D3-9D7B-0000F81EF32E}}'); \
+         if ($image -eq $null) {{ exit 1 }}; \
+         $image.SaveFile('{}')",
+        path.display().to_string().replace('\'', "''")
+    );
+    run(Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command", &script]), path)
+}
+
+/// Scan a page with SANE's scanimage from the default scanner
+#[cfg(not(target_os = "windows"))]
+pub fn acquire() -> Result<PathBuf, String> {
+    let path = scan_path()?;
+    run(Command::new("scanimage").args(["--format=png", "--resolution=300", "-o"]).arg(&path), path)
+}
@@ -0,0 +1,50 @@
+use std::mem;
+
+use fltk::{app, enums::Key};
+
+// Custom FLTK event sent to the main window when scrubbing through images has stopped
+pub const SCRUB_EVENT: i32 = 50;
+// Time without another skipped image after which scrubbing counts as stopped, in seconds. Longer
+// than the usual key repeat interval, and a safety net for a key release that never arrives.
+const SETTLE_DELAY: f64 = 0.25;
+
+/// Tells when an arrow key is held down to scrub through the folder, so the images passed on the way
+/// only get a cheap placeholder and just the one where the key is let go is fully decoded
+#[derive(Default)]
+pub struct Scrub {
+    held_key: Option<Key>,
+    is_scrubbing: bool,
+    settle_timeout: Option<app::TimeoutHandle>,
+}
+
+impl Scrub {
+    /// Note that a key went down, true if it's the key repeat of a key being held
+    pub fn key_down(&mut self, key: Key) -> bool {
+        let is_repeat = self.held_key == Some(key);
+        self.held_key = Some(key);
+        is_repeat
+    }
+
+    pub fn key_up(&mut self) {
+        self.held_key = None;
+    }
+
+    /// Note that an image was skipped with a placeholder, SCRUB_EVENT follows once no more are skipped for a moment
+    pub fn skip(&mut self) {
+        self.is_scrubbing = true;
+        if let Some(timeout) = self.settle_timeout.take() {
+            app::remove_timeout3(timeout);
+        }
+        self.settle_timeout = Some(app::add_timeout3(SETTLE_DELAY, |_| {
+            let _ = app::handle_main(SCRUB_EVENT);
+        }));
+    }
+
+    /// Whether images were skipped and the current one still needs to be loaded, which it's up to the caller to do
+    pub fn settle(&mut self) -> bool {
+        if let Some(timeout) = self.settle_timeout.take() {
+            app::remove_timeout3(timeout);
+        }
+        mem::take(&mut self.is_scrubbing)
+    }
+}
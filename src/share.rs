@@ -0,0 +1,122 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex},
+    thread,
+};
+
+use fltk::{app, dialog};
+use image::{codecs::jpeg::JpegEncoder, imageops};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{analysis, progress::ProgressDialog};
+
+// Good enough for viewing on any screen, and much smaller than the originals
+const JPEG_QUALITY: u8 = 85;
+// How often the progress dialog is updated, in seconds
+const PROGRESS_INTERVAL: f64 = 0.2;
+
+// Downscale the image to fit the longest edge and encode it as JPEG. The encoder writes
+// no metadata, so location and camera details don't leave with the photo.
+fn prepare(image_file: &Path, max_edge: u32) -> Result<Vec<u8>, String> {
+    let image = analysis::decode_image(image_file)?.to_rgb8();
+    let scale = (max_edge as f64 / image.width().max(image.height()) as f64).min(1.0);
+    let resized = imageops::resize(
+        &image,
+        ((image.width() as f64 * scale) as u32).max(1),
+        ((image.height() as f64 * scale) as u32).max(1),
+        imageops::FilterType::Lanczos3,
+    );
+    let mut data = Cursor::new(Vec::new());
+    JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY)
+        .encode_image(&resized)
+        .map_err(|err| format!("Encoding {} failed: {}", image_file.display(), err))?;
+    Ok(data.into_inner())
+}
+
+// Name in the zip file, with a number added if another image already has it (e.g. photo.png and photo.jpg)
+fn entry_name(image_file: &Path, used_names: &mut HashSet<String>) -> String {
+    let stem = image_file.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or("image".to_string());
+    let mut name = format!("{}.jpg", stem);
+    let mut number = 2;
+    while !used_names.insert(name.to_lowercase()) {
+        name = format!("{}-{}.jpg", stem, number);
+        number += 1;
+    }
+    name
+}
+
+fn write_zip(image_files: &[PathBuf], max_edge: u32, zip_path: &Path, exported_count: &AtomicUsize, is_cancelled: &AtomicBool) -> Result<usize, String> {
+    let file = File::create(zip_path).map_err(|err| format!("Failed to create {}: {}", zip_path.display(), err))?;
+    let mut zip = ZipWriter::new(file);
+    // JPEG data doesn't get any smaller by compressing it again
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut used_names = HashSet::new();
+    let mut skipped = 0;
+    for image_file in image_files {
+        if is_cancelled.load(Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+        match prepare(image_file, max_edge) {
+            Ok(data) => {
+                zip.start_file(entry_name(image_file, &mut used_names), options)
+                    .and_then(|_| zip.write_all(&data).map_err(zip::result::ZipError::from))
+                    .map_err(|err| format!("Failed to write {}: {}", zip_path.display(), err))?;
+            }
+            Err(err) => {
+                log::error!("Skipping {}: {}", image_file.display(), err);
+                skipped += 1;
+            }
+        }
+        exported_count.fetch_add(1, Ordering::Relaxed);
+    }
+    zip.finish().map_err(|err| format!("Failed to write {}: {}", zip_path.display(), err))?;
+    Ok(skipped)
+}
+
+/// Write downscaled copies of the images without metadata into a zip file, the usual preparation for
+/// sending photos by email. This runs in the background with a progress dialog that can cancel it.
+pub fn start(image_files: Vec<PathBuf>, max_edge: u32, zip_path: PathBuf) {
+    let total = image_files.len();
+    let exported_count = Arc::new(AtomicUsize::new(0));
+    let is_cancelled = Arc::new(AtomicBool::new(false));
+    let result: Arc<Mutex<Option<Result<usize, String>>>> = Arc::new(Mutex::new(None));
+
+    let mut progress_dialog = ProgressDialog::show("Preparing for sharing", total.max(1) as f64, &is_cancelled);
+    progress_dialog.set_status(&format!("0 of {} images", total));
+
+    let thread_count = Arc::clone(&exported_count);
+    let thread_cancelled = Arc::clone(&is_cancelled);
+    let thread_result = Arc::clone(&result);
+    let thread_zip_path = zip_path.clone();
+    thread::spawn(move || {
+        let outcome = write_zip(&image_files, max_edge, &thread_zip_path, &thread_count, &thread_cancelled);
+        if outcome.is_err() {
+            let _ = std::fs::remove_file(&thread_zip_path);
+        }
+        if let Ok(mut result) = thread_result.lock() {
+            *result = Some(outcome);
+        }
+    });
+
+    app::add_timeout3(PROGRESS_INTERVAL, move |handle| {
+        let count = exported_count.load(Ordering::Relaxed);
+        progress_dialog.set_progress(count as f64, "");
+        progress_dialog.set_status(&format!("{} of {} images", count, total));
+        let outcome = result.lock().ok().and_then(|mut result| result.take());
+        match outcome {
+            None => app::repeat_timeout3(PROGRESS_INTERVAL, handle),
+            Some(outcome) => {
+                progress_dialog.hide();
+                match outcome {
+                    Ok(0) => dialog::message_default(&format!("Saved {} images to {}", total, zip_path.display())),
+                    Ok(skipped) => dialog::alert_default(&format!("Saved {} images to {}, {} could not be converted", total - skipped, zip_path.display(), skipped)),
+                    Err(err) if is_cancelled.load(Ordering::Relaxed) => log::debug!("Preparing for sharing cancelled: {}", err),
+                    Err(err) => dialog::alert_default(&format!("Preparing for sharing failed: {}", err)),
+                }
+            }
+        }
+    });
+}
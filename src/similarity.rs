@@ -0,0 +1,121 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::Hasher,
+    io,
+    path::Path,
+};
+
+use image::{imageops, GrayImage, RgbaImage};
+
+use crate::mapped;
+
+// Hashes at most this far apart count as near-duplicates
+const NEAR_DUPLICATE_DISTANCE: u32 = 4;
+
+// Every bit of the hash tells whether a pixel of the 9x8 thumbnail is brighter than its right neighbor
+fn difference_hash(thumbnail: &GrayImage) -> u64 {
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if thumbnail.get_pixel(x, y)[0] > thumbnail.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// Difference hash of an image: every bit tells whether a pixel of a 9x8 grayscale thumbnail is
+/// brighter than its right neighbor. Resizing, recompression and small edits barely change it.
+pub fn perceptual_hash(image: &RgbaImage) -> u64 {
+    difference_hash(&imageops::grayscale(&imageops::resize(image, 9, 8, imageops::FilterType::Triangle)))
+}
+
+/// The same hash as `perceptual_hash`, for grayscale images
+pub fn perceptual_hash_gray(image: &GrayImage) -> u64 {
+    difference_hash(&imageops::resize(image, 9, 8, imageops::FilterType::Triangle))
+}
+
+/// Number of differing bits between two hashes, 0 means the images look the same
+pub fn hash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Hash of the file contents, equal for byte-identical copies
+pub fn content_hash(path: &Path) -> io::Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&mapped::map(path)?);
+    Ok(hasher.finish())
+}
+
+/// Group exact copies and near-duplicates, given the (content hash, perceptual hash) of every image.
+/// Returns the groups with more than one image as indices into `hashes`, each sorted.
+pub fn duplicate_groups(hashes: &[(u64, u64)]) -> Vec<Vec<usize>> {
+    // Union-find over all pairs of images that are duplicates of each other
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+    for a in 0..hashes.len() {
+        for b in a + 1..hashes.len() {
+            let (content_a, perceptual_a) = hashes[a];
+            let (content_b, perceptual_b) = hashes[b];
+            if content_a == content_b || hash_distance(perceptual_a, perceptual_b) <= NEAR_DUPLICATE_DISTANCE {
+                let (root_a, root_b) = (root(&mut parents, a), root(&mut parents, b));
+                parents[root_b] = root_a;
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of_root = HashMap::new();
+    for index in 0..hashes.len() {
+        let group_root = root(&mut parents, index);
+        let group = *group_of_root.entry(group_root).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(index);
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_copies_are_grouped_whatever_they_look_like() {
+        assert_eq!(duplicate_groups(&[(1, 0), (1, u64::MAX)]), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn near_duplicates_are_grouped_up_to_the_distance() {
+        assert_eq!(duplicate_groups(&[(1, 0), (2, 0b1111)]), vec![vec![0, 1]]);
+        assert!(duplicate_groups(&[(1, 0), (2, 0b11111)]).is_empty());
+    }
+
+    #[test]
+    fn chains_of_near_duplicates_form_one_group() {
+        // The first two images are too far apart themselves, but both are close to the third
+        let hashes = [(1, 0), (2, 0xff), (3, 0b1111)];
+        assert_eq!(duplicate_groups(&hashes), vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn separate_groups_leave_out_unique_images() {
+        let hashes = [(1, 0), (2, u64::MAX), (3, 0x0f0f_0f0f_0000_0000), (4, 0b1), (5, u64::MAX ^ 0b11)];
+        assert_eq!(duplicate_groups(&hashes), vec![vec![0, 3], vec![1, 4]]);
+    }
+
+    #[test]
+    fn no_images_no_groups() {
+        assert!(duplicate_groups(&[]).is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+use image::RgbaImage;
+
+use crate::palette;
+
+/// Overall color and brightness figures of an image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageStatistics {
+    pub average_color: [u8; 3],
+    // Mean relative luminance from 0 (black) to 1 (white)
+    pub mean_luminance: f64,
+    // WCAG contrast ratio between the darkest and brightest percent of the image, from 1:1 to 21:1
+    pub contrast_ratio: f64,
+}
+
+// Relative luminance of an sRGB color as defined by WCAG
+fn relative_luminance(pixel: &image::Rgba<u8>) -> f64 {
+    let linear = |value: u8| {
+        let value = value as f64 / 255.0;
+        if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+    };
+    0.2126 * linear(pixel[0]) + 0.7152 * linear(pixel[1]) + 0.0722 * linear(pixel[2])
+}
+
+impl ImageStatistics {
+    /// Compute the statistics over all non-transparent pixels
+    pub fn compute(image: &RgbaImage) -> Option<ImageStatistics> {
+        let mut sums = [0f64; 3];
+        let mut luminances = Vec::new();
+        for pixel in image.pixels().filter(|pixel| pixel[3] > 0) {
+            for channel in 0..3 {
+                sums[channel] += pixel[channel] as f64;
+            }
+            luminances.push(relative_luminance(pixel));
+        }
+        if luminances.is_empty() {
+            return None;
+        }
+        let count = luminances.len() as f64;
+        let mean_luminance = luminances.iter().sum::<f64>() / count;
+
+        // Percentiles instead of the extremes, so a few hot or dead pixels don't decide the contrast
+        luminances.sort_by(|a, b| a.total_cmp(b));
+        let darkest = luminances[luminances.len() / 100];
+        let brightest = luminances[luminances.len() - 1 - luminances.len() / 100];
+        Some(ImageStatistics {
+            average_color: sums.map(|sum| (sum / count).round() as u8),
+            mean_luminance,
+            contrast_ratio: (brightest + 0.05) / (darkest + 0.05),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "Average color: {} (RGB {}, {}, {})\nMean luminance: {:.1}%\nContrast ratio: {:.1}:1",
+            palette::hex_code(self.average_color),
+            self.average_color[0], self.average_color[1], self.average_color[2],
+            self.mean_luminance * 100.0,
+            self.contrast_ratio,
+        )
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::PathBuf,
+    process,
+};
+
+use image::ImageFormat;
+
+/// The file name that stands for the image piped in, like `curl ... | lightningview -`
+pub const STDIN_ARGUMENT: &str = "-";
+
+// Start of FITS files, the first header keyword
+const FITS_SIGNATURE: &[u8] = b"SIMPLE  =";
+
+/// Extension the viewer opens the image data with, from the first bytes of it
+pub fn detect_extension(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(FITS_SIGNATURE) {
+        return Some("fits");
+    }
+    let start = String::from_utf8_lossy(&data[..data.len().min(1024)]).to_lowercase();
+    let start = start.trim_start();
+    if start.starts_with("<svg") || (start.starts_with("<?xml") && start.contains("<svg")) {
+        return Some("svg");
+    }
+    match image::guess_format(data).ok()? {
+        ImageFormat::Jpeg => Some("jpg"),
+        ImageFormat::Png => Some("png"),
+        ImageFormat::Gif => Some("gif"),
+        ImageFormat::WebP => Some("webp"),
+        ImageFormat::Tiff => Some("tif"),
+        ImageFormat::Bmp => Some("bmp"),
+        ImageFormat::Ico => Some("ico"),
+        ImageFormat::Pnm => Some("pnm"),
+        _ => None,
+    }
+}
+
+/// Temporary folder of this viewer process for files it makes itself, like the piped in image. Each
+/// purpose gets its own folder holding only those files, so browsing stays on them.
+pub fn session_dir(purpose: &str) -> PathBuf {
+    env::temp_dir().join(format!("lightningview-{}-{}", purpose, process::id()))
+}
+
+/// Remove the temporary folder of this viewer process for the purpose, with the files in it
+pub fn remove_session_dir(purpose: &str) {
+    let directory = session_dir(purpose);
+    if directory.exists() {
+        if let Err(err) = fs::remove_dir_all(&directory) {
+            log::error!("Failed to remove {}: {}", directory.display(), err);
+        }
+    }
+}
+
+/// Read the image piped in and save it to a file with the extension of its format, as everything
+/// in the viewer works on files. Remove it with `remove_file` when done.
+pub fn save_to_file() -> Result<PathBuf, String> {
+    let mut data = Vec::new();
+    io::stdin().read_to_end(&mut data).map_err(|err| format!("Failed to read the image from stdin: {}", err))?;
+    if data.is_empty() {
+        return Err("No image data on stdin".to_string());
+    }
+    let extension = detect_extension(&data).ok_or("The data on stdin is not an image format LightningView can open".to_string())?;
+    let directory = session_dir("stdin");
+    fs::create_dir_all(&directory).map_err(|err| format!("Failed to create {}: {}", directory.display(), err))?;
+    let path = directory.join(format!("stdin.{}", extension));
+    fs::write(&path, &data).map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+    log::debug!("Saved {} bytes from stdin to {}", data.len(), path.display());
+    Ok(path)
+}
+
+/// Remove the file the piped in image was saved to
+pub fn remove_file() {
+    remove_session_dir("stdin");
+}
@@ -0,0 +1,124 @@
+use std::{
+    env, fs, io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    path::{Path, PathBuf},
+};
+
+// Custom FLTK event sent to the main window to poll for view updates from other instances
+pub const SYNC_EVENT: i32 = 40;
+// How often to poll for view updates, in seconds
+pub const SYNC_POLL_INTERVAL: f64 = 0.05;
+// Zoom factors taken from other instances, the viewer doesn't zoom out beyond the fitted size
+const MIN_ZOOM_FACTOR: f64 = 1.0;
+const MAX_ZOOM_FACTOR: f64 = 100.0;
+
+/// Zoom and pan state shared between synchronized viewers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewState {
+    pub zoom_factor: f64,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl ViewState {
+    fn to_message(&self) -> String {
+        format!("{} {} {}", self.zoom_factor, self.x, self.y)
+    }
+
+    fn from_message(message: &str) -> Option<ViewState> {
+        let mut parts = message.split_whitespace();
+        // Any local process can send to the port, so only a zoom the viewer could have is taken over
+        let zoom_factor = parts.next()?.parse::<f64>().ok().filter(|zoom_factor| zoom_factor.is_finite())?;
+        let x = parts.next()?.parse().ok()?;
+        let y = parts.next()?.parse().ok()?;
+        Some(ViewState { zoom_factor: zoom_factor.clamp(MIN_ZOOM_FACTOR, MAX_ZOOM_FACTOR), x, y })
+    }
+}
+
+/// Broadcasts zoom/pan changes to all other instances that have view sync enabled.
+/// Every participating instance binds a UDP socket on localhost and announces its port
+/// by creating a file in a shared directory below the system temp dir.
+pub struct ViewSync {
+    socket: UdpSocket,
+    port_file: PathBuf,
+}
+
+fn registry_dir() -> PathBuf {
+    env::temp_dir().join("lightningview-sync")
+}
+
+// Whether the instance that registered the port is still there. Port files of instances that crashed stay
+// behind, and the port may have been taken by another program since.
+fn is_registered_instance(port: u16, port_file: &Path) -> bool {
+    // Nobody listens on a port that can be bound
+    if UdpSocket::bind((Ipv4Addr::LOCALHOST, port)).is_ok() {
+        return false;
+    }
+    #[cfg(target_os = "linux")]
+    if let Ok(pid) = fs::read_to_string(port_file) {
+        return Path::new("/proc").join(pid.trim()).exists();
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = port_file;
+    true
+}
+
+impl ViewSync {
+    pub fn new() -> io::Result<ViewSync> {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+        socket.set_nonblocking(true)?;
+        let port = socket.local_addr()?.port();
+
+        let dir = registry_dir();
+        fs::create_dir_all(&dir)?;
+        let port_file = dir.join(port.to_string());
+        fs::write(&port_file, std::process::id().to_string())?;
+        log::debug!("View sync listening on port {}", port);
+
+        Ok(ViewSync { socket, port_file })
+    }
+
+    /// Send the view state to all other registered instances
+    pub fn broadcast(&self, state: ViewState) {
+        let own_port = self.socket.local_addr().map(|addr| addr.port()).unwrap_or_default();
+        let message = state.to_message();
+        if let Ok(entries) = fs::read_dir(registry_dir()) {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let port = match entry.file_name().to_string_lossy().parse::<u16>() {
+                    Ok(port) => port,
+                    Err(_) => continue,
+                };
+                if port == own_port {
+                    continue;
+                }
+                if !is_registered_instance(port, &entry.path()) {
+                    log::debug!("Removing the stale view sync registration of port {}", port);
+                    let _ = fs::remove_file(entry.path());
+                    continue;
+                }
+                let target = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+                if let Err(err) = self.socket.send_to(message.as_bytes(), target) {
+                    log::debug!("Failed to send view state to port {}: {}", port, err);
+                }
+            }
+        }
+    }
+
+    /// Return the most recent view state received since the last poll, if any
+    pub fn poll(&self) -> Option<ViewState> {
+        let mut buffer = [0u8; 128];
+        let mut latest = None;
+        while let Ok((size, _)) = self.socket.recv_from(&mut buffer) {
+            if let Some(state) = ViewState::from_message(&String::from_utf8_lossy(&buffer[..size])) {
+                latest = Some(state);
+            }
+        }
+        latest
+    }
+}
+
+impl Drop for ViewSync {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.port_file);
+    }
+}
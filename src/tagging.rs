@@ -0,0 +1,78 @@
+use std::{fs, path::PathBuf};
+
+use image::{imageops, RgbaImage};
+use ort::{session::Session, value::Tensor};
+
+use crate::config;
+
+const MODEL_FILENAME: &str = "tagging.onnx";
+const LABELS_FILENAME: &str = "tagging_labels.txt";
+// Input size of the classification model
+const INPUT_SIZE: u32 = 224;
+// Tags below this probability are not suggested
+const MIN_PROBABILITY: f32 = 0.2;
+// ImageNet normalization, which most small classification models are trained with
+const MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+/// Suggests tags for images with a local classification model. The model (tagging.onnx) and its
+/// class names (tagging_labels.txt, one per line) are read from the config directory, so any
+/// classifier with a 1x3x224x224 input works, e.g. one fine-tuned on people/landscape/document/screenshot.
+pub struct Tagger {
+    session: Session,
+    labels: Vec<String>,
+}
+
+fn model_file(filename: &str) -> Result<PathBuf, String> {
+    config::config_dir()
+        .map(|dir| dir.join(filename))
+        .ok_or("No config directory found".to_string())
+}
+
+impl Tagger {
+    pub fn load() -> Result<Tagger, String> {
+        let model_path = model_file(MODEL_FILENAME)?;
+        let labels_path = model_file(LABELS_FILENAME)?;
+        let labels: Vec<String> = fs::read_to_string(&labels_path)
+            .map_err(|err| format!("Failed to read {}: {}", labels_path.display(), err))?
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+        let session = Session::builder()
+            .and_then(|builder| builder.commit_from_file(&model_path))
+            .map_err(|err| format!("Failed to load {}: {}", model_path.display(), err))?;
+        log::debug!("Loaded tagging model {} with {} labels", model_path.display(), labels.len());
+        Ok(Tagger { session, labels })
+    }
+
+    /// Tags with their probability, most likely first
+    pub fn suggest(&self, image: &RgbaImage) -> Result<Vec<(String, f32)>, String> {
+        let resized = imageops::resize(image, INPUT_SIZE, INPUT_SIZE, imageops::FilterType::Triangle);
+        let plane = (INPUT_SIZE * INPUT_SIZE) as usize;
+        let mut input = vec![0f32; 3 * plane];
+        for (index, pixel) in resized.pixels().enumerate() {
+            for channel in 0..3 {
+                input[channel * plane + index] = (pixel[channel] as f32 / 255.0 - MEAN[channel]) / STD[channel];
+            }
+        }
+        let tensor = Tensor::from_array(([1usize, 3, INPUT_SIZE as usize, INPUT_SIZE as usize], input.into_boxed_slice()))
+            .map_err(|err| format!("Failed to create the model input: {}", err))?;
+        let outputs = self.session.run(ort::inputs![tensor].map_err(|err| err.to_string())?)
+            .map_err(|err| format!("Running the tagging model failed: {}", err))?;
+        let logits = outputs[0].try_extract_tensor::<f32>()
+            .map_err(|err| format!("Unexpected tagging model output: {}", err))?;
+
+        // Softmax over the class scores
+        let max = logits.iter().copied().fold(f32::MIN, f32::max);
+        let exponentials: Vec<f32> = logits.iter().map(|logit| (logit - max).exp()).collect();
+        let sum: f32 = exponentials.iter().sum();
+        let mut tags: Vec<(String, f32)> = exponentials.iter()
+            .zip(self.labels.iter())
+            .map(|(exponential, label)| (label.clone(), exponential / sum))
+            .filter(|(_, probability)| *probability >= MIN_PROBABILITY)
+            .collect();
+        tags.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        Ok(tags)
+    }
+}
@@ -0,0 +1,63 @@
+/// Output size an image is selected for, like a screen resolution or a social media format
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Target {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Common output formats, with the name shown in the menu
+pub const PRESETS: [(&str, Target); 7] = [
+    ("Full HD 1920x1080", Target { width: 1920, height: 1080 }),
+    ("4K UHD 3840x2160", Target { width: 3840, height: 2160 }),
+    ("Instagram portrait 4:5", Target { width: 1080, height: 1350 }),
+    ("Instagram square 1:1", Target { width: 1080, height: 1080 }),
+    ("Story 9:16", Target { width: 1080, height: 1920 }),
+    ("Print 10x15 at 300 dpi", Target { width: 1800, height: 1200 }),
+    ("A4 at 300 dpi", Target { width: 3508, height: 2480 }),
+];
+
+// Share of the target that titles and important content should stay within
+const SAFE_AREA: f64 = 0.9;
+
+impl Target {
+    /// Parse a custom target like "1200x628"
+    pub fn parse(text: &str) -> Option<Target> {
+        let (width, height) = text.trim().split_once(['x', 'X', '×'])?;
+        let target = Target { width: width.trim().parse().ok()?, height: height.trim().parse().ok()? };
+        if target.width > 0 && target.height > 0 { Some(target) } else { None }
+    }
+
+    /// Part of an image of the given size that remains when it's cropped to fill the target,
+    /// as (x, y, width, height) in the same units as the size
+    pub fn crop(&self, image_width: f64, image_height: f64) -> (f64, f64, f64, f64) {
+        let aspect = self.width as f64 / self.height as f64;
+        if image_width / image_height > aspect {
+            let width = image_height * aspect;
+            ((image_width - width) / 2.0, 0.0, width, image_height)
+        } else {
+            let height = image_width / aspect;
+            (0.0, (image_height - height) / 2.0, image_width, height)
+        }
+    }
+
+    /// The middle of the crop that important content should stay within
+    pub fn safe_area(crop: (f64, f64, f64, f64)) -> (f64, f64, f64, f64) {
+        let (x, y, width, height) = crop;
+        let margin_x = width * (1.0 - SAFE_AREA) / 2.0;
+        let margin_y = height * (1.0 - SAFE_AREA) / 2.0;
+        (x + margin_x, y + margin_y, width - 2.0 * margin_x, height - 2.0 * margin_y)
+    }
+
+    /// How much of the image is cropped away and how it's scaled, with a warning if it's too small
+    pub fn describe(&self, image_width: u32, image_height: u32) -> String {
+        let (_, _, crop_width, crop_height) = self.crop(image_width as f64, image_height as f64);
+        let cropped = 1.0 - (crop_width * crop_height) / (image_width as f64 * image_height as f64);
+        let scale = self.width as f64 / crop_width;
+        let scaling = if scale > 1.0 {
+            format!("needs upscaling to {:.0}%, the image is too small", scale * 100.0)
+        } else {
+            format!("scaled to {:.0}%", scale * 100.0)
+        };
+        format!("{}x{}: {:.0}% cropped, {}", self.width, self.height, cropped * 100.0, scaling)
+    }
+}
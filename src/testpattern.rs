@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+
+use image::{Rgb, RgbImage};
+
+// Test patterns are browsed like files, using these virtual paths
+const TEST_PATTERN_PREFIX: &str = "testpattern:";
+
+pub const TEST_PATTERNS: [&str; 11] = [
+    "white", "black", "red", "green", "blue",
+    "gray-gradient", "color-gradients", "gray-steps",
+    "checkerboard", "line-pairs", "grid",
+];
+
+/// Virtual paths for all test patterns, in the order they are browsed
+pub fn test_pattern_paths() -> Vec<PathBuf> {
+    TEST_PATTERNS.iter().map(|name| PathBuf::from(format!("{}{}", TEST_PATTERN_PREFIX, name))).collect()
+}
+
+/// The name of the test pattern if the path is a virtual test pattern path
+pub fn test_pattern_name(path: &Path) -> Option<String> {
+    path.to_str()
+        .and_then(|path| path.strip_prefix(TEST_PATTERN_PREFIX))
+        .map(|name| name.to_string())
+}
+
+fn solid(width: u32, height: u32, color: [u8; 3]) -> RgbImage {
+    RgbImage::from_pixel(width, height, Rgb(color))
+}
+
+/// Generate the named test pattern at the given size
+pub fn generate(name: &str, width: u32, height: u32) -> Option<RgbImage> {
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let ramp = |x: u32| (x * 255 / (width - 1).max(1)) as u8;
+    let image = match name {
+        // Full color fills make dead and stuck pixels stand out
+        "white" => solid(width, height, [255, 255, 255]),
+        "black" => solid(width, height, [0, 0, 0]),
+        "red" => solid(width, height, [255, 0, 0]),
+        "green" => solid(width, height, [0, 255, 0]),
+        "blue" => solid(width, height, [0, 0, 255]),
+        // Smooth gradients show banding and color casts
+        "gray-gradient" => RgbImage::from_fn(width, height, |x, _| {
+            let value = ramp(x);
+            Rgb([value, value, value])
+        }),
+        "color-gradients" => RgbImage::from_fn(width, height, |x, y| {
+            let value = ramp(x);
+            match y * 4 / height {
+                0 => Rgb([value, 0, 0]),
+                1 => Rgb([0, value, 0]),
+                2 => Rgb([0, 0, value]),
+                _ => Rgb([value, value, value]),
+            }
+        }),
+        // Discrete steps, where neighboring near-black and near-white steps should still be distinguishable
+        "gray-steps" => RgbImage::from_fn(width, height, |x, y| {
+            let steps = 32;
+            let step = (x * steps / width) as u8;
+            let value = if y < height / 2 { step } else { 255 - (steps as u8 - 1) + step };
+            Rgb([value, value, value])
+        }),
+        // Single pixel patterns for checking sharpness and scaling
+        "checkerboard" => RgbImage::from_fn(width, height, |x, y| {
+            if (x + y) % 2 == 0 { Rgb([255, 255, 255]) } else { Rgb([0, 0, 0]) }
+        }),
+        "line-pairs" => RgbImage::from_fn(width, height, |x, y| {
+            let is_white = if x < width / 2 { x % 2 == 0 } else { y % 2 == 0 };
+            if is_white { Rgb([255, 255, 255]) } else { Rgb([0, 0, 0]) }
+        }),
+        // Geometry and overscan check
+        "grid" => RgbImage::from_fn(width, height, |x, y| {
+            if x % 64 == 0 || y % 64 == 0 || x == width - 1 || y == height - 1 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        }),
+        _ => return None,
+    };
+    Some(image)
+}
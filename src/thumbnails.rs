@@ -0,0 +1,186 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use image::{imageops, RgbaImage};
+
+// Thumbnail sizes of the freedesktop thumbnail spec and their folders, smallest first
+const FLAVORS: [(u32, &str); 4] = [(128, "normal"), (256, "large"), (512, "x-large"), (1024, "xx-large")];
+// Folder for markers of images that couldn't be thumbnailed, so they aren't tried again
+const FAIL_FOLDER: &str = concat!("fail/lightningview-", env!("CARGO_PKG_VERSION"));
+// Characters kept as they are in file URIs, like GLib does, so other programs find the same thumbnails
+const URI_SAFE_CHARACTERS: &[u8] = b"-_.!~*'()/:@&=+$,";
+
+/// Folder of the thumbnail cache. On Linux and other freedesktop systems it's the one shared
+/// with file managers and other viewers, elsewhere it's LightningView's own.
+fn cache_dir() -> Option<PathBuf> {
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        dirs::cache_dir().map(|dir| dir.join("thumbnails"))
+    } else {
+        dirs::cache_dir().map(|dir| dir.join("lightningview").join("thumbnails"))
+    }
+}
+
+// The file:// URI of the image, which identifies its thumbnails
+fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from(if path.starts_with('/') { "file://" } else { "file:///" });
+    for &byte in path.as_bytes() {
+        if byte.is_ascii_alphanumeric() || URI_SAFE_CHARACTERS.contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+// Modification time in seconds, a thumbnail is outdated once it differs
+fn modification_time(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+// Whether the PNG file is a thumbnail of the image as it is now
+fn is_current(thumbnail_file: &Path, uri: &str, mtime: u64) -> bool {
+    let Ok(file) = File::open(thumbnail_file) else {
+        return false;
+    };
+    let Ok(reader) = png::Decoder::new(file).read_info() else {
+        return false;
+    };
+    let text = &reader.info().uncompressed_latin1_text;
+    let value = |keyword: &str| text.iter().find(|chunk| chunk.keyword == keyword).map(|chunk| chunk.text.as_str());
+    value("Thumb::URI") == Some(uri) && value("Thumb::MTime") == Some(mtime.to_string().as_str())
+}
+
+// Write the PNG with the spec's attributes, through a temporary file so other programs never see half of it
+fn save(thumbnail_file: &Path, image: &RgbaImage, uri: &str, mtime: u64) -> Result<(), String> {
+    let folder = thumbnail_file.parent().ok_or("Invalid thumbnail path".to_string())?;
+    create_private_dir(folder).map_err(|err| format!("Failed to create {}: {}", folder.display(), err))?;
+    let temporary_file = thumbnail_file.with_extension(format!("{}.tmp", std::process::id()));
+    let write = || -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(&temporary_file)?), image.width(), image.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.add_text_chunk("Thumb::URI".to_string(), uri.to_string())?;
+        encoder.add_text_chunk("Thumb::MTime".to_string(), mtime.to_string())?;
+        encoder.add_text_chunk("Software".to_string(), "LightningView".to_string())?;
+        encoder.write_header()?.write_image_data(image.as_raw())
+    };
+    if let Err(err) = write() {
+        let _ = fs::remove_file(&temporary_file);
+        return Err(format!("Failed to write {}: {}", thumbnail_file.display(), err));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(&temporary_file, fs::Permissions::from_mode(0o600));
+    }
+    fs::rename(&temporary_file, thumbnail_file).map_err(|err| format!("Failed to write {}: {}", thumbnail_file.display(), err))
+}
+
+// The spec asks for folders only the user can read, as thumbnails show what's in private files
+#[cfg(unix)]
+fn create_private_dir(folder: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(folder)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(folder: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(folder)
+}
+
+// The absolute path, URI, thumbnail file name and modification time that identify the thumbnails of the image
+fn identify(path: &Path) -> Result<(PathBuf, String, String, u64), String> {
+    // Thumbnails are found by the absolute path, without ./ and ../ in it so it's the one file managers use
+    let path = match fs::canonicalize(path) {
+        Ok(canonical) => {
+            // Windows gives the \\?\ form, which isn't part of the path as other programs see it
+            let plain = canonical.to_string_lossy().strip_prefix(r"\\?\").map(PathBuf::from);
+            plain.unwrap_or(canonical)
+        }
+        Err(_) => env::current_dir().map(|dir| dir.join(path)).unwrap_or_else(|_| path.to_path_buf()),
+    };
+    let uri = file_uri(&path);
+    let name = format!("{:x}.png", md5::compute(uri.as_bytes()));
+    let mtime = modification_time(&path).ok_or(format!("Failed to read the modification time of {}", path.display()))?;
+    Ok((path, uri, name, mtime))
+}
+
+/// The largest current thumbnail of the image in the cache, made by LightningView or another program.
+/// Never decodes the image itself.
+pub fn cached(path: &Path) -> Option<RgbaImage> {
+    let (_, uri, name, mtime) = identify(path).ok()?;
+    let cache = cache_dir()?;
+    FLAVORS.iter().rev()
+        .map(|(_, flavor)| cache.join(flavor).join(&name))
+        .filter(|thumbnail_file| is_current(thumbnail_file, &uri, mtime))
+        .find_map(|thumbnail_file| image::open(thumbnail_file).ok())
+        .map(|thumbnail| thumbnail.into_rgba8())
+}
+
+// Size and name of the cache flavor thumbnails of the size are made from
+fn flavor_for(size: u32) -> (u32, &'static str) {
+    FLAVORS.iter().copied().find(|&(flavor_size, _)| flavor_size >= size).unwrap_or(FLAVORS[FLAVORS.len() - 1])
+}
+
+/// Size of the thumbnail stored in the cache for thumbnails of the size, which `decode` of
+/// `get_or_create` should decode at least
+pub fn stored_size(size: u32) -> u32 {
+    flavor_for(size).0
+}
+
+/// Thumbnail of the image at most `size` pixels wide and high, from the cache if it has a current
+/// one, otherwise made from the image decoded by `decode` and stored in the cache
+pub fn get_or_create(path: &Path, size: u32, decode: impl FnOnce(&Path) -> Result<RgbaImage, String>) -> Result<RgbaImage, String> {
+    let (flavor_size, flavor) = flavor_for(size);
+    let (path, uri, name, mtime) = identify(path)?;
+    let cache = cache_dir();
+
+    if let Some(cache) = &cache {
+        let thumbnail_file = cache.join(flavor).join(&name);
+        if is_current(&thumbnail_file, &uri, mtime) {
+            if let Ok(thumbnail) = image::open(&thumbnail_file) {
+                return Ok(fit(thumbnail.into_rgba8(), size));
+            }
+        }
+        if is_current(&cache.join(FAIL_FOLDER).join(&name), &uri, mtime) {
+            return Err(format!("{} could not be thumbnailed before", path.display()));
+        }
+    }
+
+    let image = match decode(&path) {
+        Ok(image) => image,
+        Err(err) => {
+            if let Some(cache) = &cache {
+                // An empty thumbnail in the fail folder marks the image, as the spec describes
+                if let Err(err) = save(&cache.join(FAIL_FOLDER).join(&name), &RgbaImage::new(1, 1), &uri, mtime) {
+                    log::debug!("{}", err);
+                }
+            }
+            return Err(err);
+        }
+    };
+    let thumbnail = fit(image, flavor_size);
+    if let Some(cache) = &cache {
+        if let Err(err) = save(&cache.join(flavor).join(&name), &thumbnail, &uri, mtime) {
+            log::debug!("{}", err);
+        }
+    }
+    Ok(fit(thumbnail, size))
+}
+
+// Shrink the image to fit into a square of the size, smaller images stay as they are
+fn fit(image: RgbaImage, size: u32) -> RgbaImage {
+    if image.width() <= size && image.height() <= size {
+        return image;
+    }
+    let scale = size as f64 / image.width().max(image.height()) as f64;
+    imageops::thumbnail(&image, ((image.width() as f64 * scale).round() as u32).max(1), ((image.height() as f64 * scale).round() as u32).max(1))
+}
@@ -0,0 +1,28 @@
+use image::{imageops, Rgba, RgbaImage};
+
+// Number of copies in each direction
+const TILE_COUNT: u32 = 3;
+const SEAM_COLOR: Rgba<u8> = Rgba([255, 0, 255, 255]);
+
+/// Repeat the image 3x3 to check that a texture tiles without visible seams.
+/// Seam lines mark where the copies meet, which helps to find the edges that don't match.
+pub fn tile(image: &RgbaImage, is_showing_seams: bool) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let mut tiled = RgbaImage::new(width * TILE_COUNT, height * TILE_COUNT);
+    for row in 0..TILE_COUNT {
+        for column in 0..TILE_COUNT {
+            imageops::replace(&mut tiled, image, (column * width) as i64, (row * height) as i64);
+        }
+    }
+    if is_showing_seams {
+        for index in 1..TILE_COUNT {
+            for y in 0..tiled.height() {
+                tiled.put_pixel(index * width, y, SEAM_COLOR);
+            }
+            for x in 0..tiled.width() {
+                tiled.put_pixel(x, index * height, SEAM_COLOR);
+            }
+        }
+    }
+    tiled
+}
@@ -0,0 +1,85 @@
+use std::{cell::Cell, rc::Rc};
+
+use fltk::{app, button::Button, enums::{Color, FrameType}, group::{Pack, PackType}, prelude::*, window::Window};
+
+use crate::keymap::Action;
+
+// Custom FLTK event sent to the main window when a toolbar button was clicked
+pub const TOOLBAR_EVENT: i32 = 43;
+const BUTTON_WIDTH: i32 = 48;
+const BUTTON_HEIGHT: i32 = 32;
+// The toolbar appears when the mouse comes this close to the top of the window
+const SHOW_DISTANCE: i32 = 60;
+
+// Label, tooltip and action of each button, the labels starting with @ are FLTK's built-in symbols
+const BUTTONS: [(&str, &str, Action); 8] = [
+    ("@<", "Previous image", Action::Previous),
+    ("@>", "Next image", Action::Next),
+    ("@undo", "Rotate counter-clockwise", Action::RotateCounterClockwise),
+    ("@redo", "Rotate clockwise", Action::RotateClockwise),
+    ("Fit", "Scale to fit / 1:1", Action::ToggleScaleToFit),
+    ("Play", "Start or stop the slideshow", Action::ToggleSlideshow),
+    ("Full", "Toggle fullscreen", Action::ToggleFullscreen),
+    ("Del", "Delete image", Action::Delete),
+];
+
+/// A row of buttons at the top of the window for the most common actions, shown while the mouse is near it
+pub struct Toolbar {
+    group: Pack,
+    // Action of the clicked button, taken by the window's event handler
+    pending: Rc<Cell<Option<Action>>>,
+    pub is_enabled: bool,
+}
+
+impl Toolbar {
+    /// Add the toolbar to the window that is being built, hidden until it's enabled
+    pub fn new(wind: &Window, is_enabled: bool) -> Toolbar {
+        let pending = Rc::new(Cell::new(None));
+        let mut group = Pack::new(0, 0, BUTTON_WIDTH * BUTTONS.len() as i32, BUTTON_HEIGHT, None).with_type(PackType::Horizontal);
+        for (label, tooltip, action) in BUTTONS {
+            let mut button = Button::default().with_size(BUTTON_WIDTH, BUTTON_HEIGHT).with_label(label);
+            button.set_tooltip(tooltip);
+            button.set_frame(FrameType::FlatBox);
+            button.set_color(Color::from_rgb(48, 48, 48));
+            button.set_label_color(Color::White);
+            button.clear_visible_focus();
+            let button_pending = Rc::clone(&pending);
+            let button_wind = wind.clone();
+            button.set_callback(move |_| {
+                button_pending.set(Some(action));
+                let _ = app::handle(TOOLBAR_EVENT, &button_wind);
+            });
+        }
+        group.end();
+        group.hide();
+        Toolbar { group, pending, is_enabled }
+    }
+
+    /// The action of the button that was clicked, if any
+    pub fn take_action(&self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    /// Whether the position is on the visible toolbar, so clicks there go to its buttons
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.group.visible() && x >= self.group.x() && x < self.group.x() + self.group.w() && y >= self.group.y() && y < self.group.y() + self.group.h()
+    }
+
+    /// Show the toolbar centered at the top while the mouse is close to it, hide it otherwise.
+    /// Returns true if it appeared or disappeared.
+    pub fn update(&mut self, mouse_y: i32, window_width: i32) -> bool {
+        let is_shown = self.is_enabled && mouse_y < SHOW_DISTANCE;
+        if is_shown == self.group.visible() {
+            return false;
+        }
+        if is_shown {
+            // Resizing the window scales the toolbar, so it gets its size back whenever it's shown
+            let width = BUTTON_WIDTH * BUTTONS.len() as i32;
+            self.group.resize((window_width - width) / 2, 0, width, BUTTON_HEIGHT);
+            self.group.show();
+        } else {
+            self.group.hide();
+        }
+        true
+    }
+}
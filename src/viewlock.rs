@@ -0,0 +1,45 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Keeps zoom and pan while flipping through a sequence like a timelapse, with a nudge per image
+/// to register frames that are slightly shifted against each other
+#[derive(Default)]
+pub struct ViewLock {
+    pub is_locked: bool,
+    // Shift of each image in image pixels, for the session
+    nudges: HashMap<PathBuf, (i32, i32)>,
+    // Nudge included in the position of the image being shown
+    current_nudge: (i32, i32),
+}
+
+impl ViewLock {
+    pub fn toggle(&mut self) {
+        self.is_locked = !self.is_locked;
+        // The image being shown was placed without its nudge
+        self.current_nudge = (0, 0);
+        log::debug!("View lock: {}", self.is_locked);
+    }
+
+    /// Nudge of the image being shown, in image pixels
+    pub fn current_nudge(&self) -> (i32, i32) {
+        self.current_nudge
+    }
+
+    /// Called when an image is shown, returns the nudge to place it with
+    pub fn show(&mut self, path: &Path) -> (i32, i32) {
+        self.current_nudge = if self.is_locked { self.nudges.get(path).copied().unwrap_or_default() } else { (0, 0) };
+        self.current_nudge
+    }
+
+    /// Shift the image being shown by some image pixels, returning its new nudge
+    pub fn nudge(&mut self, path: &Path, dx: i32, dy: i32) -> (i32, i32) {
+        let nudge = self.nudges.entry(path.to_path_buf()).or_default();
+        nudge.0 += dx;
+        nudge.1 += dy;
+        self.current_nudge = *nudge;
+        log::debug!("Nudged {} to {:?}", path.display(), nudge);
+        *nudge
+    }
+}
@@ -0,0 +1,29 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use nokhwa::{
+    pixel_format::RgbFormat,
+    utils::{CameraIndex, RequestedFormat, RequestedFormatType},
+    Camera,
+};
+
+/// Grab a single frame at the highest resolution from the first webcam and save it as PNG in the directory
+pub fn snapshot(directory: &Path) -> Result<PathBuf, String> {
+    let format = RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestResolution);
+    let mut camera = Camera::new(CameraIndex::Index(0), format).map_err(|err| format!("Failed to open the webcam: {}", err))?;
+    camera.open_stream().map_err(|err| format!("Failed to start the webcam: {}", err))?;
+    let frame = camera.frame().map_err(|err| format!("Failed to grab a frame: {}", err));
+    let _ = camera.stop_stream();
+    let decoded = frame?.decode_image::<RgbFormat>().map_err(|err| format!("Failed to decode the frame: {}", err))?;
+
+    // nokhwa has its own version of the image crate, so go through the raw pixels
+    let (width, height) = (decoded.width(), decoded.height());
+    let image = image::RgbImage::from_raw(width, height, decoded.into_raw()).ok_or("Unexpected webcam frame size".to_string())?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_millis()).unwrap_or_default();
+    let path = directory.join(format!("webcam-{}.png", timestamp));
+    image.save(&path).map_err(|err| format!("Failed to save {}: {}", path.display(), err))?;
+    log::debug!("Saved {}x{} webcam snapshot to {}", width, height, path.display());
+    Ok(path)
+}
@@ -3,7 +3,9 @@ use const_format::concatcp;
 use std::{
     error::Error,
     io,
-    path::PathBuf,
+    iter::once,
+    os::windows::ffi::OsStrExt,
+    path::{Path, PathBuf},
 };
 use winreg::{enums::*, RegKey};
 
@@ -200,4 +202,111 @@ fn get_exe_relative_path(filename: &str) -> io::Result<PathBuf> {
     Ok(path)
 }
 
+/// Add a file or folder to the recent documents, which also shows it in the taskbar jump list
+pub fn add_to_recent_documents(path: &Path) {
+    use windows::Win32::UI::Shell::{SHAddToRecentDocs, SHARD_PATHW};
 
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(once(0)).collect();
+    unsafe {
+        SHAddToRecentDocs(SHARD_PATHW.0 as u32, Some(wide_path.as_ptr() as *const _));
+    }
+}
+
+/// Keep the window above all other windows, or stop doing so
+pub fn set_topmost(window: *mut std::ffi::c_void, is_topmost: bool) -> Result<(), String> {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::WindowsAndMessaging::{SetWindowPos, HWND_NOTOPMOST, HWND_TOPMOST, SWP_NOMOVE, SWP_NOSIZE};
+
+    let insert_after = if is_topmost { HWND_TOPMOST } else { HWND_NOTOPMOST };
+    unsafe {
+        SetWindowPos(HWND(window), insert_after, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE)
+            .map_err(|err| format!("Failed to change the window order: {}", err))
+    }
+}
+
+// Windows marks the mouse messages it generates from touch and pen input with this signature,
+// and sets the top bit of the low byte for touch
+const TOUCH_SIGNATURE: usize = 0xFF515700;
+const TOUCH_SIGNATURE_MASK: usize = 0xFFFFFF00;
+const TOUCH_FLAG: usize = 0x80;
+// Gesture ID of the pinch gesture and the flag of its first message, from winuser.h
+const GID_ZOOM: u32 = 3;
+const GF_BEGIN: u32 = 1;
+
+/// Whether the mouse message being handled was generated from a touch screen
+pub fn is_touch_event() -> bool {
+    use windows::Win32::UI::WindowsAndMessaging::GetMessageExtraInfo;
+
+    let info = unsafe { GetMessageExtraInfo() }.0 as usize;
+    info & TOUCH_SIGNATURE_MASK == TOUCH_SIGNATURE && info & TOUCH_FLAG != 0
+}
+
+/// A step of a pinch gesture: how much further apart the fingers are than before, and the point
+/// between them in screen coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pinch {
+    pub ratio: f64,
+    pub x: i32,
+    pub y: i32,
+}
+
+struct PinchState {
+    // Distance between the fingers at the previous step
+    distance: u64,
+    // Steps not handled by the window yet, combined
+    pending: Option<Pinch>,
+}
+
+// Filled in by the window procedure, which can't reach the window's event handler state
+static PINCH: std::sync::Mutex<PinchState> = std::sync::Mutex::new(PinchState { distance: 0, pending: None });
+
+unsafe extern "system" fn gesture_proc(
+    window: windows::Win32::Foundation::HWND,
+    message: u32,
+    wparam: windows::Win32::Foundation::WPARAM,
+    lparam: windows::Win32::Foundation::LPARAM,
+    _id: usize,
+    event: usize,
+) -> windows::Win32::Foundation::LRESULT {
+    use windows::Win32::Foundation::LRESULT;
+    use windows::Win32::UI::Input::Touch::{CloseGestureInfoHandle, GetGestureInfo, GESTUREINFO, HGESTUREINFO};
+    use windows::Win32::UI::Shell::DefSubclassProc;
+    use windows::Win32::UI::WindowsAndMessaging::WM_GESTURE;
+
+    if message == WM_GESTURE {
+        let handle = HGESTUREINFO(lparam.0 as *mut std::ffi::c_void);
+        let mut info = GESTUREINFO { cbSize: std::mem::size_of::<GESTUREINFO>() as u32, ..Default::default() };
+        if GetGestureInfo(handle, &mut info).is_ok() && info.dwID == GID_ZOOM {
+            if let Ok(mut pinch) = PINCH.lock() {
+                if info.dwFlags & GF_BEGIN == 0 && pinch.distance > 0 {
+                    let step = info.ullArguments as f64 / pinch.distance as f64;
+                    let ratio = pinch.pending.map(|pending| pending.ratio).unwrap_or(1.0) * step;
+                    pinch.pending = Some(Pinch { ratio, x: info.ptsLocation.x as i32, y: info.ptsLocation.y as i32 });
+                }
+                pinch.distance = info.ullArguments;
+            }
+            let _ = CloseGestureInfoHandle(handle);
+            let _ = fltk::app::handle_main(event as i32);
+            return LRESULT(0);
+        }
+    }
+    DefSubclassProc(window, message, wparam, lparam)
+}
+
+/// Send the custom event to the main window for every step of a pinch gesture on the window,
+/// the step is then taken with take_pinch
+pub fn watch_pinch(window: *mut std::ffi::c_void, event: i32) -> Result<(), String> {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::Shell::SetWindowSubclass;
+
+    if unsafe { SetWindowSubclass(HWND(window), Some(gesture_proc), 1, event as usize) }.as_bool() {
+        Ok(())
+    } else {
+        Err("Failed to watch the window for touch gestures".to_string())
+    }
+}
+
+/// The pinch steps since the last call, combined
+pub fn take_pinch() -> Option<Pinch> {
+    PINCH.lock().ok().and_then(|mut pinch| pinch.pending.take())
+}